@@ -0,0 +1,317 @@
+use super::Aabb2d;
+use core::ops::{Add, Div, Mul, Sub};
+
+/// Where a ray or segment first hits a box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit<N> {
+    pub point: [N; 2],
+    /// The normal of the face that was hit. Zero if the cast started inside the box.
+    pub normal: [N; 2],
+    /// How far along the cast the hit happened, as a multiple of the direction.
+    pub fraction: N,
+}
+
+/// When a moving box first touches another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfImpact<N> {
+    /// How far along the movement the impact happened, from 0 to 1.
+    pub fraction: N,
+    /// The normal of the face that was hit. Zero if the boxes started overlapping.
+    pub normal: [N; 2],
+}
+
+impl<N> Aabb2d<N>
+where
+    N: PartialEq
+        + Copy
+        + PartialOrd
+        + Eq
+        + Sub<Output = N>
+        + From<i64>
+        + Add<Output = N>
+        + Mul<Output = N>
+        + Div<Output = N>,
+{
+    /// Casts an infinite ray against the box.
+    /// The fraction of the hit is in multiples of `direction`.
+    pub fn ray_cast(&self, origin: [N; 2], direction: [N; 2]) -> Option<RayHit<N>> {
+        let (fraction, normal) = cast(self, origin, direction, None)?;
+        Some(RayHit {
+            point: point_along(origin, direction, fraction),
+            normal,
+            fraction,
+        })
+    }
+
+    /// Casts a segment against the box, returning a fraction from 0 at `start` to 1 at `end`.
+    pub fn segment_cast(&self, start: [N; 2], end: [N; 2]) -> Option<RayHit<N>> {
+        let delta = [end[0] - start[0], end[1] - start[1]];
+        let (fraction, normal) = cast(self, start, delta, Some(1.into()))?;
+        Some(RayHit {
+            point: point_along(start, delta, fraction),
+            normal,
+            fraction,
+        })
+    }
+
+    /// Sweeps the box by `delta`, returning when it first touches `other`.
+    /// Unlike `check_collision` this can't miss thin boxes when moving fast.
+    pub fn sweep(&self, delta: [N; 2], other: &Self) -> Option<TimeOfImpact<N>> {
+        // Grow the other box by this box's size, which reduces the sweep to a segment
+        // cast of this box's min corner.
+        let expanded = Aabb2d {
+            min: [
+                other.min[0] - (self.max[0] - self.min[0]),
+                other.min[1] - (self.max[1] - self.min[1]),
+            ],
+            max: other.max,
+        };
+
+        let (fraction, normal) = cast(&expanded, self.min, delta, Some(1.into()))?;
+        Some(TimeOfImpact { fraction, normal })
+    }
+
+    /// Sweeps the box by `delta` against all `others`, returning the index of the first one hit.
+    /// Ties go to the lowest index so the result is deterministic.
+    pub fn shape_cast(&self, delta: [N; 2], others: &[Self]) -> Option<(usize, TimeOfImpact<N>)> {
+        let mut first: Option<(usize, TimeOfImpact<N>)> = None;
+        for (i, other) in others.iter().enumerate() {
+            if let Some(hit) = self.sweep(delta, other) {
+                let is_first = match &first {
+                    Some((_, first)) => hit.fraction < first.fraction,
+                    None => true,
+                };
+
+                if is_first {
+                    first = Some((i, hit));
+                }
+            }
+        }
+
+        first
+    }
+}
+
+/// Returns `origin + delta * fraction`.
+fn point_along<N>(origin: [N; 2], delta: [N; 2], fraction: N) -> [N; 2]
+where
+    N: Copy + Add<Output = N> + Mul<Output = N>,
+{
+    [
+        origin[0] + delta[0] * fraction,
+        origin[1] + delta[1] * fraction,
+    ]
+}
+
+/// Casts from `origin` along `delta` using the slab method, returning the entry fraction and normal.
+/// Grazing an edge is not a hit, matching `check_collision`.
+fn cast<N>(
+    aabb: &Aabb2d<N>,
+    origin: [N; 2],
+    delta: [N; 2],
+    max_fraction: Option<N>,
+) -> Option<(N, [N; 2])>
+where
+    N: PartialEq + Copy + PartialOrd + Sub<Output = N> + From<i64> + Div<Output = N>,
+{
+    let zero: N = 0.into();
+
+    // The latest entry and earliest exit across both axes. None means the axis never limits them.
+    let mut enter: Option<(N, usize, N)> = None;
+    let mut exit: Option<N> = None;
+    for axis in 0..2 {
+        let (min, max, o, d) = (aabb.min[axis], aabb.max[axis], origin[axis], delta[axis]);
+        if d == zero {
+            if o <= min || o >= max {
+                return None;
+            }
+            continue;
+        }
+
+        let to_min = (min - o) / d;
+        let to_max = (max - o) / d;
+        let (near, far, normal) = if d > zero {
+            (to_min, to_max, (-1).into())
+        } else {
+            (to_max, to_min, 1.into())
+        };
+
+        if enter.is_none_or(|(t, _, _)| near > t) {
+            enter = Some((near, axis, normal));
+        }
+        if exit.is_none_or(|t| far < t) {
+            exit = Some(far);
+        }
+    }
+
+    let (enter, axis, direction) = match (enter, exit) {
+        (Some(enter), Some(exit)) if enter.0 < exit && exit > zero => enter,
+        // Only possible with a zero delta, where the origin is inside the box.
+        (None, None) => return Some((zero, [zero, zero])),
+        _ => return None,
+    };
+
+    // Started inside the box.
+    if enter < zero {
+        return Some((zero, [zero, zero]));
+    }
+
+    if let Some(max_fraction) = max_fraction {
+        if enter > max_fraction {
+            return None;
+        }
+    }
+
+    let mut normal = [zero, zero];
+    normal[axis] = direction;
+    Some((enter, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::DNum;
+
+    fn n(f: f32) -> DNum {
+        DNum::from_f32(f)
+    }
+
+    fn v(x: f32, y: f32) -> [DNum; 2] {
+        [n(x), n(y)]
+    }
+
+    fn aabb(min: [DNum; 2], max: [DNum; 2]) -> Aabb2d<DNum> {
+        Aabb2d { min, max }
+    }
+
+    fn wall() -> Aabb2d<DNum> {
+        aabb(v(10.0, -5.0), v(10.5, 5.0))
+    }
+
+    #[test]
+    fn ray_hits_near_face() {
+        let hit = wall().ray_cast(v(0.0, 0.0), v(2.0, 0.0));
+        assert_eq!(
+            Some(RayHit {
+                point: v(10.0, 0.0),
+                normal: v(-1.0, 0.0),
+                fraction: n(5.0),
+            }),
+            hit
+        );
+    }
+
+    #[test]
+    fn ray_hits_from_each_side() {
+        let b = aabb(v(-1.0, -1.0), v(1.0, 1.0));
+        let cases = [
+            (v(-4.0, 0.0), v(1.0, 0.0), v(-1.0, 0.0)),
+            (v(4.0, 0.0), v(-1.0, 0.0), v(1.0, 0.0)),
+            (v(0.0, -4.0), v(0.0, 1.0), v(0.0, -1.0)),
+            (v(0.0, 4.0), v(0.0, -1.0), v(0.0, 1.0)),
+        ];
+        for (origin, direction, normal) in cases {
+            let hit = b.ray_cast(origin, direction).unwrap();
+            assert_eq!(normal, hit.normal);
+            assert_eq!(n(3.0), hit.fraction);
+        }
+    }
+
+    #[test]
+    fn diagonal_ray_uses_last_face_entered() {
+        let b = aabb(v(2.0, 1.0), v(4.0, 4.0));
+        let hit = b.ray_cast(v(0.0, 0.0), v(1.0, 1.0)).unwrap();
+        assert_eq!(v(2.0, 2.0), hit.point);
+        assert_eq!(v(-1.0, 0.0), hit.normal);
+    }
+
+    #[test]
+    fn ray_misses() {
+        assert_eq!(None, wall().ray_cast(v(0.0, 0.0), v(-1.0, 0.0)));
+        assert_eq!(None, wall().ray_cast(v(0.0, 6.0), v(1.0, 0.0)));
+        assert_eq!(None, wall().ray_cast(v(0.0, 0.0), v(0.0, 0.0)));
+        // Grazing the top edge
+        assert_eq!(None, wall().ray_cast(v(0.0, 5.0), v(1.0, 0.0)));
+    }
+
+    #[test]
+    fn ray_starting_inside_hits_immediately() {
+        let b = aabb(v(-1.0, -1.0), v(1.0, 1.0));
+        let expected = Some(RayHit {
+            point: v(0.0, 0.0),
+            normal: v(0.0, 0.0),
+            fraction: n(0.0),
+        });
+        assert_eq!(expected, b.ray_cast(v(0.0, 0.0), v(1.0, 0.0)));
+        assert_eq!(expected, b.ray_cast(v(0.0, 0.0), v(0.0, 0.0)));
+    }
+
+    #[test]
+    fn segment_only_hits_within_length() {
+        let hit = wall().segment_cast(v(0.0, 0.0), v(20.0, 0.0)).unwrap();
+        assert_eq!(n(0.5), hit.fraction);
+        assert_eq!(v(10.0, 0.0), hit.point);
+
+        assert_eq!(None, wall().segment_cast(v(0.0, 0.0), v(9.0, 0.0)));
+    }
+
+    #[test]
+    fn fast_sweep_does_not_tunnel() {
+        let bullet = aabb(v(0.0, 0.0), v(1.0, 1.0));
+        let delta = v(100.0, 0.0);
+
+        // Moving the full distance in one step lands past the wall.
+        let moved = aabb(v(100.0, 0.0), v(101.0, 1.0));
+        assert_eq!(None, moved.check_collision(&wall()));
+
+        let hit = bullet.sweep(delta, &wall()).unwrap();
+        assert_eq!(v(-1.0, 0.0), hit.normal);
+        assert!((hit.fraction - n(0.09)).abs() <= DNum::EPSILON);
+    }
+
+    #[test]
+    fn sweep_misses_when_passing_by() {
+        let b = aabb(v(0.0, 6.0), v(1.0, 7.0));
+        assert_eq!(None, b.sweep(v(100.0, 0.0), &wall()));
+        assert_eq!(None, b.sweep(v(-100.0, 0.0), &wall()));
+    }
+
+    #[test]
+    fn sweep_starting_overlapped_is_immediate() {
+        let b = aabb(v(9.0, 0.0), v(11.0, 1.0));
+        let expected = Some(TimeOfImpact {
+            fraction: n(0.0),
+            normal: v(0.0, 0.0),
+        });
+        assert_eq!(expected, b.sweep(v(1.0, 0.0), &wall()));
+    }
+
+    #[test]
+    fn shape_cast_returns_first_hit() {
+        let b = aabb(v(0.0, 0.0), v(1.0, 1.0));
+        let others = [
+            aabb(v(20.0, -5.0), v(21.0, 5.0)),
+            aabb(v(0.0, 10.0), v(1.0, 11.0)),
+            wall(),
+            aabb(v(10.0, -1.0), v(11.0, 2.0)),
+        ];
+
+        let (index, hit) = b.shape_cast(v(30.0, 0.0), &others).unwrap();
+        assert_eq!(2, index);
+        assert_eq!(v(-1.0, 0.0), hit.normal);
+
+        assert_eq!(None, b.shape_cast(v(-30.0, 0.0), &others));
+    }
+
+    #[test]
+    fn works_with_integers() {
+        let b = Aabb2d::<i64> {
+            min: [10, 0],
+            max: [12, 10],
+        };
+        let hit = b.ray_cast([0, 5], [1, 0]).unwrap();
+        assert_eq!(10, hit.fraction);
+        assert_eq!([10, 5], hit.point);
+        assert_eq!([-1, 0], hit.normal);
+    }
+}
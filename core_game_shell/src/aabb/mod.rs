@@ -0,0 +1,175 @@
+mod cast;
+
+use crate::math::*;
+use core::ops::Sub;
+
+pub use cast::*;
+
+#[allow(dead_code)]
+type Aabb<N> = Aabb2d<N>;
+
+/// 2d representation of an AABB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb2d<N> {
+    pub min: [N; 2],
+    pub max: [N; 2],
+}
+impl<N> Aabb<N>
+where
+    N: PartialEq + Copy + PartialOrd + Eq + Sub<Output = N> + From<i64>,
+{
+    /// Checks the aabb for a collision.
+    pub fn check_collision(&self, other: &Self) -> Option<Manifold<N>> {
+        check_collision(self, &other)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Manifold<N> {
+    /// How far the boxes overlap along the normal.
+    pub depth: N,
+    /// The axis of minimum penetration, pointing from the first box towards the second.
+    pub normal: [N; 2],
+}
+
+fn check_collision<N>(a: &Aabb<N>, b: &Aabb<N>) -> Option<Manifold<N>>
+where
+    N: PartialEq + Copy + PartialOrd + Eq + Sub<Output = N> + From<i64>,
+{
+    let x_overlap: N = max(0.into(), min(a.max[0], b.max[0]) - max(a.min[0], b.min[0]));
+    let y_overlap: N = max(0.into(), min(a.max[1], b.max[1]) - max(a.min[1], b.min[1]));
+
+    if x_overlap > 0.into() && y_overlap > 0.into() {
+        // Separate along the axis that needs the least movement, preferring x on ties.
+        let axis = if y_overlap < x_overlap { 1 } else { 0 };
+        let depth = if axis == 0 { x_overlap } else { y_overlap };
+
+        // The center of b is below the center of a when b.min + b.max < a.min + a.max.
+        // Rearranged to only use subtraction. Identical centers push towards positive.
+        let direction = if b.min[axis] - a.min[axis] < a.max[axis] - b.max[axis] {
+            (-1).into()
+        } else {
+            1.into()
+        };
+
+        let mut normal = [0.into(), 0.into()];
+        normal[axis] = direction;
+        Some(Manifold { depth, normal })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_overlapping_aabbs_returns_none() {
+        let a = Aabb::<DNum> {
+            min: [0.into(), 0.into()],
+            max: [4.into(), 4.into()],
+        };
+
+        let b = Aabb {
+            min: [10.into(), 10.into()],
+            max: [14.into(), 14.into()],
+        };
+
+        let expected = None;
+        assert_eq!(expected, check_collision(&a, &b))
+    }
+    #[test]
+    fn overlapping_returns_a() {
+        let a = Aabb::<DNum> {
+            min: [0.into(), 0.into()],
+            max: [4.into(), 4.into()],
+        };
+
+        let b = Aabb {
+            min: [2.into(), 2.into()],
+            max: [5.into(), 5.into()],
+        };
+
+        let expected = Some(Manifold {
+            depth: 2.into(),
+            normal: [1.into(), 0.into()],
+        });
+        assert_eq!(expected, check_collision(&a, &b))
+    }
+
+    #[test]
+    fn same_aabbs_returns_overlapping() {
+        let a = Aabb::<DNum> {
+            min: [0.into(), 0.into()],
+            max: [4.into(), 4.into()],
+        };
+
+        let b = a;
+
+        let expected = Some(Manifold {
+            depth: 4.into(),
+            normal: [1.into(), 0.into()],
+        });
+        assert_eq!(expected, check_collision(&a, &b))
+    }
+
+    #[test]
+    fn normal_uses_axis_of_minimum_penetration() {
+        let a = Aabb::<DNum> {
+            min: [0.into(), 0.into()],
+            max: [10.into(), 10.into()],
+        };
+
+        let below = Aabb {
+            min: [2.into(), (-3).into()],
+            max: [8.into(), 1.into()],
+        };
+        let expected = Some(Manifold {
+            depth: 1.into(),
+            normal: [0.into(), (-1).into()],
+        });
+        assert_eq!(expected, check_collision(&a, &below));
+
+        let left = Aabb {
+            min: [(-4).into(), 2.into()],
+            max: [3.into(), 8.into()],
+        };
+        let expected = Some(Manifold {
+            depth: 3.into(),
+            normal: [(-1).into(), 0.into()],
+        });
+        assert_eq!(expected, check_collision(&a, &left));
+    }
+
+    #[test]
+    fn normal_flips_with_order() {
+        let a = Aabb::<DNum> {
+            min: [0.into(), 0.into()],
+            max: [4.into(), 4.into()],
+        };
+        let b = Aabb {
+            min: [3.into(), 1.into()],
+            max: [7.into(), 3.into()],
+        };
+
+        let ab = check_collision(&a, &b).unwrap();
+        let ba = check_collision(&b, &a).unwrap();
+        assert_eq!(ab.depth, ba.depth);
+        assert_eq!([DNum::ONE, DNum::ZERO], ab.normal);
+        assert_eq!([-DNum::ONE, DNum::ZERO], ba.normal);
+    }
+
+    #[test]
+    fn touching_aabbs_returns_none() {
+        let a = Aabb::<DNum> {
+            min: [0.into(), 0.into()],
+            max: [4.into(), 4.into()],
+        };
+        let b = Aabb {
+            min: [4.into(), 0.into()],
+            max: [8.into(), 4.into()],
+        };
+        assert_eq!(None, check_collision(&a, &b));
+    }
+}
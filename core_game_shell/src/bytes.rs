@@ -0,0 +1,158 @@
+use crate::math::hash::Fnv1a32;
+
+/// Something that little endian bytes can be written to.
+pub trait ByteWriter {
+    /// Writes the bytes.
+    fn write(&mut self, bytes: &[u8]);
+
+    fn write_u8(&mut self, n: u8) {
+        self.write(&[n]);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_f32(&mut self, n: f32) {
+        self.write(&n.to_le_bytes());
+    }
+}
+
+impl ByteWriter for Fnv1a32 {
+    fn write(&mut self, bytes: &[u8]) {
+        Fnv1a32::write(self, bytes)
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl ByteWriter for std::vec::Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes)
+    }
+}
+
+/// Writes bytes into a fixed size buffer.
+/// Panics if the buffer is too small, so callers should size it for the largest value written.
+pub struct SliceWriter<'a> {
+    bytes: &'a mut [u8],
+    position: usize,
+}
+impl<'a> SliceWriter<'a> {
+    /// Creates a new writer at the start of the buffer.
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Returns the number of bytes written.
+    pub fn len(&self) -> usize {
+        self.position
+    }
+}
+impl<'a> ByteWriter for SliceWriter<'a> {
+    fn write(&mut self, bytes: &[u8]) {
+        let end = self.position + bytes.len();
+        self.bytes[self.position..end].copy_from_slice(bytes);
+        self.position = end;
+    }
+}
+
+/// Reads little endian bytes from a slice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+impl<'a> ByteReader<'a> {
+    /// Creates a new reader at the start of the bytes.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Returns whether all bytes have been read.
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    /// Reads the given number of bytes.
+    pub fn read(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(len)?;
+        let bytes = self.bytes.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Option<u8> {
+        self.read(1).map(|b| b[0])
+    }
+
+    pub fn read_u16(&mut self) -> Option<u16> {
+        self.read(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Option<u32> {
+        self.read(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Option<u64> {
+        self.read(8).map(|b| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(b);
+            u64::from_le_bytes(bytes)
+        })
+    }
+
+    pub fn read_f32(&mut self) -> Option<f32> {
+        self.read_u32().map(f32::from_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut bytes = vec![];
+        bytes.write_u8(1);
+        bytes.write_u16(0xBEEF);
+        bytes.write_u32(0xDEAD_BEEF);
+        bytes.write_u64(0x0123_4567_89AB_CDEF);
+        bytes.write_f32(0.5);
+
+        let mut r = ByteReader::new(&bytes);
+        assert_eq!(Some(1), r.read_u8());
+        assert_eq!(Some(0xBEEF), r.read_u16());
+        assert_eq!(Some(0xDEAD_BEEF), r.read_u32());
+        assert_eq!(Some(0x0123_4567_89AB_CDEF), r.read_u64());
+        assert_eq!(Some(0.5), r.read_f32());
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn slice_writer_tracks_len() {
+        let mut buffer = [0; 4];
+        let mut w = SliceWriter::new(&mut buffer);
+        w.write_u8(1);
+        w.write_u16(0xBEEF);
+        assert_eq!(3, w.len());
+        assert_eq!([1, 0xEF, 0xBE, 0], buffer);
+    }
+
+    #[test]
+    fn read_past_end_returns_none() {
+        let mut r = ByteReader::new(&[1, 2, 3]);
+        assert_eq!(None, r.read_u32());
+        assert_eq!(Some(0x0201), r.read_u16());
+        assert_eq!(None, r.read_u16());
+        assert_eq!(Some(3), r.read_u8());
+    }
+}
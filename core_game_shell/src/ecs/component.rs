@@ -0,0 +1,219 @@
+use super::MAX_ENTITIES;
+use crate::{
+    bytes::{ByteReader, ByteWriter},
+    game::{PlayerId, MAX_PLAYERS},
+    math::DNum,
+};
+use core::fmt::Debug;
+
+/// Every kind of component, used by systems to declare what they access.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComponentKind {
+    Position,
+    Velocity,
+    Controller,
+}
+
+/// Something that can be attached to an entity.
+/// Components must be `Copy` so that the whole store can be copied for rollback.
+pub trait Component: Copy + Debug + PartialEq + 'static {
+    const KIND: ComponentKind;
+    /// The largest number of bytes `write_bytes` writes.
+    const MAX_SIZE: usize;
+
+    fn storage(components: &Components) -> &Storage<Self>;
+    fn storage_mut(components: &mut Components) -> &mut Storage<Self>;
+
+    /// Writes the component as bytes that are stable across platforms.
+    fn write_bytes<W: ByteWriter>(&self, writer: &mut W);
+    /// Reads a component that was written with `write_bytes`.
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self>;
+}
+
+/// The position of an entity, in world units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position(pub [DNum; 2]);
+
+/// How far an entity moves each tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity(pub [DNum; 2]);
+
+/// The player whose input drives an entity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Controller(pub PlayerId);
+
+fn write_vector<W: ByteWriter>(v: &[DNum; 2], writer: &mut W) {
+    for n in v {
+        writer.write_u64(n.inner() as u64);
+    }
+}
+
+fn read_vector(reader: &mut ByteReader) -> Option<[DNum; 2]> {
+    let x = DNum::from_raw(reader.read_u64()? as i64);
+    let y = DNum::from_raw(reader.read_u64()? as i64);
+    Some([x, y])
+}
+
+impl Component for Position {
+    const KIND: ComponentKind = ComponentKind::Position;
+    const MAX_SIZE: usize = 16;
+
+    fn storage(components: &Components) -> &Storage<Self> {
+        &components.positions
+    }
+    fn storage_mut(components: &mut Components) -> &mut Storage<Self> {
+        &mut components.positions
+    }
+
+    fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        write_vector(&self.0, writer)
+    }
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        read_vector(reader).map(Self)
+    }
+}
+
+impl Component for Velocity {
+    const KIND: ComponentKind = ComponentKind::Velocity;
+    const MAX_SIZE: usize = 16;
+
+    fn storage(components: &Components) -> &Storage<Self> {
+        &components.velocities
+    }
+    fn storage_mut(components: &mut Components) -> &mut Storage<Self> {
+        &mut components.velocities
+    }
+
+    fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        write_vector(&self.0, writer)
+    }
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        read_vector(reader).map(Self)
+    }
+}
+
+impl Component for Controller {
+    const KIND: ComponentKind = ComponentKind::Controller;
+    const MAX_SIZE: usize = 1;
+
+    fn storage(components: &Components) -> &Storage<Self> {
+        &components.controllers
+    }
+    fn storage_mut(components: &mut Components) -> &mut Storage<Self> {
+        &mut components.controllers
+    }
+
+    fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        writer.write_u8(self.0.index() as u8);
+    }
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let player = reader.read_u8()?;
+        if player as usize >= MAX_PLAYERS {
+            return None;
+        }
+        Some(Self(player.into()))
+    }
+}
+
+/// A pre-allocated array holding one component type, indexed by entity index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Storage<T> {
+    values: [Option<T>; MAX_ENTITIES],
+}
+impl<T> Storage<T>
+where
+    T: Component,
+{
+    /// The largest number of bytes `write_bytes` writes.
+    const MAX_SIZE: usize = 2 + MAX_ENTITIES * (2 + T::MAX_SIZE);
+
+    fn new() -> Self {
+        Self {
+            values: [None; MAX_ENTITIES],
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.values.get(index)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.values.get_mut(index)?.as_mut()
+    }
+
+    pub(crate) fn set(&mut self, index: usize, value: Option<T>) -> Option<T> {
+        core::mem::replace(&mut self.values[index], value)
+    }
+
+    /// Returns all components and the index they are stored at.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.as_ref().map(|v| (i, v)))
+    }
+
+    fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        writer.write_u16(self.iter().count() as u16);
+        for (index, value) in self.iter() {
+            writer.write_u16(index as u16);
+            value.write_bytes(writer);
+        }
+    }
+
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let mut storage = Self::new();
+        for _ in 0..reader.read_u16()? {
+            let index = reader.read_u16()? as usize;
+            if index >= MAX_ENTITIES {
+                return None;
+            }
+            storage.values[index] = Some(T::read_bytes(reader)?);
+        }
+
+        Some(storage)
+    }
+}
+
+/// Storage for every component type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Components {
+    positions: Storage<Position>,
+    velocities: Storage<Velocity>,
+    controllers: Storage<Controller>,
+}
+impl Components {
+    /// The largest number of bytes `write_bytes` writes.
+    pub const MAX_SIZE: usize = Storage::<Position>::MAX_SIZE
+        + Storage::<Velocity>::MAX_SIZE
+        + Storage::<Controller>::MAX_SIZE;
+
+    pub fn new() -> Self {
+        Self {
+            positions: Storage::new(),
+            velocities: Storage::new(),
+            controllers: Storage::new(),
+        }
+    }
+
+    /// Removes all components stored at the index.
+    pub(crate) fn clear(&mut self, index: usize) {
+        self.positions.set(index, None);
+        self.velocities.set(index, None);
+        self.controllers.set(index, None);
+    }
+
+    pub fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        self.positions.write_bytes(writer);
+        self.velocities.write_bytes(writer);
+        self.controllers.write_bytes(writer);
+    }
+
+    pub fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        Some(Self {
+            positions: Storage::read_bytes(reader)?,
+            velocities: Storage::read_bytes(reader)?,
+            controllers: Storage::read_bytes(reader)?,
+        })
+    }
+}
@@ -0,0 +1,167 @@
+use super::MAX_ENTITIES;
+
+/// Identifies an entity.
+/// The generation changes every time an index is reused, so stale ids never refer to a new entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntityId {
+    index: u16,
+    generation: u16,
+}
+impl EntityId {
+    /// Returns the index of the entity, which is also its index in component storage.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u16 {
+        self.generation
+    }
+}
+
+/// Allocates entity ids.
+/// The lowest free index is always used first, so allocation is deterministic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entities {
+    generations: [u16; MAX_ENTITIES],
+    alive: [bool; MAX_ENTITIES],
+    len: usize,
+}
+impl Entities {
+    /// Creates a new allocator with no entities.
+    pub fn new() -> Self {
+        Self {
+            generations: [0; MAX_ENTITIES],
+            alive: [false; MAX_ENTITIES],
+            len: 0,
+        }
+    }
+
+    /// Allocates a new entity. Returns `None` if `MAX_ENTITIES` are already alive.
+    pub fn spawn(&mut self) -> Option<EntityId> {
+        let index = self.alive.iter().position(|alive| !alive)?;
+        self.alive[index] = true;
+        self.len += 1;
+
+        Some(EntityId {
+            index: index as u16,
+            generation: self.generations[index],
+        })
+    }
+
+    /// Frees the entity. Returns false if it was not alive.
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        if !self.is_alive(id) {
+            return false;
+        }
+
+        self.alive[id.index()] = false;
+        self.generations[id.index()] = id.generation.wrapping_add(1);
+        self.len -= 1;
+        true
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        id.index() < MAX_ENTITIES
+            && self.alive[id.index()]
+            && self.generations[id.index()] == id.generation
+    }
+
+    /// Returns the entity alive at the given index, if any.
+    pub fn get(&self, index: usize) -> Option<EntityId> {
+        if *self.alive.get(index)? {
+            Some(EntityId {
+                index: index as u16,
+                generation: self.generations[index],
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the generation the next entity at the index will have, or the current one if alive.
+    pub(crate) fn generation(&self, index: usize) -> u16 {
+        self.generations[index]
+    }
+
+    /// Returns the number of alive entities.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns all alive entities, ordered by index.
+    pub fn iter(&self) -> impl Iterator<Item = EntityId> + '_ {
+        (0..MAX_ENTITIES).filter_map(move |i| self.get(i))
+    }
+
+    /// Restores an entity from its raw parts, used when reading serialized state.
+    pub(crate) fn restore(&mut self, index: usize, generation: u16, alive: bool) -> Option<()> {
+        if index >= MAX_ENTITIES {
+            return None;
+        }
+
+        if self.alive[index] != alive {
+            if alive {
+                self.len += 1;
+            } else {
+                self.len -= 1;
+            }
+        }
+        self.alive[index] = alive;
+        self.generations[index] = generation;
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_uses_lowest_free_index() {
+        let mut e = Entities::new();
+        let a = e.spawn().unwrap();
+        let b = e.spawn().unwrap();
+        let c = e.spawn().unwrap();
+        assert_eq!((0, 1, 2), (a.index(), b.index(), c.index()));
+
+        assert!(e.despawn(b));
+        let d = e.spawn().unwrap();
+        assert_eq!(1, d.index());
+        assert_eq!(3, e.len());
+    }
+
+    #[test]
+    fn stale_ids_are_not_alive() {
+        let mut e = Entities::new();
+        let a = e.spawn().unwrap();
+        e.despawn(a);
+        let b = e.spawn().unwrap();
+
+        assert_eq!(a.index(), b.index());
+        assert_ne!(a, b);
+        assert!(!e.is_alive(a));
+        assert!(e.is_alive(b));
+        assert!(!e.despawn(a));
+    }
+
+    #[test]
+    fn generation_wraps() {
+        let mut e = Entities::new();
+        let mut id = e.spawn().unwrap();
+        for _ in 0..=u16::MAX as u32 {
+            e.despawn(id);
+            id = e.spawn().unwrap();
+        }
+        assert_eq!(0, id.generation());
+    }
+
+    #[test]
+    fn spawn_fails_when_full() {
+        let mut e = Entities::new();
+        for _ in 0..MAX_ENTITIES {
+            assert!(e.spawn().is_some());
+        }
+        assert_eq!(None, e.spawn());
+        assert_eq!(MAX_ENTITIES, e.iter().count());
+    }
+}
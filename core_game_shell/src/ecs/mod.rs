@@ -0,0 +1,13 @@
+mod component;
+mod entity;
+mod registry;
+mod schedule;
+
+pub use component::*;
+pub use entity::*;
+pub use registry::*;
+pub use schedule::*;
+
+/// The maximum number of entities that can be alive at once.
+/// All component storage is allocated up front for this many.
+pub const MAX_ENTITIES: usize = 256;
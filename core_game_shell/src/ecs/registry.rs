@@ -0,0 +1,258 @@
+use super::{Component, Components, Entities, EntityId, MAX_ENTITIES};
+use crate::bytes::{ByteReader, ByteWriter};
+
+/// Holds all entities and their components.
+/// Everything is pre-allocated plain data, so copying a registry for rollback is a single memcpy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registry {
+    entities: Entities,
+    components: Components,
+}
+impl Registry {
+    /// The largest number of bytes `write_bytes` writes.
+    pub const MAX_SIZE: usize = MAX_ENTITIES * 3 + Components::MAX_SIZE;
+
+    /// Creates a new registry with no entities.
+    pub fn new() -> Self {
+        Self {
+            entities: Entities::new(),
+            components: Components::new(),
+        }
+    }
+
+    /// Creates a new entity without any components.
+    /// Returns `None` if `MAX_ENTITIES` are already alive.
+    pub fn spawn(&mut self) -> Option<EntityId> {
+        self.entities.spawn()
+    }
+
+    /// Removes the entity and all of its components. Returns false if it was not alive.
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        if !self.entities.despawn(id) {
+            return false;
+        }
+
+        self.components.clear(id.index());
+        true
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.entities.is_alive(id)
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    pub fn get<T: Component>(&self, id: EntityId) -> Option<&T> {
+        if !self.is_alive(id) {
+            return None;
+        }
+        T::storage(&self.components).get(id.index())
+    }
+
+    pub fn get_mut<T: Component>(&mut self, id: EntityId) -> Option<&mut T> {
+        if !self.is_alive(id) {
+            return None;
+        }
+        T::storage_mut(&mut self.components).get_mut(id.index())
+    }
+
+    /// Attaches the component to the entity, replacing any existing one.
+    /// Returns false if the entity is not alive.
+    pub fn insert<T: Component>(&mut self, id: EntityId, component: T) -> bool {
+        if !self.is_alive(id) {
+            return false;
+        }
+        T::storage_mut(&mut self.components).set(id.index(), Some(component));
+        true
+    }
+
+    /// Detaches the component from the entity, returning it.
+    pub fn remove<T: Component>(&mut self, id: EntityId) -> Option<T> {
+        if !self.is_alive(id) {
+            return None;
+        }
+        T::storage_mut(&mut self.components).set(id.index(), None)
+    }
+
+    /// Returns every entity with the component, ordered by index.
+    pub fn query<T: Component>(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        let entities = &self.entities;
+        T::storage(&self.components)
+            .iter()
+            .filter_map(move |(i, c)| entities.get(i).map(|id| (id, c)))
+    }
+
+    /// Calls `f` for every entity with both components, ordered by index.
+    /// `R` is copied out so that `W` can be borrowed mutably.
+    pub fn for_each<R: Component, W: Component, F>(&mut self, mut f: F)
+    where
+        F: FnMut(EntityId, R, &mut W),
+    {
+        for index in 0..MAX_ENTITIES {
+            let id = match self.entities.get(index) {
+                Some(id) => id,
+                None => continue,
+            };
+            let read = match R::storage(&self.components).get(index) {
+                Some(read) => *read,
+                None => continue,
+            };
+            if let Some(write) = W::storage_mut(&mut self.components).get_mut(index) {
+                f(id, read, write);
+            }
+        }
+    }
+
+    /// Writes the registry as bytes that are stable across platforms.
+    pub fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        // Generations are written for every index, as dead ones still matter for the next spawn.
+        for index in 0..MAX_ENTITIES {
+            let (generation, alive) = match self.entities.get(index) {
+                Some(id) => (id.generation(), true),
+                None => (self.entities.generation(index), false),
+            };
+            writer.write_u16(generation);
+            writer.write_u8(alive as u8);
+        }
+
+        self.components.write_bytes(writer);
+    }
+
+    /// Reads a registry that was written with `write_bytes`.
+    pub fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let mut registry = Self::new();
+        for index in 0..MAX_ENTITIES {
+            let generation = reader.read_u16()?;
+            let alive = match reader.read_u8()? {
+                0 => false,
+                1 => true,
+                _ => return None,
+            };
+            registry.entities.restore(index, generation, alive)?;
+        }
+
+        registry.components = Components::read_bytes(reader)?;
+        Some(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::{Controller, Position, Velocity},
+        math::DNum,
+    };
+
+    fn position(x: i64, y: i64) -> Position {
+        Position([x.into(), y.into()])
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let mut r = Registry::new();
+        let id = r.spawn().unwrap();
+        assert!(r.insert(id, position(1, 2)));
+
+        assert_eq!(Some(&position(1, 2)), r.get::<Position>(id));
+        assert_eq!(None, r.get::<Velocity>(id));
+
+        r.get_mut::<Position>(id).unwrap().0[0] = 5.into();
+        assert_eq!(Some(position(5, 2)), r.remove::<Position>(id));
+        assert_eq!(None, r.get::<Position>(id));
+    }
+
+    #[test]
+    fn despawn_clears_components() {
+        let mut r = Registry::new();
+        let a = r.spawn().unwrap();
+        r.insert(a, position(1, 2));
+        r.insert(a, Controller(3.into()));
+        assert!(r.despawn(a));
+
+        let b = r.spawn().unwrap();
+        assert_eq!(a.index(), b.index());
+        assert_eq!(None, r.get::<Position>(b));
+        assert_eq!(None, r.get::<Controller>(b));
+        assert_eq!(0, r.query::<Position>().count());
+    }
+
+    #[test]
+    fn stale_ids_do_not_access_new_entity() {
+        let mut r = Registry::new();
+        let a = r.spawn().unwrap();
+        r.despawn(a);
+        let b = r.spawn().unwrap();
+        r.insert(b, position(1, 1));
+
+        assert_eq!(None, r.get::<Position>(a));
+        assert!(!r.insert(a, position(2, 2)));
+        assert_eq!(None, r.remove::<Position>(a));
+        assert!(!r.despawn(a));
+        assert_eq!(Some(&position(1, 1)), r.get::<Position>(b));
+    }
+
+    #[test]
+    fn for_each_visits_entities_with_both() {
+        let mut r = Registry::new();
+        let mut ids = vec![];
+        for i in 0..4 {
+            let id = r.spawn().unwrap();
+            r.insert(id, position(0, 0));
+            if i % 2 == 0 {
+                r.insert(id, Velocity([i.into(), DNum::ONE]));
+            }
+            ids.push(id);
+        }
+
+        let mut visited = vec![];
+        r.for_each::<Velocity, Position, _>(|id, v, p| {
+            p.0[0] += v.0[0];
+            p.0[1] += v.0[1];
+            visited.push(id);
+        });
+
+        assert_eq!(vec![ids[0], ids[2]], visited);
+        assert_eq!(Some(&position(2, 1)), r.get::<Position>(ids[2]));
+        assert_eq!(Some(&position(0, 0)), r.get::<Position>(ids[1]));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut r = Registry::new();
+        for i in 0..10 {
+            let id = r.spawn().unwrap();
+            r.insert(id, position(i, -i));
+            r.insert(id, Velocity([DNum::from_raw(i), DNum::MIN]));
+            r.insert(id, Controller((i as u8).into()));
+            if i % 3 == 0 {
+                r.despawn(id);
+            }
+        }
+
+        let mut bytes = vec![];
+        r.write_bytes(&mut bytes);
+        assert!(bytes.len() <= Registry::MAX_SIZE);
+        let read = Registry::read_bytes(&mut ByteReader::new(&bytes)).unwrap();
+        assert_eq!(r, read);
+
+        // Dead generations are kept so the next spawn matches.
+        let mut a = r;
+        let mut b = read;
+        assert_eq!(a.spawn(), b.spawn());
+    }
+
+    #[test]
+    fn copies_are_independent() {
+        let mut a = Registry::new();
+        let id = a.spawn().unwrap();
+        a.insert(id, position(1, 1));
+
+        let mut b = a;
+        b.get_mut::<Position>(id).unwrap().0[0] = 9.into();
+        assert_eq!(Some(&position(1, 1)), a.get::<Position>(id));
+        assert_ne!(a, b);
+    }
+}
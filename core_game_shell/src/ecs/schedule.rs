@@ -0,0 +1,444 @@
+use super::{Component, ComponentKind, EntityId, Registry};
+use crate::{
+    events::{Event, EventQueue},
+    game::{Frame, PlayerId, MAX_PLAYERS},
+    math::hash::Fnv1a32,
+    player_input::PlayerInput,
+    rand::Rng,
+};
+
+/// The maximum number of systems a schedule can hold.
+pub const MAX_SYSTEMS: usize = 32;
+
+/// A set of components a system accesses, plus whether it spawns or despawns entities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access(u8);
+impl Access {
+    pub const NONE: Self = Self(0);
+    const ENTITIES_FLAG: u8 = 1 << 7;
+
+    /// Adds the component kind to the set.
+    pub const fn with(self, kind: ComponentKind) -> Self {
+        Self(self.0 | 1 << kind as u8)
+    }
+
+    /// Allows spawning and despawning entities.
+    pub const fn with_entities(self) -> Self {
+        Self(self.0 | Self::ENTITIES_FLAG)
+    }
+
+    pub const fn contains(&self, kind: ComponentKind) -> bool {
+        self.0 & 1 << kind as u8 != 0
+    }
+
+    pub const fn contains_entities(&self) -> bool {
+        self.0 & Self::ENTITIES_FLAG != 0
+    }
+
+    /// Returns whether any component or entity access is in both sets.
+    pub const fn overlaps(&self, other: &Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+/// A piece of gameplay logic run once per tick.
+/// Access is checked at run time, and accessing anything undeclared panics.
+#[derive(Clone, Copy, Debug)]
+pub struct System {
+    pub name: &'static str,
+    /// Components that are only read.
+    pub reads: Access,
+    /// Components that are read and written.
+    pub writes: Access,
+    pub run: fn(&mut SystemContext),
+}
+impl System {
+    /// Returns whether the order of the two systems matters, as one writes something the other accesses.
+    pub fn conflicts_with(&self, other: &Self) -> bool {
+        self.writes.overlaps(&other.reads)
+            || self.writes.overlaps(&other.writes)
+            || other.writes.overlaps(&self.reads)
+    }
+
+    /// Returns the random stream for the system.
+    /// Based on the name, so adding or reordering systems doesn't change what the others roll.
+    pub fn stream(&self) -> u64 {
+        let mut hasher = Fnv1a32::new();
+        hasher.write(self.name.as_bytes());
+        hasher.finish() as u64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScheduleError {
+    /// The schedule already holds `MAX_SYSTEMS`.
+    Full,
+    /// A system with the same name was already added.
+    DuplicateName(&'static str),
+}
+
+/// An ordered list of systems.
+/// Systems always run in the order they were added, so ticks are deterministic.
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    systems: [Option<System>; MAX_SYSTEMS],
+    len: usize,
+}
+impl Schedule {
+    /// Creates a new empty schedule.
+    pub fn new() -> Self {
+        Self {
+            systems: [None; MAX_SYSTEMS],
+            len: 0,
+        }
+    }
+
+    /// Adds a system to run after all others.
+    pub fn add(&mut self, system: System) -> Result<(), ScheduleError> {
+        if self.systems().any(|s| s.name == system.name) {
+            return Err(ScheduleError::DuplicateName(system.name));
+        }
+        if self.len >= MAX_SYSTEMS {
+            return Err(ScheduleError::Full);
+        }
+
+        self.systems[self.len] = Some(system);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns all systems in the order they run.
+    pub fn systems(&self) -> impl Iterator<Item = &System> {
+        self.systems[..self.len].iter().flatten()
+    }
+
+    /// Runs every system once.
+    /// Each system gets its own stream forked from `rng`, keyed by its name.
+    pub fn run(
+        &self,
+        registry: &mut Registry,
+        frame: Frame,
+        inputs: &[PlayerInput; MAX_PLAYERS],
+        events: &mut EventQueue,
+        rng: &Rng,
+    ) {
+        for system in self.systems() {
+            let mut context = SystemContext {
+                system,
+                registry: &mut *registry,
+                frame,
+                inputs,
+                events: &mut *events,
+                rng: rng.fork(system.stream()),
+            };
+            (system.run)(&mut context);
+        }
+    }
+}
+
+/// Everything a system can see while it runs.
+pub struct SystemContext<'a> {
+    system: &'a System,
+    registry: &'a mut Registry,
+    frame: Frame,
+    inputs: &'a [PlayerInput; MAX_PLAYERS],
+    events: &'a mut EventQueue,
+    rng: Rng,
+}
+impl<'a> SystemContext<'a> {
+    /// Returns the frame being ticked.
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+
+    /// Returns the input applied for the player on this frame.
+    pub fn input(&self, player: PlayerId) -> PlayerInput {
+        self.inputs[player.index()]
+    }
+
+    /// Returns the input applied for every player on this frame.
+    pub fn inputs(&self) -> [PlayerInput; MAX_PLAYERS] {
+        *self.inputs
+    }
+
+    /// Returns a copy of the events emitted so far this frame, including by earlier systems.
+    pub fn events(&self) -> EventQueue {
+        *self.events
+    }
+
+    /// Returns the random number generator for this system and frame.
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// Emits an event. Returns false if too many were emitted this frame.
+    pub fn emit(&mut self, event: Event) -> bool {
+        self.events.emit(event)
+    }
+
+    /// Returns every alive entity, ordered by index.
+    pub fn entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.registry.entities().iter()
+    }
+
+    pub fn get<T: Component>(&self, id: EntityId) -> Option<&T> {
+        self.check_read(T::KIND);
+        self.registry.get(id)
+    }
+
+    pub fn get_mut<T: Component>(&mut self, id: EntityId) -> Option<&mut T> {
+        self.check_write(T::KIND);
+        self.registry.get_mut(id)
+    }
+
+    pub fn insert<T: Component>(&mut self, id: EntityId, component: T) -> bool {
+        self.check_write(T::KIND);
+        self.registry.insert(id, component)
+    }
+
+    pub fn remove<T: Component>(&mut self, id: EntityId) -> Option<T> {
+        self.check_write(T::KIND);
+        self.registry.remove(id)
+    }
+
+    pub fn query<T: Component>(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.check_read(T::KIND);
+        self.registry.query()
+    }
+
+    /// Calls `f` for every entity with both components.
+    pub fn for_each<R: Component, W: Component, F>(&mut self, f: F)
+    where
+        F: FnMut(EntityId, R, &mut W),
+    {
+        self.check_read(R::KIND);
+        self.check_write(W::KIND);
+        self.registry.for_each(f)
+    }
+
+    pub fn spawn(&mut self) -> Option<EntityId> {
+        self.check_entities();
+        self.registry.spawn()
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        self.check_entities();
+        self.registry.despawn(id)
+    }
+
+    fn check_read(&self, kind: ComponentKind) {
+        assert!(
+            self.system.reads.contains(kind) || self.system.writes.contains(kind),
+            "system '{}' did not declare it reads {:?}",
+            self.system.name,
+            kind
+        );
+    }
+
+    fn check_write(&self, kind: ComponentKind) {
+        assert!(
+            self.system.writes.contains(kind),
+            "system '{}' did not declare it writes {:?}",
+            self.system.name,
+            kind
+        );
+    }
+
+    fn check_entities(&self) {
+        assert!(
+            self.system.writes.contains_entities(),
+            "system '{}' did not declare it spawns or despawns entities",
+            self.system.name
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::{Position, Velocity},
+        math::DNum,
+    };
+
+    fn spawner(ctx: &mut SystemContext) {
+        let id = ctx.spawn().unwrap();
+        ctx.insert(id, Position([DNum::ZERO; 2]));
+        ctx.insert(id, Velocity([DNum::ONE, DNum::ZERO]));
+    }
+
+    fn mover(ctx: &mut SystemContext) {
+        ctx.for_each::<Velocity, Position, _>(|_, v, p| {
+            p.0[0] += v.0[0];
+            p.0[1] += v.0[1];
+        });
+    }
+
+    fn announcer(ctx: &mut SystemContext) {
+        let count = ctx.query::<Position>().count() as u16;
+        ctx.emit(Event::Sound { cue: count });
+    }
+
+    fn cheater(ctx: &mut SystemContext) {
+        for id in ctx.entities().collect::<Vec<_>>() {
+            ctx.get_mut::<Position>(id);
+        }
+    }
+
+    const SPAWNER: System = System {
+        name: "spawner",
+        reads: Access::NONE,
+        writes: Access::NONE
+            .with(ComponentKind::Position)
+            .with(ComponentKind::Velocity)
+            .with_entities(),
+        run: spawner,
+    };
+    const MOVER: System = System {
+        name: "mover",
+        reads: Access::NONE.with(ComponentKind::Velocity),
+        writes: Access::NONE.with(ComponentKind::Position),
+        run: mover,
+    };
+    const ANNOUNCER: System = System {
+        name: "announcer",
+        reads: Access::NONE.with(ComponentKind::Position),
+        writes: Access::NONE,
+        run: announcer,
+    };
+
+    fn run(schedule: &Schedule, registry: &mut Registry) -> EventQueue {
+        let mut events = EventQueue::new();
+        events.begin(0.into());
+        schedule.run(
+            registry,
+            0.into(),
+            &[PlayerInput::new(); MAX_PLAYERS],
+            &mut events,
+            &Rng::new(0),
+        );
+        events
+    }
+
+    fn roller(ctx: &mut SystemContext) {
+        let cue = ctx.rng().u32() as u16;
+        ctx.emit(Event::Sound { cue });
+    }
+
+    #[test]
+    fn systems_get_own_random_streams() {
+        const ROLLER: System = System {
+            name: "roller",
+            reads: Access::NONE,
+            writes: Access::NONE,
+            run: roller,
+        };
+        const OTHER_ROLLER: System = System {
+            name: "other_roller",
+            ..ROLLER
+        };
+
+        let mut schedule = Schedule::new();
+        schedule.add(ROLLER).unwrap();
+        schedule.add(OTHER_ROLLER).unwrap();
+        let both = run(&schedule, &mut Registry::new());
+
+        // Removing a system doesn't change what the other rolls.
+        let mut schedule = Schedule::new();
+        schedule.add(OTHER_ROLLER).unwrap();
+        let other = run(&schedule, &mut Registry::new());
+
+        let both: Vec<_> = both.iter().collect();
+        let other: Vec<_> = other.iter().collect();
+        assert_ne!(both[0], both[1]);
+        assert_eq!(both[1], other[0]);
+    }
+
+    #[test]
+    fn systems_run_in_order() {
+        let mut schedule = Schedule::new();
+        schedule.add(SPAWNER).unwrap();
+        schedule.add(MOVER).unwrap();
+        schedule.add(ANNOUNCER).unwrap();
+
+        let mut registry = Registry::new();
+        let events = run(&schedule, &mut registry);
+        run(&schedule, &mut registry);
+
+        // The first entity moved on both ticks, the second only on the one it spawned.
+        let positions: Vec<Position> = registry.query::<Position>().map(|(_, p)| *p).collect();
+        assert_eq!(
+            vec![
+                Position([2.into(), DNum::ZERO]),
+                Position([1.into(), DNum::ZERO])
+            ],
+            positions
+        );
+        assert_eq!(
+            vec![&Event::Sound { cue: 1 }],
+            events.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "system 'cheater' did not declare it writes Position")]
+    fn undeclared_write_panics() {
+        let mut schedule = Schedule::new();
+        schedule.add(SPAWNER).unwrap();
+        schedule
+            .add(System {
+                name: "cheater",
+                reads: Access::NONE.with(ComponentKind::Position),
+                writes: Access::NONE,
+                run: cheater,
+            })
+            .unwrap();
+
+        run(&schedule, &mut Registry::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "did not declare it spawns or despawns entities")]
+    fn undeclared_spawn_panics() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add(System {
+                writes: Access::NONE
+                    .with(ComponentKind::Position)
+                    .with(ComponentKind::Velocity),
+                ..SPAWNER
+            })
+            .unwrap();
+
+        run(&schedule, &mut Registry::new());
+    }
+
+    #[test]
+    fn add_rejects_duplicates_and_overflow() {
+        let mut schedule = Schedule::new();
+        schedule.add(MOVER).unwrap();
+        assert_eq!(
+            Err(ScheduleError::DuplicateName("mover")),
+            schedule.add(MOVER)
+        );
+
+        const NAMES: [&str; MAX_SYSTEMS] = [
+            "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
+            "16", "17", "18", "19", "20", "21", "22", "23", "24", "25", "26", "27", "28", "29",
+            "30", "31",
+        ];
+        for name in &NAMES[1..] {
+            schedule.add(System { name, ..MOVER }).unwrap();
+        }
+        assert_eq!(Err(ScheduleError::Full), schedule.add(SPAWNER));
+        assert_eq!(MAX_SYSTEMS, schedule.systems().count());
+    }
+
+    #[test]
+    fn conflicts() {
+        assert!(SPAWNER.conflicts_with(&MOVER));
+        assert!(MOVER.conflicts_with(&ANNOUNCER));
+        assert!(ANNOUNCER.conflicts_with(&MOVER));
+        assert!(!ANNOUNCER.conflicts_with(&ANNOUNCER));
+    }
+}
@@ -1,2 +0,0 @@
-#[derive(Clone, PartialEq, Debug)]
-pub enum Event {}
@@ -0,0 +1,370 @@
+use super::{Event, EventQueue, MAX_EVENTS_PER_FRAME};
+use crate::game::Frame;
+
+/// The maximum number of events that can wait on confirmation.
+pub const MAX_PENDING_EVENTS: usize = MAX_EVENTS_PER_FRAME * 16;
+/// The maximum number of notices that can wait on the host before the oldest are overwritten.
+pub const MAX_NOTICES: usize = MAX_PENDING_EVENTS * 4;
+
+/// Uniquely identifies an event, so a host can match a cancellation to what it played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventId(u32);
+impl EventId {
+    pub fn inner(&self) -> u32 {
+        self.0
+    }
+}
+
+/// An event stamped with the frame it was emitted on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameEvent {
+    pub id: EventId,
+    pub frame: Frame,
+    pub event: Event,
+}
+
+/// Tells the host what happened to an event.
+/// Every event is first `Predicted`, then exactly once either `Confirmed` or `Cancelled`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventNotice {
+    /// The event was emitted on a frame that may still be rolled back.
+    /// Hosts that want low latency can play it now, and stop it if it's cancelled.
+    Predicted(GameEvent),
+    /// The frame of the event was confirmed, so it will never be cancelled.
+    /// Hosts that only want certain events should play it now.
+    Confirmed(GameEvent),
+    /// The frame of the event was rolled back and resimulating it did not emit the event again.
+    Cancelled(GameEvent),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PendingEvent {
+    event: GameEvent,
+    /// Set while the frame is being resimulated, cleared if the event is emitted again.
+    is_stale: bool,
+}
+
+/// Tracks events from predicted frames until they are confirmed or rolled back.
+///
+/// The game calls `rollback` before resimulating, `record` after each tick,
+/// `cancel_stale` once resimulation is done and `confirm` as frames are confirmed.
+/// An event that is emitted again on the same frame while resimulating keeps its id,
+/// so a host playing predicted events doesn't hear it twice.
+pub struct EventBus {
+    next_id: u32,
+    /// Ordered by frame, then by the order they were emitted.
+    pending: [Option<PendingEvent>; MAX_PENDING_EVENTS],
+    pending_len: usize,
+    notices: [Option<EventNotice>; MAX_NOTICES],
+    notices_start: usize,
+    notices_len: usize,
+    dropped: u32,
+}
+impl EventBus {
+    /// Creates a new empty bus.
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            pending: [None; MAX_PENDING_EVENTS],
+            pending_len: 0,
+            notices: [None; MAX_NOTICES],
+            notices_start: 0,
+            notices_len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Marks all pending events on or after the frame as stale, as those frames are about to be resimulated.
+    pub fn rollback(&mut self, frame: Frame) {
+        for pending in self.pending[..self.pending_len].iter_mut().flatten() {
+            if !frame.is_after(pending.event.frame) {
+                pending.is_stale = true;
+            }
+        }
+    }
+
+    /// Records the events emitted while ticking a frame.
+    /// Events that match a stale one on the same frame keep it instead of being predicted again.
+    pub fn record(&mut self, queue: &EventQueue) {
+        let frame = queue.frame();
+        for event in queue.iter() {
+            let stale = self.pending[..self.pending_len]
+                .iter_mut()
+                .flatten()
+                .find(|p| p.is_stale && p.event.frame == frame && p.event.event == *event);
+
+            match stale {
+                Some(pending) => pending.is_stale = false,
+                None => self.predict(frame, *event),
+            }
+        }
+    }
+
+    /// Cancels all stale events, as resimulating their frames did not emit them again.
+    pub fn cancel_stale(&mut self) {
+        self.remove_pending(|p| p.is_stale, EventNotice::Cancelled);
+    }
+
+    /// Confirms all pending events from before the frame.
+    pub fn confirm(&mut self, frame: Frame) {
+        self.remove_pending(|p| frame.is_after(p.event.frame), EventNotice::Confirmed);
+    }
+
+    /// Returns all events that have been predicted but not yet confirmed or cancelled.
+    pub fn pending(&self) -> impl Iterator<Item = &GameEvent> {
+        self.pending[..self.pending_len]
+            .iter()
+            .flatten()
+            .map(|p| &p.event)
+    }
+
+    /// Returns the next notice for the host, oldest first.
+    pub fn poll(&mut self) -> Option<EventNotice> {
+        if self.notices_len == 0 {
+            return None;
+        }
+
+        let notice = self.notices[self.notices_start].take();
+        self.notices_start = (self.notices_start + 1) % MAX_NOTICES;
+        self.notices_len -= 1;
+        notice
+    }
+
+    /// Returns the number of notices that were overwritten because the host didn't poll them in time.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Adds a new pending event after all others on the same or earlier frames.
+    fn predict(&mut self, frame: Frame, event: Event) {
+        let event = GameEvent {
+            id: EventId(self.next_id),
+            frame,
+            event,
+        };
+        self.next_id = self.next_id.wrapping_add(1);
+
+        if self.pending_len >= MAX_PENDING_EVENTS {
+            // Nothing can be confirmed or cancelled later, so the host never hears of it.
+            return;
+        }
+
+        let index = self.pending[..self.pending_len]
+            .iter()
+            .flatten()
+            .position(|p| p.event.frame.is_after(frame))
+            .unwrap_or(self.pending_len);
+        self.pending[index..=self.pending_len].rotate_right(1);
+        self.pending[index] = Some(PendingEvent {
+            event,
+            is_stale: false,
+        });
+        self.pending_len += 1;
+
+        self.notify(EventNotice::Predicted(event));
+    }
+
+    /// Removes all matching pending events, keeping the rest in order.
+    fn remove_pending<F, N>(&mut self, should_remove: F, notice: N)
+    where
+        F: Fn(&PendingEvent) -> bool,
+        N: Fn(GameEvent) -> EventNotice,
+    {
+        let mut kept = 0;
+        for i in 0..self.pending_len {
+            let pending = match self.pending[i].take() {
+                Some(pending) => pending,
+                None => continue,
+            };
+
+            if should_remove(&pending) {
+                self.notify(notice(pending.event));
+            } else {
+                self.pending[kept] = Some(pending);
+                kept += 1;
+            }
+        }
+
+        self.pending_len = kept;
+    }
+
+    fn notify(&mut self, notice: EventNotice) {
+        if self.notices_len == MAX_NOTICES {
+            self.notices_start = (self.notices_start + 1) % MAX_NOTICES;
+            self.notices_len -= 1;
+            self.dropped = self.dropped.wrapping_add(1);
+        }
+
+        let index = (self.notices_start + self.notices_len) % MAX_NOTICES;
+        self.notices[index] = Some(notice);
+        self.notices_len += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(frame: u16, cues: &[u16]) -> EventQueue {
+        let mut q = EventQueue::new();
+        q.begin(frame.into());
+        for cue in cues {
+            q.emit(Event::Sound { cue: *cue });
+        }
+        q
+    }
+
+    fn notices(bus: &mut EventBus) -> Vec<EventNotice> {
+        core::iter::from_fn(|| bus.poll()).collect()
+    }
+
+    fn cues(events: &[GameEvent]) -> Vec<(u16, u16)> {
+        events
+            .iter()
+            .map(|e| match e.event {
+                Event::Sound { cue } => (e.frame.inner(), cue),
+                _ => panic!("unexpected event"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recorded_events_are_predicted() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(0, &[1, 2]));
+
+        let n = notices(&mut bus);
+        assert_eq!(2, n.len());
+        assert!(matches!(n[0], EventNotice::Predicted(e) if e.event == Event::Sound { cue: 1 }));
+        assert!(matches!(n[1], EventNotice::Predicted(e) if e.event == Event::Sound { cue: 2 }));
+        assert_ne!(
+            bus.pending().next().unwrap().id,
+            bus.pending().nth(1).unwrap().id
+        );
+    }
+
+    #[test]
+    fn confirm_only_confirms_earlier_frames() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(0, &[1]));
+        bus.record(&queue(1, &[2]));
+        bus.record(&queue(2, &[3]));
+        notices(&mut bus);
+
+        bus.confirm(2.into());
+        let confirmed: Vec<GameEvent> = notices(&mut bus)
+            .into_iter()
+            .map(|n| match n {
+                EventNotice::Confirmed(e) => e,
+                _ => panic!("expected confirmation"),
+            })
+            .collect();
+        assert_eq!(vec![(0, 1), (1, 2)], cues(&confirmed));
+
+        let pending: Vec<GameEvent> = bus.pending().copied().collect();
+        assert_eq!(vec![(2, 3)], cues(&pending));
+    }
+
+    #[test]
+    fn rollback_cancels_events_not_emitted_again() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(0, &[1]));
+        bus.record(&queue(1, &[2, 3]));
+        bus.record(&queue(2, &[4]));
+        let predicted = notices(&mut bus);
+
+        bus.rollback(1.into());
+        bus.record(&queue(1, &[3, 5]));
+        bus.record(&queue(2, &[4]));
+        bus.cancel_stale();
+
+        let n = notices(&mut bus);
+        assert_eq!(2, n.len());
+        assert!(
+            matches!(n[0], EventNotice::Predicted(e) if e.frame == 1.into() && e.event == Event::Sound { cue: 5 })
+        );
+        assert_eq!(
+            EventNotice::Cancelled(match predicted[1] {
+                EventNotice::Predicted(e) => e,
+                _ => unreachable!(),
+            }),
+            n[1]
+        );
+
+        let pending: Vec<GameEvent> = bus.pending().copied().collect();
+        assert_eq!(vec![(0, 1), (1, 3), (1, 5), (2, 4)], cues(&pending));
+    }
+
+    #[test]
+    fn events_emitted_again_keep_their_id() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(3, &[7, 7]));
+        let before: Vec<GameEvent> = bus.pending().copied().collect();
+
+        bus.rollback(3.into());
+        bus.record(&queue(3, &[7, 7]));
+        bus.cancel_stale();
+
+        notices(&mut bus);
+        let after: Vec<GameEvent> = bus.pending().copied().collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn same_event_on_a_different_frame_is_not_kept() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(3, &[7]));
+        notices(&mut bus);
+
+        bus.rollback(3.into());
+        bus.record(&queue(3, &[]));
+        bus.record(&queue(4, &[7]));
+        bus.cancel_stale();
+
+        let n = notices(&mut bus);
+        assert!(matches!(n[0], EventNotice::Predicted(e) if e.frame == 4.into()));
+        assert!(matches!(n[1], EventNotice::Cancelled(e) if e.frame == 3.into()));
+    }
+
+    #[test]
+    fn rollback_leaves_earlier_frames_alone() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(0, &[1]));
+        bus.rollback(1.into());
+        bus.cancel_stale();
+
+        assert_eq!(1, notices(&mut bus).len());
+        assert_eq!(1, bus.pending().count());
+    }
+
+    #[test]
+    fn frames_compare_across_wrap_around() {
+        let mut bus = EventBus::new();
+        bus.record(&queue(u16::MAX, &[1]));
+        bus.record(&queue(0, &[2]));
+        notices(&mut bus);
+
+        bus.confirm(0.into());
+        assert_eq!(1, notices(&mut bus).len());
+
+        let pending: Vec<GameEvent> = bus.pending().copied().collect();
+        assert_eq!(vec![(0, 2)], cues(&pending));
+    }
+
+    #[test]
+    fn oldest_notices_are_dropped_when_full() {
+        let mut bus = EventBus::new();
+        for frame in 0..=(MAX_NOTICES / MAX_EVENTS_PER_FRAME) as u16 {
+            let cues: Vec<u16> = (0..MAX_EVENTS_PER_FRAME as u16).collect();
+            bus.record(&queue(frame, &cues));
+            bus.confirm((frame + 1).into());
+        }
+
+        assert!(bus.dropped() > 0);
+        let n = notices(&mut bus);
+        assert_eq!(MAX_NOTICES, n.len());
+        assert!(
+            matches!(n[MAX_NOTICES - 1], EventNotice::Confirmed(e) if e.event == Event::Sound { cue: MAX_EVENTS_PER_FRAME as u16 - 1 })
+        );
+    }
+}
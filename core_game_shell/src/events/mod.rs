@@ -0,0 +1,115 @@
+mod bus;
+
+pub use bus::*;
+
+use crate::{
+    game::{Frame, PlayerId},
+    physics::BodyId,
+};
+
+/// The maximum number of events that can be emitted while ticking a single frame.
+pub const MAX_EVENTS_PER_FRAME: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    /// The checksum of a confirmed state differs between this peer and a remote one.
+    DesyncDetected {
+        frame: Frame,
+        local: u32,
+        remote: u32,
+    },
+    /// A player's input started being applied.
+    PlayerJoined { player: PlayerId },
+    /// A player's input stopped being applied.
+    PlayerLeft { player: PlayerId },
+    /// Two bodies collided.
+    Collision { a: BodyId, b: BodyId },
+    /// A player took damage.
+    Damage { target: PlayerId, amount: u16 },
+    /// A sound should be played. The meaning of the cue is up to the host.
+    Sound { cue: u16 },
+}
+
+/// The events emitted by systems while ticking a single frame.
+/// Events past `MAX_EVENTS_PER_FRAME` are dropped, so the queue never allocates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EventQueue {
+    frame: Frame,
+    events: [Option<Event>; MAX_EVENTS_PER_FRAME],
+    len: usize,
+}
+impl EventQueue {
+    /// Creates a new empty queue.
+    pub fn new() -> Self {
+        Self {
+            frame: 0.into(),
+            events: [None; MAX_EVENTS_PER_FRAME],
+            len: 0,
+        }
+    }
+
+    /// Clears the queue so it can hold the events for the given frame.
+    pub fn begin(&mut self, frame: Frame) {
+        self.frame = frame;
+        self.events = [None; MAX_EVENTS_PER_FRAME];
+        self.len = 0;
+    }
+
+    /// Returns the frame the events were emitted on.
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+
+    /// Adds an event to the queue. Returns false if the queue is full.
+    pub fn emit(&mut self, event: Event) -> bool {
+        if self.len >= MAX_EVENTS_PER_FRAME {
+            return false;
+        }
+
+        self.events[self.len] = Some(event);
+        self.len += 1;
+        true
+    }
+
+    /// Returns the events in the order they were emitted.
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events[..self.len].iter().filter_map(|e| e.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emit_keeps_order() {
+        let mut q = EventQueue::new();
+        q.begin(4.into());
+        assert!(q.emit(Event::Sound { cue: 1 }));
+        assert!(q.emit(Event::Sound { cue: 2 }));
+
+        assert_eq!(Frame::from(4), q.frame());
+        assert_eq!(
+            vec![&Event::Sound { cue: 1 }, &Event::Sound { cue: 2 }],
+            q.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn emit_fails_when_full() {
+        let mut q = EventQueue::new();
+        for cue in 0..MAX_EVENTS_PER_FRAME as u16 {
+            assert!(q.emit(Event::Sound { cue }));
+        }
+        assert!(!q.emit(Event::Sound { cue: 0 }));
+        assert_eq!(MAX_EVENTS_PER_FRAME, q.iter().count());
+    }
+
+    #[test]
+    fn begin_clears_events() {
+        let mut q = EventQueue::new();
+        q.emit(Event::Sound { cue: 1 });
+        q.begin(1.into());
+        assert_eq!(0, q.iter().count());
+    }
+}
@@ -0,0 +1,63 @@
+use super::frame::Frame;
+
+/// The number of frames of checksums kept.
+/// Must divide `u16::MAX + 1` so slots stay stable when frames wrap.
+pub const CHECKSUM_HISTORY_LEN: usize = 32;
+
+/// Ring buffer of state checksums keyed by frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChecksumHistory {
+    checksums: [Option<(Frame, u32)>; CHECKSUM_HISTORY_LEN],
+}
+impl ChecksumHistory {
+    /// Creates a new empty history.
+    pub fn new() -> Self {
+        Self {
+            checksums: [None; CHECKSUM_HISTORY_LEN],
+        }
+    }
+
+    fn slot(frame: Frame) -> usize {
+        frame.inner() as usize % CHECKSUM_HISTORY_LEN
+    }
+
+    /// Records the checksum for the given frame, replacing the oldest one.
+    pub fn insert(&mut self, frame: Frame, checksum: u32) {
+        self.checksums[Self::slot(frame)] = Some((frame, checksum));
+    }
+
+    /// Returns the checksum for the given frame, if it is still held.
+    pub fn get(&self, frame: Frame) -> Option<u32> {
+        match self.checksums[Self::slot(frame)] {
+            Some((f, checksum)) if f == frame => Some(checksum),
+            _ => None,
+        }
+    }
+
+    /// Returns all held checksums.
+    pub fn iter(&self) -> impl Iterator<Item = (Frame, u32)> + '_ {
+        self.checksums.iter().filter_map(|c| *c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_returns_inserted() {
+        let mut h = ChecksumHistory::new();
+        h.insert(3.into(), 42);
+        assert_eq!(Some(42), h.get(3.into()));
+        assert_eq!(None, h.get(4.into()));
+    }
+
+    #[test]
+    fn insert_replaces_oldest() {
+        let mut h = ChecksumHistory::new();
+        h.insert(3.into(), 42);
+        h.insert((3 + CHECKSUM_HISTORY_LEN as u16).into(), 7);
+        assert_eq!(None, h.get(3.into()));
+        assert_eq!(Some(7), h.get((3 + CHECKSUM_HISTORY_LEN as u16).into()));
+    }
+}
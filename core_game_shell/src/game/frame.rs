@@ -1,8 +1,18 @@
+use core::ops::{Add, Sub};
+
+use crate::math::sequences::sequence_a_after_b_u16;
+
 type N = u16;
 
+/// A tick of the simulation.
+/// Frames wrap around after `u16::MAX`, about 18 minutes at 60Hz, so they are compared within a window
+/// of half the range instead of by value. Frames further apart than that can't be ordered.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Frame(N);
 impl Frame {
+    /// How far apart two frames can be and still be ordered correctly.
+    pub const WINDOW: N = N::MAX / 2;
+
     /// Returns the inner representation of the frame.
     pub fn inner(&self) -> N {
         self.0
@@ -15,9 +25,188 @@ impl Frame {
     pub fn increment(&self) -> Self {
         Self(self.0.wrapping_add(1))
     }
+
+    /// Returns whether this frame comes after the other, taking wrap-around into account.
+    pub fn is_after(&self, other: Frame) -> bool {
+        sequence_a_after_b_u16(self.0, other.0)
+    }
+
+    /// Returns whether this frame comes before the other, taking wrap-around into account.
+    pub fn is_before(&self, other: Frame) -> bool {
+        other.is_after(*self)
+    }
+
+    /// Returns how many frames this one is after the other, which is negative if it is before.
+    pub fn offset_from(&self, other: Frame) -> i16 {
+        self.0.wrapping_sub(other.0) as i16
+    }
+
+    /// Returns how many frames it takes to count up from `earlier` to this frame.
+    pub fn frames_since(&self, earlier: Frame) -> N {
+        self.0.wrapping_sub(earlier.0)
+    }
+
+    /// Returns whether this frame is one of the `len` frames starting at `start`.
+    pub fn is_within(&self, start: Frame, len: N) -> bool {
+        self.frames_since(start) < len
+    }
+
+    /// Returns every frame from this one up to, but not including, `end`.
+    /// Empty if `end` isn't after this frame.
+    pub fn until(&self, end: Frame) -> FrameRange {
+        let len = if end.is_after(*self) {
+            end.frames_since(*self)
+        } else {
+            0
+        };
+
+        FrameRange { next: *self, len }
+    }
 }
 impl From<u16> for Frame {
     fn from(frame: N) -> Self {
         Self(frame)
     }
 }
+impl Add<N> for Frame {
+    type Output = Self;
+
+    fn add(self, frames: N) -> Self::Output {
+        Self(self.0.wrapping_add(frames))
+    }
+}
+impl Sub<N> for Frame {
+    type Output = Self;
+
+    fn sub(self, frames: N) -> Self::Output {
+        Self(self.0.wrapping_sub(frames))
+    }
+}
+
+/// Consecutive frames, which may wrap around.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameRange {
+    next: Frame,
+    len: N,
+}
+impl Iterator for FrameRange {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let frame = self.next;
+        self.next = frame.increment();
+        self.len -= 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len as usize, Some(self.len as usize))
+    }
+}
+impl ExactSizeIterator for FrameRange {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Offsets that are checked from every frame.
+    const OFFSETS: [N; 6] = [1, 2, 8, 255, 1000, Frame::WINDOW];
+
+    fn every_frame() -> impl Iterator<Item = Frame> {
+        (0..=N::MAX).map(Frame::from)
+    }
+
+    #[test]
+    fn later_frames_are_after_from_every_frame() {
+        for frame in every_frame() {
+            assert!(!frame.is_after(frame));
+            assert!(!frame.is_before(frame));
+            for offset in OFFSETS {
+                let later = frame + offset;
+                assert!(later.is_after(frame), "{:?} {:?}", later, frame);
+                assert!(frame.is_before(later));
+                assert!(!frame.is_after(later));
+
+                let earlier = frame - offset;
+                assert!(frame.is_after(earlier));
+                assert!(earlier.is_before(frame));
+            }
+        }
+    }
+
+    #[test]
+    fn distance_is_consistent_from_every_frame() {
+        for frame in every_frame() {
+            assert_eq!(0, frame.offset_from(frame));
+            for offset in OFFSETS {
+                let later = frame + offset;
+                assert_eq!(offset, later.frames_since(frame));
+                assert_eq!(offset as i16, later.offset_from(frame));
+                assert_eq!(-(offset as i16), frame.offset_from(later));
+                assert_eq!(frame, later - offset);
+            }
+        }
+    }
+
+    #[test]
+    fn wraps_at_the_boundary() {
+        let last = Frame::from(N::MAX);
+        let first = Frame::from(0);
+        assert_eq!(first, last.increment());
+        assert_eq!(last, first.decrement());
+        assert_eq!(first, last + 1);
+        assert_eq!(last, first - 1);
+
+        assert!(first.is_after(last));
+        assert!(last.is_before(first));
+        assert_eq!(1, first.offset_from(last));
+        assert_eq!(-1, last.offset_from(first));
+        assert_eq!(1, first.frames_since(last));
+        assert_eq!(N::MAX, last.frames_since(first));
+    }
+
+    #[test]
+    fn frames_too_far_apart_are_not_ordered() {
+        let a = Frame::from(10);
+        let b = a + Frame::WINDOW + 1;
+        assert!(!a.is_after(b));
+        assert!(!b.is_after(a));
+
+        // Past the window the order flips.
+        let c = a + Frame::WINDOW + 2;
+        assert!(a.is_after(c));
+    }
+
+    #[test]
+    fn window_contains_frames_across_the_boundary() {
+        let start = Frame::from(N::MAX - 2);
+        let inside: Vec<N> = every_frame()
+            .filter(|f| f.is_within(start, 5))
+            .map(|f| f.inner())
+            .collect();
+        assert_eq!(vec![0, 1, N::MAX - 2, N::MAX - 1, N::MAX], inside);
+        assert!(!start.is_within(start, 0));
+    }
+
+    #[test]
+    fn ranges_iterate_across_the_boundary() {
+        let start = Frame::from(N::MAX - 1);
+        let range = start.until(2.into());
+        assert_eq!(4, range.len());
+        assert_eq!(
+            vec![N::MAX - 1, N::MAX, 0, 1],
+            range.map(|f| f.inner()).collect::<Vec<N>>()
+        );
+
+        assert_eq!(0, start.until(start).count());
+        assert_eq!(0, start.until(start - 3).count());
+        for frame in every_frame() {
+            assert_eq!(8, frame.until(frame + 8).count());
+            assert_eq!(Some(frame + 7), frame.until(frame + 8).last());
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use core::ops::{AddAssign, SubAssign};
+use crate::time::{AccumulatedTime, Seconds, TickRate};
+
+/// How much longer or shorter a tick gets for every frame one peer is ahead of another.
+const DILATION_PER_FRAME: f32 = 0.02;
+/// The most a tick can be stretched or shrunk, so that time sync is never noticeable.
+const MAX_DILATION: f32 = 0.1;
+/// Advantages below this many frames are jitter and are ignored.
+const MIN_ADVANTAGE_FRAMES: f32 = 1.0;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TimerResult {
@@ -6,62 +13,152 @@ pub enum TimerResult {
     NotTicked,
 }
 
+/// Fixed timestep timer that can be slightly sped up or slowed down to stay in step with remote peers.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct GameTimer {
-    accumulated_time: AccumulatedSeconds,
-    tick_hz: TickHz,
+    accumulated_time: AccumulatedTime,
+    tick_rate: TickRate,
+    /// Multiplier for the length of a tick. Above 1 the game slows down, below 1 it speeds up.
+    dilation: f32,
 }
 impl GameTimer {
     /// Creates a new game timer that triggers at the given rate
-    pub fn new(tick_hz: f32) -> Self {
+    pub fn new(tick_rate: TickRate) -> Self {
         Self {
-            accumulated_time: AccumulatedSeconds(0.0),
-            tick_hz: TickHz(tick_hz),
+            accumulated_time: 0.0.into(),
+            tick_rate,
+            dilation: 1.0,
         }
     }
 
     /// Updates the timer.
-    pub fn update(&mut self, delta_t_seconds: f32) {
-        self.accumulated_time += ElapsedSeconds(delta_t_seconds);
+    pub fn update(&mut self, delta_t: Seconds) {
+        self.accumulated_time += delta_t;
     }
 
     /// Ticks the timer.
     pub fn tick(&mut self) -> TimerResult {
-        let mut result = TimerResult::NotTicked;
-        if should_tick(self.accumulated_time, self.tick_hz) {
-            self.accumulated_time -= self.tick_hz;
-            result = TimerResult::Ticked
+        let tick_length = self.tick_length();
+        if self.accumulated_time.seconds() >= tick_length {
+            self.accumulated_time -= tick_length;
+            TimerResult::Ticked
+        } else {
+            TimerResult::NotTicked
+        }
+    }
+
+    /// Discards every whole tick that is still accumulated, returning how many there were.
+    /// Used to recover when updates fall too far behind, instead of trying to catch up forever.
+    pub fn drop_ticks(&mut self) -> u32 {
+        let mut dropped = 0;
+        while self.tick() == TimerResult::Ticked {
+            dropped += 1;
         }
 
-        result
+        dropped
     }
-}
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-struct AccumulatedSeconds(f32);
+    /// Returns how far the accumulated time is between the last tick and the next one, from 0 to 1.
+    /// Used to interpolate between the previous and current state when rendering.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulated_time.seconds() / self.tick_length()).clamp(0.0, 1.0)
+    }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-struct ElapsedSeconds(f32);
+    /// Sets how many frames the local game is ahead of remote peers.
+    /// When ahead ticks are stretched so the others can catch up, when behind they are shrunk.
+    pub fn set_frame_advantage(&mut self, frames: f32) {
+        self.dilation = if frames.abs() < MIN_ADVANTAGE_FRAMES {
+            1.0
+        } else {
+            1.0 + (frames * DILATION_PER_FRAME).clamp(-MAX_DILATION, MAX_DILATION)
+        };
+    }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-struct TickHz(f32);
+    /// Returns the multiplier applied to the length of a tick.
+    pub fn dilation(&self) -> f32 {
+        self.dilation
+    }
 
-fn should_tick(acc: AccumulatedSeconds, delta_t: TickHz) -> bool {
-    acc.0 >= delta_t.0
+    fn tick_length(&self) -> Seconds {
+        self.tick_rate.seconds() * self.dilation
+    }
 }
 
-impl AddAssign<ElapsedSeconds> for AccumulatedSeconds {
-    fn add_assign(&mut self, rhs: ElapsedSeconds) {
-        self.0 += rhs.0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer() -> GameTimer {
+        GameTimer::new(0.25.into())
     }
-}
-impl SubAssign<ElapsedSeconds> for AccumulatedSeconds {
-    fn sub_assign(&mut self, rhs: ElapsedSeconds) {
-        self.0 -= rhs.0;
+
+    #[test]
+    fn ticks_once_per_tick_length() {
+        let mut t = timer();
+        t.update(0.2.into());
+        assert_eq!(TimerResult::NotTicked, t.tick());
+
+        t.update(0.55.into());
+        assert_eq!(TimerResult::Ticked, t.tick());
+        assert_eq!(TimerResult::Ticked, t.tick());
+        assert_eq!(TimerResult::Ticked, t.tick());
+        assert_eq!(TimerResult::NotTicked, t.tick());
     }
-}
-impl SubAssign<TickHz> for AccumulatedSeconds {
-    fn sub_assign(&mut self, rhs: TickHz) {
-        self.0 -= rhs.0
+
+    #[test]
+    fn alpha_is_progress_to_next_tick() {
+        let mut t = timer();
+        assert_eq!(0.0, t.alpha());
+
+        t.update(0.125.into());
+        assert_eq!(0.5, t.alpha());
+
+        t.update(0.1875.into());
+        t.tick();
+        assert_eq!(0.25, t.alpha());
+    }
+
+    #[test]
+    fn drop_ticks_keeps_remainder() {
+        let mut t = timer();
+        t.update(1.125.into());
+        t.tick();
+
+        assert_eq!(3, t.drop_ticks());
+        assert_eq!(0.5, t.alpha());
+        assert_eq!(0, t.drop_ticks());
+    }
+
+    #[test]
+    fn being_ahead_slows_ticks() {
+        let mut t = timer();
+        t.set_frame_advantage(2.0);
+        assert!(t.dilation() > 1.0);
+
+        t.update(0.25.into());
+        assert_eq!(TimerResult::NotTicked, t.tick());
+    }
+
+    #[test]
+    fn being_behind_speeds_up_ticks() {
+        let mut t = timer();
+        t.set_frame_advantage(-2.0);
+        assert!(t.dilation() < 1.0);
+
+        t.update(0.24.into());
+        assert_eq!(TimerResult::Ticked, t.tick());
+    }
+
+    #[test]
+    fn dilation_is_limited() {
+        let mut t = timer();
+        t.set_frame_advantage(100.0);
+        assert_eq!(1.0 + MAX_DILATION, t.dilation());
+
+        t.set_frame_advantage(-100.0);
+        assert_eq!(1.0 - MAX_DILATION, t.dilation());
+
+        t.set_frame_advantage(0.5);
+        assert_eq!(1.0, t.dilation());
     }
 }
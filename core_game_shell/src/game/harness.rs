@@ -0,0 +1,489 @@
+use super::{Game, InputPacket, PlayerId, Session, State, Transport, MAX_PLAYERS};
+use crate::{player_input::PlayerInput, rand::Rng, time::Seconds};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, vec, vec::Vec};
+
+/// The time each step of the harness advances every peer by, which is also the tick length.
+pub const HARNESS_DELTA_T: f32 = 1.0 / 60.0;
+
+/// A change to the network while a scripted condition is active.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NetworkCondition {
+    /// Adds to the latency of every packet.
+    LatencySpike(Seconds),
+    /// Adds to the jitter of every packet, so more arrive out of order.
+    Reorder(Seconds),
+    /// Drops packets with the chance from 0 to 1, if higher than the usual packet loss.
+    PacketLoss(f32),
+    /// Drops every packet.
+    Outage,
+}
+
+/// A condition that applies to packets sent during a range of steps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScriptedCondition {
+    /// The first step the condition applies to.
+    pub from_step: u32,
+    /// The step the condition stops applying at.
+    pub to_step: u32,
+    /// Only packets sent by this peer are affected, or every peer's if `None`.
+    pub sender: Option<usize>,
+    pub condition: NetworkCondition,
+}
+
+/// Conditions of the simulated network, which are the same for every link unless scripted otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConfig {
+    pub latency: Seconds,
+    /// The maximum random time added on top of the latency.
+    pub jitter: Seconds,
+    /// The chance from 0 to 1 that a packet is dropped.
+    pub packet_loss: f32,
+    pub seed: u64,
+    pub script: Vec<ScriptedCondition>,
+}
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            latency: 0.0.into(),
+            jitter: 0.0.into(),
+            packet_loss: 0.0,
+            seed: 1,
+            script: vec![],
+        }
+    }
+}
+
+/// Counts of what happened to packets on the simulated network.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct NetworkStats {
+    /// Packets sent, counting each peer a packet was sent to.
+    pub sent: u32,
+    pub dropped: u32,
+    pub delivered: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InFlight {
+    remaining: f32,
+    packet: InputPacket,
+}
+
+struct Network {
+    config: NetworkConfig,
+    rng: Rng,
+    step: u32,
+    incoming: Vec<Vec<InFlight>>,
+    stats: NetworkStats,
+}
+impl Network {
+    fn send(&mut self, sender: usize, packet: &InputPacket) {
+        let mut latency = self.config.latency.inner();
+        let mut jitter = self.config.jitter.inner();
+        let mut packet_loss = self.config.packet_loss;
+        let active = self.config.script.iter().filter(|s| {
+            (s.from_step..s.to_step).contains(&self.step) && s.sender.is_none_or(|p| p == sender)
+        });
+        for scripted in active {
+            match scripted.condition {
+                NetworkCondition::LatencySpike(spike) => latency += spike.inner(),
+                NetworkCondition::Reorder(extra) => jitter += extra.inner(),
+                NetworkCondition::PacketLoss(chance) => packet_loss = packet_loss.max(chance),
+                NetworkCondition::Outage => packet_loss = 1.0,
+            }
+        }
+
+        for receiver in 0..self.incoming.len() {
+            if receiver == sender {
+                continue;
+            }
+
+            self.stats.sent += 1;
+            if self.rng.f32() < packet_loss {
+                self.stats.dropped += 1;
+                continue;
+            }
+
+            let remaining = latency + self.rng.f32() * jitter;
+            self.incoming[receiver].push(InFlight {
+                remaining,
+                packet: *packet,
+            });
+        }
+    }
+}
+
+/// A transport that sends to every other peer on a simulated network.
+pub struct SimulatedTransport {
+    peer: usize,
+    network: Rc<RefCell<Network>>,
+}
+impl Transport for SimulatedTransport {
+    fn update(&mut self, delta_t: Seconds) {
+        for in_flight in self.network.borrow_mut().incoming[self.peer].iter_mut() {
+            in_flight.remaining -= delta_t.inner();
+        }
+    }
+
+    fn send(&mut self, packet: &InputPacket) {
+        self.network.borrow_mut().send(self.peer, packet);
+    }
+
+    fn receive(&mut self) -> Option<InputPacket> {
+        let mut network = self.network.borrow_mut();
+        // Jitter may cause later packets to arrive first.
+        let incoming = &mut network.incoming[self.peer];
+        let index = incoming.iter().position(|p| p.remaining <= 0.0)?;
+        let packet = incoming.remove(index).packet;
+        network.stats.delivered += 1;
+        Some(packet)
+    }
+}
+
+/// Where a peer's input comes from each step.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputSource {
+    /// The inputs in order, starting over once they run out.
+    Scripted(Vec<PlayerInput>),
+    /// A random input every step, from the seed.
+    Random { seed: u64 },
+}
+
+/// Everything the harness found, for every peer combined.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HarnessSummary {
+    pub steps: u32,
+    /// The lowest frame every peer has confirmed, counted from the start of the run so it doesn't wrap.
+    pub confirmed_frame: u64,
+    /// The number of frames at least two peers confirmed, whose states were compared.
+    pub compared_frames: u32,
+    /// Frames where the confirmed states of two peers differed, in the order they were found.
+    /// Frames are counted from the start of the run, so they don't wrap.
+    pub mismatched_frames: Vec<u64>,
+    /// The number of peers that detected a desync from remote checksums.
+    pub desyncs: u32,
+    pub rollbacks: u32,
+    pub max_rollback_depth: u16,
+    pub resimulated_frames: u32,
+    pub network: NetworkStats,
+}
+impl HarnessSummary {
+    /// Panics unless every peer confirmed the same states and none detected a desync.
+    pub fn assert_consistent(&self) {
+        assert!(
+            self.mismatched_frames.is_empty() && self.desyncs == 0 && self.compared_frames > 0,
+            "peers diverged: {:?}",
+            self
+        );
+    }
+}
+
+/// Runs several games in one process, connected by a simulated network.
+/// Every peer is a player, with peer `i` controlling player `i`.
+pub struct Harness {
+    games: Vec<Game<SimulatedTransport>>,
+    inputs: Vec<InputSource>,
+    input_rngs: Vec<Rng>,
+    network: Rc<RefCell<Network>>,
+    confirmed: Rc<RefCell<ConfirmedStates>>,
+    step: u32,
+}
+impl Harness {
+    /// Creates a peer for every input source.
+    ///
+    /// Panics if there are more sources than `MAX_PLAYERS`.
+    pub fn new(network: NetworkConfig, inputs: Vec<InputSource>) -> Self {
+        assert!(inputs.len() <= MAX_PLAYERS, "too many peers");
+
+        let players: Vec<PlayerId> = (0..inputs.len()).map(|i| (i as u8).into()).collect();
+        let session = Session::from_players(&players);
+        let network = Rc::new(RefCell::new(Network {
+            rng: Rng::new(network.seed),
+            config: network,
+            step: 0,
+            incoming: vec![vec![]; inputs.len()],
+            stats: NetworkStats::default(),
+        }));
+
+        let confirmed = Rc::new(RefCell::new(ConfirmedStates::new(inputs.len())));
+        let games = players
+            .iter()
+            .enumerate()
+            .map(|(peer, player)| {
+                let transport = SimulatedTransport {
+                    peer,
+                    network: network.clone(),
+                };
+                let mut game =
+                    Game::new(HARNESS_DELTA_T.into(), *player, session.clone(), transport);
+                let confirmed = confirmed.clone();
+                game.set_on_confirmed(move |state| confirmed.borrow_mut().confirm(peer, state));
+                game
+            })
+            .collect();
+        let input_rngs = inputs
+            .iter()
+            .map(|source| match source {
+                InputSource::Random { seed } => Rng::new(*seed),
+                InputSource::Scripted(_) => Rng::new(0),
+            })
+            .collect();
+
+        Self {
+            games,
+            confirmed,
+            inputs,
+            input_rngs,
+            network,
+            step: 0,
+        }
+    }
+
+    pub fn games(&self) -> &[Game<SimulatedTransport>] {
+        &self.games
+    }
+
+    /// Updates every peer once with its next input.
+    pub fn step(&mut self) {
+        for (peer, game) in self.games.iter_mut().enumerate() {
+            let input = match &self.inputs[peer] {
+                InputSource::Scripted(inputs) if inputs.is_empty() => PlayerInput::new(),
+                InputSource::Scripted(inputs) => inputs[self.step as usize % inputs.len()],
+                InputSource::Random { .. } => self.input_rngs[peer].u64().into(),
+            };
+            game.set_local_input(input);
+            game.update(HARNESS_DELTA_T);
+        }
+
+        self.step += 1;
+        self.network.borrow_mut().step = self.step;
+    }
+
+    pub fn run(&mut self, steps: u32) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    /// Compares the confirmed states of every peer and totals up their rollbacks.
+    pub fn summary(&self) -> HarnessSummary {
+        let confirmed = self.confirmed.borrow();
+        let mut summary = HarnessSummary {
+            steps: self.step,
+            confirmed_frame: confirmed.confirmed_frame(),
+            compared_frames: confirmed.compared,
+            mismatched_frames: confirmed.mismatched.clone(),
+            desyncs: self.games.iter().filter(|g| g.desync().is_some()).count() as u32,
+            rollbacks: 0,
+            max_rollback_depth: 0,
+            resimulated_frames: 0,
+            network: self.network.borrow().stats,
+        };
+        for game in &self.games {
+            let stats = game.rollback_stats();
+            summary.rollbacks += stats.rollbacks;
+            summary.max_rollback_depth = summary.max_rollback_depth.max(stats.max_depth);
+            summary.resimulated_frames += stats.resimulated_frames;
+        }
+
+        summary
+    }
+}
+
+/// Compares the states peers confirm, keeping each only until every peer has confirmed its frame.
+struct ConfirmedStates {
+    /// The next frame each peer confirms, counted from the start of the run.
+    next_frames: Vec<u64>,
+    /// The first state confirmed for each frame not every peer has reached, and how many peers have.
+    pending: HashMap<u64, (State, usize)>,
+    compared: u32,
+    mismatched: Vec<u64>,
+}
+impl ConfirmedStates {
+    /// Every peer starts out having confirmed the initial state.
+    fn new(peers: usize) -> Self {
+        let mut confirmed = Self {
+            next_frames: vec![0; peers],
+            pending: HashMap::new(),
+            compared: 0,
+            mismatched: vec![],
+        };
+        for peer in 0..peers {
+            confirmed.confirm(peer, &State::new());
+        }
+        confirmed
+    }
+
+    /// Records the next state the peer confirmed, comparing it with the state the first peer confirmed for the frame.
+    fn confirm(&mut self, peer: usize, state: &State) {
+        let frame = self.next_frames[peer];
+        self.next_frames[peer] += 1;
+
+        let (first, seen) = self.pending.entry(frame).or_insert((*state, 0));
+        *seen += 1;
+        if *seen == 2 {
+            self.compared += 1;
+        }
+        if first != state && !self.mismatched.contains(&frame) {
+            self.mismatched.push(frame);
+        }
+
+        if *seen == self.next_frames.len() {
+            self.pending.remove(&frame);
+        }
+    }
+
+    /// Returns the lowest frame every peer has confirmed.
+    fn confirmed_frame(&self) -> u64 {
+        let next = self.next_frames.iter().min().copied().unwrap_or(0);
+        next.saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_inputs(peers: u64) -> Vec<InputSource> {
+        (0..peers)
+            .map(|i| InputSource::Random { seed: i + 1 })
+            .collect()
+    }
+
+    #[test]
+    fn peers_agree_on_a_clean_network() {
+        let mut harness = Harness::new(NetworkConfig::default(), random_inputs(4));
+        harness.run(200);
+
+        let summary = harness.summary();
+        summary.assert_consistent();
+        assert!(summary.confirmed_frame >= 190);
+        assert_eq!(0, summary.network.dropped);
+    }
+
+    #[test]
+    fn peers_agree_through_spikes_reordering_and_drops() {
+        let network = NetworkConfig {
+            latency: 0.03.into(),
+            jitter: 0.01.into(),
+            packet_loss: 0.05,
+            seed: 7,
+            script: vec![
+                ScriptedCondition {
+                    from_step: 40,
+                    to_step: 60,
+                    sender: Some(1),
+                    condition: NetworkCondition::LatencySpike(0.1.into()),
+                },
+                ScriptedCondition {
+                    from_step: 80,
+                    to_step: 120,
+                    sender: None,
+                    condition: NetworkCondition::Reorder(0.08.into()),
+                },
+                ScriptedCondition {
+                    from_step: 150,
+                    to_step: 160,
+                    sender: Some(2),
+                    condition: NetworkCondition::Outage,
+                },
+                ScriptedCondition {
+                    from_step: 200,
+                    to_step: 240,
+                    sender: None,
+                    condition: NetworkCondition::PacketLoss(0.4),
+                },
+            ],
+        };
+        let mut harness = Harness::new(network, random_inputs(3));
+        harness.run(400);
+
+        let summary = harness.summary();
+        summary.assert_consistent();
+        assert!(summary.confirmed_frame >= 300, "{:?}", summary);
+        // Confirmed frames jump ahead after outages, but each one is still compared.
+        assert!(summary.compared_frames as u64 > summary.confirmed_frame);
+        assert!(summary.rollbacks > 0);
+        assert!(summary.max_rollback_depth > 1);
+        assert!(summary.resimulated_frames >= summary.rollbacks);
+        assert!(summary.network.dropped > 0);
+    }
+
+    #[test]
+    fn outage_stalls_every_peer() {
+        let network = NetworkConfig {
+            script: vec![ScriptedCondition {
+                from_step: 0,
+                to_step: u32::MAX,
+                sender: None,
+                condition: NetworkCondition::Outage,
+            }],
+            ..Default::default()
+        };
+        let mut harness = Harness::new(network, random_inputs(2));
+        harness.run(60);
+
+        let summary = harness.summary();
+        assert_eq!(summary.network.sent, summary.network.dropped);
+        assert_eq!(0, summary.network.delivered);
+        // Nothing is confirmed, and prediction stops once it gets too far ahead.
+        assert_eq!(0, summary.confirmed_frame);
+        for game in harness.games() {
+            assert!(game.current_state().frame().inner() < 20);
+        }
+    }
+
+    #[test]
+    fn scripted_inputs_repeat() {
+        let inputs = vec![
+            InputSource::Scripted(vec![1.into(), 2.into(), 3.into()]),
+            InputSource::Scripted(vec![]),
+        ];
+        let mut harness = Harness::new(NetworkConfig::default(), inputs);
+        harness.run(10);
+        harness.summary().assert_consistent();
+    }
+
+    #[test]
+    fn runs_are_deterministic() {
+        let network = NetworkConfig {
+            latency: 0.02.into(),
+            jitter: 0.05.into(),
+            packet_loss: 0.1,
+            seed: 3,
+            script: vec![],
+        };
+        let mut a = Harness::new(network.clone(), random_inputs(3));
+        let mut b = Harness::new(network, random_inputs(3));
+        a.run(100);
+        b.run(100);
+
+        assert_eq!(a.summary(), b.summary());
+        assert_eq!(
+            a.games()[0].confirmed_state(),
+            b.games()[0].confirmed_state()
+        );
+    }
+
+    #[test]
+    fn confirmed_states_are_compared_and_dropped_once_every_peer_has_them() {
+        let state = State::new();
+        let mut other = State::new();
+        other.apply_input(0.into(), 5.into());
+
+        let mut confirmed = ConfirmedStates::new(3);
+        confirmed.confirm(0, &state);
+        confirmed.confirm(0, &state);
+        confirmed.confirm(1, &other);
+        assert_eq!(2, confirmed.compared);
+        assert_eq!(vec![1], confirmed.mismatched);
+        assert_eq!(0, confirmed.confirmed_frame());
+        assert_eq!(2, confirmed.pending.len());
+
+        confirmed.confirm(2, &state);
+        confirmed.confirm(2, &state);
+        assert_eq!(3, confirmed.compared);
+        assert_eq!(vec![1], confirmed.mismatched);
+        assert_eq!(1, confirmed.confirmed_frame());
+        assert_eq!(vec![&2], confirmed.pending.keys().collect::<Vec<_>>());
+    }
+}
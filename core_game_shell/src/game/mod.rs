@@ -1,78 +1,1128 @@
+mod checksums;
 mod frame;
 mod game_timer;
+#[cfg(any(test, feature = "std"))]
+mod harness;
 mod player_id;
+#[cfg(any(test, feature = "std"))]
+mod replay;
 mod rollback_controls;
+mod scripts;
+mod session;
+mod snapshots;
 mod state;
+mod systems;
+mod transport;
 
-use crate::math::sequences::sequence_a_after_b_u16;
+#[cfg(any(test, feature = "std"))]
+use crate::script::{ScriptEngine, ScriptError, Scripts};
+use crate::{
+    ecs::Schedule,
+    events::{Event, EventBus, EventNotice, EventQueue, GameEvent},
+    player_input::PlayerInput,
+    time::{Seconds, TickRate},
+};
+use checksums::*;
+pub use frame::*;
 use game_timer::*;
-use player_id::*;
+#[cfg(any(test, feature = "std"))]
+pub use harness::*;
+pub use player_id::*;
+#[cfg(any(test, feature = "std"))]
+pub use replay::*;
 use rollback_controls::*;
+use scripts::*;
+pub use session::*;
+pub use snapshots::*;
 use state::*;
+#[cfg(any(test, feature = "std"))]
+use std::boxed::Box;
+pub use transport::*;
 
-const MAX_TICKS_PER_UPDATE: usize = 10;
-type Players = [Option<PlayerId>; 12];
+const MAX_TICKS_PER_UPDATE: u32 = 10;
+/// The number of frames that can be simulated ahead of the last confirmed frame.
+const MAX_PREDICTION_FRAMES: u16 = 8;
+/// The number of frames kept for rolling back to.
+const SNAPSHOT_FRAMES: usize = 32;
+/// The bytes set aside for the deltas between snapshots.
+const SNAPSHOT_POOL_BYTES: usize = 64 * 1024;
 
-pub struct Game {
+/// What happened during a single call to `Game::update`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UpdateReport {
+    /// The number of ticks that were simulated.
+    pub ticks: u32,
+    /// The number of ticks that were skipped while waiting on remote input.
+    pub stalled_ticks: u32,
+    /// The number of ticks that were skipped because the update fell too far behind.
+    /// Anything above zero means the host can't keep up with the tick rate.
+    pub dropped_ticks: u32,
+}
+
+/// How much rolling back a game has done.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct RollbackStats {
+    /// The number of times the game rolled back to a mispredicted frame.
+    pub rollbacks: u32,
+    /// The most frames rolled back at once.
+    pub max_depth: u16,
+    /// The number of frames simulated again after rolling back.
+    pub resimulated_frames: u32,
+}
+
+/// A callback for every state as it is confirmed.
+#[cfg(any(test, feature = "std"))]
+type OnConfirmed = Box<dyn FnMut(&State)>;
+
+pub struct Game<T> {
+    tick_rate: TickRate,
     tick_timer: GameTimer,
-    players: Players,
+    local_player: PlayerId,
+    local_input: PlayerInput,
+    session: Session,
+    /// The last frame each remote peer has confirmed input for.
+    acks: [Frame; MAX_PLAYERS],
+    /// The last frame each remote peer reported being on, and how far it was ahead of its slowest peer.
+    remote_frames: [Option<(Frame, i8)>; MAX_PLAYERS],
     controls: RollbackControls,
+    schedule: Schedule,
+    transport: T,
+    /// Checksums of the local confirmed states.
+    checksums: ChecksumHistory,
+    /// Checksums of the confirmed states reported by each remote peer.
+    remote_checksums: [ChecksumHistory; MAX_PLAYERS],
+    desync: Option<Event>,
+    events: EventBus,
+    /// The states since the confirmed one, for rolling back to a mispredicted frame.
+    snapshots: SnapshotRing<SNAPSHOT_FRAMES, SNAPSHOT_POOL_BYTES>,
     confirmed_state: State,
     current_state: State,
+    rollback_stats: RollbackStats,
+    scripts: GameScripts,
+    /// Called with every state as it is confirmed, in frame order.
+    #[cfg(any(test, feature = "std"))]
+    on_confirmed: Option<OnConfirmed>,
 }
-impl Game {
+impl<T> Game<T>
+where
+    T: Transport,
+{
+    /// Creates a new game for the given session, controlled locally by `local_player`.
+    /// If the local slot is a spectator, no input is ever sent.
+    pub fn new(
+        tick_rate: TickRate,
+        local_player: PlayerId,
+        session: Session,
+        transport: T,
+    ) -> Self {
+        let state = State::new();
+        let mut controls = RollbackControls::new();
+        let mut checksums = ChecksumHistory::new();
+        checksums.insert(state.frame(), state.checksum());
+        let mut snapshots = SnapshotRing::new();
+        snapshots.push(&state);
+        let mut acks = [state.frame().decrement(); MAX_PLAYERS];
+        for player in session.players() {
+            if let Some((joined, left)) = session.frames(player) {
+                controls.add_player(player, joined);
+                acks[player.index()] = joined.decrement();
+                if let Some(left) = left {
+                    controls.remove_player(player, left);
+                }
+            }
+        }
+
+        let mut game = Self {
+            tick_rate,
+            tick_timer: GameTimer::new(tick_rate),
+            local_player,
+            local_input: PlayerInput::new(),
+            session,
+            acks,
+            remote_frames: [None; MAX_PLAYERS],
+            controls,
+            schedule: systems::schedule(),
+            transport,
+            checksums,
+            remote_checksums: [ChecksumHistory::new(); MAX_PLAYERS],
+            desync: None,
+            events: EventBus::new(),
+            snapshots,
+            confirmed_state: state,
+            current_state: state,
+            rollback_stats: RollbackStats::default(),
+            scripts: GameScripts::default(),
+            #[cfg(any(test, feature = "std"))]
+            on_confirmed: None,
+        };
+        game.add_input_delay_padding();
+
+        game
+    }
+
+    /// Runs the scripts after the systems every tick. Must be called before the first update.
+    /// All peers must use the same scripts, so they fail the same way and stay in lockstep.
+    #[cfg(any(test, feature = "std"))]
+    pub fn set_scripts(&mut self, scripts: Scripts<Box<dyn ScriptEngine>>) {
+        self.scripts = GameScripts::new(scripts);
+    }
+
+    /// Calls `on_confirmed` with every state after the initial one as it is confirmed, in frame order.
+    #[cfg(any(test, feature = "std"))]
+    pub fn set_on_confirmed(&mut self, on_confirmed: impl FnMut(&State) + 'static) {
+        self.on_confirmed = Some(Box::new(on_confirmed));
+    }
+
+    /// Returns the first error scripts raised since the last call, including on frames that were rolled back.
+    #[cfg(any(test, feature = "std"))]
+    pub fn take_script_error(&mut self) -> Option<ScriptError> {
+        self.scripts.take_error()
+    }
+
+    /// Adds someone to the game, starting at the given frame.
+    /// All peers must make the same call so that the join is applied on the same frame.
+    pub fn join(
+        &mut self,
+        id: PlayerId,
+        frame: Frame,
+        settings: PlayerSettings,
+    ) -> Result<(), SessionError> {
+        self.check_unconfirmed(frame)?;
+        self.session.join(id, frame, settings)?;
+
+        if settings.kind == SlotKind::Player {
+            self.controls.add_player(id, frame);
+            self.controls.invalidate(frame);
+            self.acks[id.index()] = frame.decrement();
+            if id == self.local_player {
+                self.add_input_delay_padding();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes someone from the game, starting at the given frame.
+    /// All peers must make the same call so that the leave is applied on the same frame.
+    pub fn leave(&mut self, id: PlayerId, frame: Frame) -> Result<(), SessionError> {
+        self.check_unconfirmed(frame)?;
+        self.session.leave(id, frame)?;
+        self.controls.remove_player(id, frame);
+        self.controls.invalidate(frame);
+
+        Ok(())
+    }
+
+    /// Returns the session for the game.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Ensures the frame can still be resimulated.
+    fn check_unconfirmed(&self, frame: Frame) -> Result<(), SessionError> {
+        if self.confirmed_state.frame().is_after(frame) {
+            Err(SessionError::FrameConfirmed(frame))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the number of frames local input is delayed by.
+    fn input_delay(&self) -> u8 {
+        match self.session.settings(self.local_player) {
+            Some(settings) if settings.kind == SlotKind::Player => settings.input_delay,
+            _ => 0,
+        }
+    }
+
+    /// Fills the frames covered by the local input delay with default input.
+    fn add_input_delay_padding(&mut self) {
+        if let Some((joined, _)) = self.session.frames(self.local_player) {
+            let mut frame = joined;
+            for _ in 0..self.input_delay() {
+                self.controls
+                    .add_local_input(self.local_player, frame, PlayerInput::new());
+                frame = frame.increment();
+            }
+        }
+    }
+
+    /// Sets the input the local player will use for the next ticks.
+    pub fn set_local_input(&mut self, input: PlayerInput) {
+        self.local_input = input;
+    }
+
+    /// Returns the last state where all input was confirmed.
+    pub fn confirmed_state(&self) -> &State {
+        &self.confirmed_state
+    }
+
+    /// Returns the current, possibly predicted, state.
+    pub fn current_state(&self) -> &State {
+        &self.current_state
+    }
+
+    /// Returns the first frame where the confirmed state differed from a remote peer, if any.
+    pub fn desync(&self) -> Option<&Event> {
+        self.desync.as_ref()
+    }
+
+    /// Returns the next event notice, oldest first.
+    /// Hosts should poll until this returns `None` after every update.
+    pub fn poll_event(&mut self) -> Option<EventNotice> {
+        self.events.poll()
+    }
+
+    /// Returns events from frames that have not been confirmed yet.
+    pub fn pending_events(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events.pending()
+    }
+
+    /// Returns the memory used by each snapshot kept for rollback, oldest first.
+    pub fn snapshot_memory(&self) -> impl Iterator<Item = SnapshotMemory> + '_ {
+        self.snapshots.memory()
+    }
+
+    /// Returns the state before the current one, if it is still held.
+    /// Render between this and the current state using `interpolation_alpha`.
+    pub fn previous_state(&self) -> Option<State> {
+        self.snapshots.get(self.current_state.frame().decrement())
+    }
+
+    /// Returns how far time has moved from the current state towards the next one, from 0 to 1.
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.tick_timer.alpha()
+    }
+
+    /// Returns how much the game has rolled back since it was created.
+    pub fn rollback_stats(&self) -> RollbackStats {
+        self.rollback_stats
+    }
+
+    /// Returns the multiplier applied to the tick length to stay in step with remote peers.
+    pub fn time_dilation(&self) -> f32 {
+        self.tick_timer.dilation()
+    }
+
     /// Performs an update on the game.
-    pub fn update(&mut self, delta_t_seconds: f32) {
-        self.controls.poll_remote();
+    pub fn update(&mut self, delta_t_seconds: f32) -> UpdateReport {
+        let delta_t: Seconds = delta_t_seconds.into();
+        self.transport.update(delta_t);
+        self.poll_remote();
+        self.sync_time();
 
         // Gaffer on games fix your timestep.
         // https://gafferongames.com/post/fix_your_timestep/
-        self.tick_timer.update(delta_t_seconds);
-        for _ in 0..MAX_TICKS_PER_UPDATE {
+        self.tick_timer.update(delta_t);
+        let mut report = UpdateReport {
+            ticks: 0,
+            stalled_ticks: 0,
+            dropped_ticks: 0,
+        };
+        while report.ticks + report.stalled_ticks < MAX_TICKS_PER_UPDATE {
             match self.tick_timer.tick() {
-                TimerResult::Ticked => self.execute_ticks_with_rollback(),
+                TimerResult::Ticked if self.execute_ticks_with_rollback() => report.ticks += 1,
+                TimerResult::Ticked => report.stalled_ticks += 1,
                 TimerResult::NotTicked => break,
             }
         }
+        if report.ticks + report.stalled_ticks == MAX_TICKS_PER_UPDATE {
+            // Avoid the spiral of death, where catching up takes longer than the time it covers.
+            report.dropped_ticks = self.tick_timer.drop_ticks();
+        }
+
+        // Always send, even if stalled, so that lost inputs and acks get resent.
+        self.send_local_input();
+        self.check_desync();
+
+        report
     }
 
-    /// Performs all ticks
-    fn execute_ticks_with_rollback(&mut self) {
-        let current_frame = self.current_state.frame();
+    /// Returns how many frames the local game is ahead of the remote player, as last reported by them.
+    fn local_frame_advantage(&self, player: PlayerId) -> Option<i16> {
+        let (remote_frame, _) = self.remote_frames[player.index()]?;
+        Some(self.current_state.frame().offset_from(remote_frame))
+    }
+
+    /// Returns the remote players that are still in the game.
+    fn active_remote_players(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.session.players().filter(move |player| {
+            let has_left = matches!(self.session.frames(*player), Some((_, Some(_))));
+            *player != self.local_player && !has_left
+        })
+    }
+
+    /// Stretches or shrinks ticks so that the local game stays in step with the furthest behind peer.
+    /// Reported frames are stale by the latency, which cancels out when comparing both sides, like GGPO.
+    fn sync_time(&mut self) {
+        // Spectators aren't reported on, so they follow by stalling instead.
+        if !self.session.is_player(self.local_player) {
+            return;
+        }
+
+        let mut advantage: Option<f32> = None;
+        for player in self.active_remote_players() {
+            let local = match self.local_frame_advantage(player) {
+                Some(local) => local,
+                None => continue,
+            };
+            let remote = self.remote_frames[player.index()].map_or(0, |(_, a)| a);
+            let difference = (local as f32 - remote as f32) / 2.0;
+            advantage = Some(advantage.map_or(difference, |a| a.max(difference)));
+        }
+
+        self.tick_timer
+            .set_frame_advantage(advantage.unwrap_or(0.0));
+    }
+
+    /// Compares remote checksums against local ones, keeping the earliest mismatch.
+    fn check_desync(&mut self) {
+        for player in self.session.players() {
+            for (frame, remote) in self.remote_checksums[player.index()].iter() {
+                let local = match self.checksums.get(frame) {
+                    Some(local) if local != remote => local,
+                    _ => continue,
+                };
+
+                let is_earliest = match &self.desync {
+                    Some(Event::DesyncDetected { frame: first, .. }) => first.is_after(frame),
+                    _ => true,
+                };
+                if is_earliest {
+                    self.desync = Some(Event::DesyncDetected {
+                        frame,
+                        local,
+                        remote,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Feeds all received remote input into the controls.
+    fn poll_remote(&mut self) {
+        while let Some(packet) = self.transport.receive() {
+            let player = packet.player();
+            if player == self.local_player || !self.session.is_player(player) {
+                continue;
+            }
+
+            if packet.ack().is_after(self.acks[player.index()]) {
+                self.acks[player.index()] = packet.ack();
+            }
+
+            // Packets can arrive out of order, so only keep the newest frame.
+            let (frame, advantage) = packet.frame_advantage();
+            let is_newer = match self.remote_frames[player.index()] {
+                Some((previous, _)) => frame.is_after(previous),
+                None => true,
+            };
+            if is_newer {
+                self.remote_frames[player.index()] = Some((frame, advantage));
+            }
+
+            let (frame, checksum) = packet.checksum();
+            self.remote_checksums[player.index()].insert(frame, checksum);
+
+            for (frame, input) in packet.inputs() {
+                self.controls.add_remote_input(player, frame, input);
+            }
+        }
+    }
+
+    /// Sends all local input that has not been acknowledged by every remote peer.
+    fn send_local_input(&mut self) {
+        // Spectators never send
+        if !self.session.is_player(self.local_player) {
+            return;
+        }
+
+        let end_frame = self.current_state.frame() + self.input_delay() as u16;
 
-        // Take checkpoint and iterate on things
-        let should_rollback = sequence_a_after_b_u16(
-            self.controls.last_confirmed_frame().inner(),
-            self.confirmed_state.frame().inner(),
+        // Always resend a few recent frames, as spectators never acknowledge anything.
+        let mut start_frame = end_frame - MAX_PREDICTION_FRAMES;
+        let mut frame_advantage: Option<i16> = None;
+        for player in self.active_remote_players() {
+            if let Some(advantage) = self.local_frame_advantage(player) {
+                frame_advantage = Some(frame_advantage.map_or(advantage, |a| a.max(advantage)));
+            }
+
+            let unacked = self.acks[player.index()].increment();
+            if start_frame.is_after(unacked) {
+                start_frame = unacked;
+            }
+        }
+
+        let ack = match self.controls.last_confirmed_frame() {
+            Some(frame) => frame,
+            None => self.current_state.frame().decrement(),
+        };
+
+        let mut packet = InputPacket::new(self.local_player, ack, start_frame);
+        let checksum_frame = self.confirmed_state.frame();
+        let checksum = match self.checksums.get(checksum_frame) {
+            Some(checksum) => checksum,
+            None => self.confirmed_state.checksum(),
+        };
+        packet.set_checksum(checksum_frame, checksum);
+        packet.set_frame_advantage(
+            self.current_state.frame(),
+            frame_advantage
+                .unwrap_or(0)
+                .clamp(i8::MIN as i16, i8::MAX as i16) as i8,
         );
-        if should_rollback {
-            let working_state = &mut self.current_state;
-            working_state.copy_from(&self.confirmed_state);
-
-            // Iterate over all frames that need to roll back.
-            // Skip current frame though as we'll handle that after.
-            while working_state.frame() != current_frame {
-                Self::tick(working_state, &self.players, &self.controls);
-
-                // Checkpoint state if needed
-                if self.controls.is_confirmed(working_state.frame()) {
-                    self.confirmed_state.copy_from(working_state);
+        let mut frame = start_frame;
+        while end_frame.is_after(frame) {
+            if !packet.push(self.controls.get_player_input(self.local_player, frame)) {
+                break;
+            }
+            frame = frame.increment();
+        }
+
+        self.transport.send(&packet);
+    }
+
+    /// Performs all ticks, returning false if stalled waiting on remote input.
+    fn execute_ticks_with_rollback(&mut self) -> bool {
+        let current_frame = self.current_state.frame();
+
+        // Stall until remote input catches up.
+        // With input delay the confirmed input can be ahead of the current frame.
+        if let Some(last_confirmed) = self.controls.last_confirmed_frame() {
+            let is_ahead = current_frame.is_after(last_confirmed);
+            if is_ahead && current_frame.frames_since(last_confirmed) > MAX_PREDICTION_FRAMES {
+                return false;
+            }
+        }
+
+        let input_frame = current_frame + self.input_delay() as u16;
+        self.controls
+            .add_local_input(self.local_player, input_frame, self.local_input);
+
+        // Roll back to the first mispredicted frame and resimulate up to the current one.
+        // Skip current frame though as we'll handle that after.
+        let mut events = EventQueue::new();
+        if let Some(incorrect_frame) = self.controls.first_incorrect_frame() {
+            if current_frame.is_after(incorrect_frame) {
+                self.current_state = match self.snapshots.restore(incorrect_frame) {
+                    Some(state) => state,
+                    None => {
+                        // The snapshot was evicted, so start over from the confirmed state.
+                        self.snapshots.clear();
+                        self.snapshots.push(&self.confirmed_state);
+                        self.confirmed_state
+                    }
+                };
+                self.events.rollback(self.current_state.frame());
+
+                let depth = current_frame.frames_since(self.current_state.frame());
+                let stats = &mut self.rollback_stats;
+                stats.rollbacks += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+                stats.resimulated_frames += depth as u32;
+
+                while self.current_state.frame() != current_frame {
+                    tick(
+                        &mut self.current_state,
+                        &self.session,
+                        &self.controls,
+                        &self.schedule,
+                        &mut self.scripts,
+                        &mut events,
+                    );
+                    self.events.record(&events);
+                    self.snapshots.push(&self.current_state);
                 }
+
+                self.events.cancel_stale();
             }
         }
+        self.controls.clear_incorrect_frame();
 
         // Perform regular tick
-        Self::tick(&mut self.current_state, &self.players, &self.controls);
+        tick(
+            &mut self.current_state,
+            &self.session,
+            &self.controls,
+            &self.schedule,
+            &mut self.scripts,
+            &mut events,
+        );
+        self.events.record(&events);
+        self.snapshots.push(&self.current_state);
+
+        // The confirmed state advances once the input for the frame before it is confirmed.
+        let mut confirmed_frame = self.confirmed_state.frame();
+        while confirmed_frame != self.current_state.frame()
+            && self.controls.is_confirmed(confirmed_frame)
+        {
+            let next = confirmed_frame.increment();
+            match self.snapshots.checksum(next) {
+                Some(checksum) => self.checksums.insert(next, checksum),
+                None => break,
+            }
+            self.session.confirm(confirmed_frame);
+            #[cfg(any(test, feature = "std"))]
+            if let Some(on_confirmed) = &mut self.on_confirmed {
+                if let Some(state) = self.snapshots.get(next) {
+                    on_confirmed(&state);
+                }
+            }
+            confirmed_frame = next;
+        }
+        if confirmed_frame != self.confirmed_state.frame() {
+            if let Some(state) = self.snapshots.get(confirmed_frame) {
+                self.confirmed_state = state;
+            }
+        }
+
+        self.events.confirm(self.confirmed_state.frame());
+
+        true
+    }
+}
+
+/// Ticks the given state after sourcing all player input.
+/// The events emitted during the tick are left in `events`.
+fn tick(
+    state: &mut State,
+    session: &Session,
+    controls: &RollbackControls,
+    schedule: &Schedule,
+    scripts: &mut GameScripts,
+    events: &mut EventQueue,
+) {
+    events.begin(state.frame());
+    for player in session.players() {
+        if session.is_joining(player, state.frame()) {
+            events.emit(Event::PlayerJoined { player });
+        } else if session.is_leaving(player, state.frame()) {
+            events.emit(Event::PlayerLeft { player });
+        }
+    }
+
+    for player in session.active_players(state.frame()) {
+        let input = controls.get_player_input(player, state.frame());
+
+        state.apply_input(player, input);
+    }
+
+    scripts.tick(state, schedule, events);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{InstructionBudget, ScriptValue, BUDGET_STEP};
+    use std::collections::HashMap;
+
+    const DELTA_T: f32 = 1.0 / 60.0;
+
+    fn games(config: LoopbackConfig) -> (Game<LoopbackTransport>, Game<LoopbackTransport>) {
+        games_with_session(config, Session::from_players(&[0.into(), 1.into()]))
+    }
+
+    fn games_with_session(
+        config: LoopbackConfig,
+        session: Session,
+    ) -> (Game<LoopbackTransport>, Game<LoopbackTransport>) {
+        let (a, b) = LoopbackTransport::pair(config);
+        (
+            Game::new(DELTA_T.into(), 0.into(), session.clone(), a),
+            Game::new(DELTA_T.into(), 1.into(), session, b),
+        )
+    }
+
+    /// Runs both games, returning the confirmed states each one went through.
+    fn run(
+        a: &mut Game<LoopbackTransport>,
+        b: &mut Game<LoopbackTransport>,
+        updates: u32,
+    ) -> (HashMap<u16, State>, HashMap<u16, State>) {
+        let mut a_states = HashMap::new();
+        let mut b_states = HashMap::new();
+        for i in 0..updates {
+            a.set_local_input((i as u64 % 16).into());
+            b.set_local_input((i as u64 / 3 % 16).into());
+            a.update(DELTA_T);
+            b.update(DELTA_T);
+
+            a_states.insert(a.confirmed_state().frame().inner(), *a.confirmed_state());
+            b_states.insert(b.confirmed_state().frame().inner(), *b.confirmed_state());
+        }
+
+        (a_states, b_states)
+    }
+
+    /// Asserts that every frame both games confirmed has the same state.
+    fn assert_matching(a_states: &HashMap<u16, State>, b_states: &HashMap<u16, State>) {
+        let mut compared = 0;
+        for (frame, state) in a_states.iter() {
+            if let Some(other) = b_states.get(frame) {
+                assert_eq!(state, other);
+                compared += 1;
+            }
+        }
+        assert!(compared > 0);
+    }
+
+    fn assert_lockstep(config: LoopbackConfig, min_confirmed_frame: u16) {
+        let (mut a, mut b) = games(config);
+        let (a_states, b_states) = run(&mut a, &mut b, 300);
+
+        assert!(a.confirmed_state().frame().inner() >= min_confirmed_frame);
+        assert!(b.confirmed_state().frame().inner() >= min_confirmed_frame);
+        assert_matching(&a_states, &b_states);
+    }
+
+    #[test]
+    fn games_stay_in_lockstep() {
+        assert_lockstep(LoopbackConfig::default(), 290);
     }
 
-    /// Ticks the given state after sourcing all player input.
-    fn tick(state: &mut State, players: &Players, controls: &RollbackControls) {
-        for player in players.iter().filter_map(|p| *p) {
-            let input = controls.get_player_input(player, state.frame());
+    #[test]
+    fn games_stay_in_lockstep_with_latency_and_jitter() {
+        let config = LoopbackConfig {
+            latency: 0.05.into(),
+            jitter: 0.03.into(),
+            ..Default::default()
+        };
+        assert_lockstep(config, 250);
+    }
+
+    #[test]
+    fn snapshots_cover_unconfirmed_frames() {
+        let config = LoopbackConfig {
+            latency: 0.05.into(),
+            ..Default::default()
+        };
+        let (mut a, mut b) = games(config);
+        run(&mut a, &mut b, 100);
+
+        // Every frame since the confirmed one can be rolled back to.
+        let memory: Vec<_> = a.snapshot_memory().collect();
+        let confirmed = a.confirmed_state().frame();
+        assert!(memory.iter().any(|m| m.frame == confirmed));
+        assert_eq!(a.current_state().frame(), memory.last().unwrap().frame);
+        assert_eq!(
+            Some(a.confirmed_state().checksum()),
+            a.checksums.get(confirmed)
+        );
+    }
+
+    #[test]
+    fn games_stay_in_lockstep_with_packet_loss() {
+        let config = LoopbackConfig {
+            latency: 0.03.into(),
+            jitter: 0.02.into(),
+            packet_loss: 0.25,
+            seed: 1234,
+        };
+        assert_lockstep(config, 200);
+    }
+
+    #[test]
+    fn games_in_lockstep_do_not_desync() {
+        let (mut a, mut b) = games(LoopbackConfig {
+            latency: 0.05.into(),
+            jitter: 0.03.into(),
+            packet_loss: 0.1,
+            seed: 99,
+        });
+        run(&mut a, &mut b, 300);
+        assert_eq!(None, a.desync());
+        assert_eq!(None, b.desync());
+    }
+
+    #[test]
+    fn mismatched_checksum_raises_earliest_desync() {
+        let (transport, mut remote) = LoopbackTransport::pair(LoopbackConfig::default());
+        let session = Session::from_players(&[0.into(), 1.into()]);
+        let mut game = Game::new(DELTA_T.into(), 0.into(), session, transport);
+
+        let mut packet = InputPacket::new(1.into(), 0.into(), 0.into());
+        for _ in 0..5 {
+            packet.push(PlayerInput::new());
+        }
+        packet.set_checksum(0.into(), State::new().checksum());
+        remote.send(&packet);
+        for _ in 0..4 {
+            game.update(DELTA_T);
+        }
+        assert_eq!(None, game.desync());
+
+        let local = |frame: u16| game.checksums.get(frame.into()).unwrap();
+        let (local_1, local_2) = (local(1), local(2));
+        for (frame, bad) in [(2, 22), (1, 11), (3, 33)] {
+            packet.set_checksum(frame.into(), bad);
+            remote.send(&packet);
+            game.update(DELTA_T);
+        }
+
+        assert_ne!(local_1, local_2);
+        assert_eq!(
+            Some(&Event::DesyncDetected {
+                frame: 1.into(),
+                local: local_1,
+                remote: 11,
+            }),
+            game.desync()
+        );
+    }
+
+    #[test]
+    fn games_stay_in_lockstep_with_input_delay() {
+        let delayed = PlayerSettings {
+            input_delay: 3,
+            ..Default::default()
+        };
+        let mut session = Session::new();
+        session.join(0.into(), 0.into(), delayed).unwrap();
+        session.join(1.into(), 0.into(), delayed).unwrap();
+
+        let (mut a, mut b) = games_with_session(
+            LoopbackConfig {
+                latency: 0.05.into(),
+                ..Default::default()
+            },
+            session,
+        );
+        let (a_states, b_states) = run(&mut a, &mut b, 300);
+
+        assert!(a.confirmed_state().frame().inner() >= 250);
+        assert_matching(&a_states, &b_states);
+    }
+
+    #[test]
+    fn games_stay_in_lockstep_across_the_frame_wrap() {
+        let (mut a, mut b) = games(LoopbackConfig::default());
+        // Each run confirms around a hundred frames, so this stops short of the wrap.
+        while a.confirmed_state().frame().inner() < 65_400 {
+            run(&mut a, &mut b, 100);
+        }
+        for game in [&mut a, &mut b] {
+            while game.poll_event().is_some() {}
+        }
+
+        let mut recorder = ReplayRecorder::new(&a).unwrap();
+        let mut a_states = HashMap::new();
+        let mut b_states = HashMap::new();
+        for _ in 0..20 {
+            let (a_run, b_run) = run(&mut a, &mut b, 10);
+            a_states.extend(a_run);
+            b_states.extend(b_run);
+            recorder.record(&a).unwrap();
+        }
+        assert!(a.confirmed_state().frame().inner() < 200);
+        assert!(a_states.contains_key(&u16::MAX) && a_states.contains_key(&0));
+        assert_matching(&a_states, &b_states);
+        for game in [&mut a, &mut b] {
+            let frame = game.current_state().frame();
+            assert_eq!(2, game.session().active_players(frame).count());
+            // Frame 0 comes around again, but the players joined long ago.
+            assert_eq!(None, game.poll_event());
+        }
+
+        let replay = recorder.finish();
+        assert_eq!(Ok(*a.confirmed_state()), replay.verify());
+        assert_eq!(Ok(replay.clone()), Replay::from_bytes(&replay.to_bytes()));
+    }
+
+    #[test]
+    fn player_joining_later_stays_in_lockstep() {
+        let (mut a, mut b) = games_with_session(
+            LoopbackConfig {
+                latency: 0.03.into(),
+                ..Default::default()
+            },
+            Session::from_players(&[0.into()]),
+        );
+        for game in [&mut a, &mut b] {
+            game.join(1.into(), 40.into(), PlayerSettings::default())
+                .unwrap();
+        }
+        let (a_states, b_states) = run(&mut a, &mut b, 300);
+
+        assert!(a.confirmed_state().frame().inner() >= 250);
+        assert_matching(&a_states, &b_states);
+    }
+
+    #[test]
+    fn events_are_confirmed_once_on_every_peer() {
+        let (mut a, mut b) = games_with_session(
+            LoopbackConfig {
+                latency: 0.05.into(),
+                ..Default::default()
+            },
+            Session::from_players(&[0.into()]),
+        );
+        for game in [&mut a, &mut b] {
+            game.join(1.into(), 40.into(), PlayerSettings::default())
+                .unwrap();
+        }
+
+        let mut a_notices = vec![];
+        let mut b_notices = vec![];
+        for i in 0..300 {
+            a.set_local_input((i % 16).into());
+            a.update(DELTA_T);
+            b.update(DELTA_T);
+            a_notices.extend(core::iter::from_fn(|| a.poll_event()));
+            b_notices.extend(core::iter::from_fn(|| b.poll_event()));
+        }
+
+        for notices in [&a_notices, &b_notices] {
+            let mut predicted = HashMap::new();
+            let mut confirmed = vec![];
+            for notice in notices {
+                match notice {
+                    EventNotice::Predicted(e) => {
+                        assert_eq!(None, predicted.insert(e.id, *e));
+                    }
+                    EventNotice::Confirmed(e) => {
+                        assert_eq!(Some(*e), predicted.remove(&e.id));
+                        confirmed.push((e.frame, e.event));
+                    }
+                    EventNotice::Cancelled(e) => {
+                        assert_eq!(Some(*e), predicted.remove(&e.id));
+                    }
+                }
+            }
+
+            assert!(predicted.is_empty());
+            assert_eq!(
+                vec![
+                    (0.into(), Event::PlayerJoined { player: 0.into() }),
+                    (40.into(), Event::PlayerJoined { player: 1.into() }),
+                ],
+                confirmed
+            );
+        }
+    }
+
+    /// Emits a sound with the frame as its cue, and fails on frame 3.
+    struct FrameSoundEngine;
+    impl ScriptEngine for FrameSoundEngine {
+        fn run(&mut self, _: &str) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn load(&mut self, _: &str, _: &str) -> Result<(), ScriptError> {
+            Ok(())
+        }
+
+        fn call(
+            &mut self,
+            name: &str,
+            argument: ScriptValue,
+            _: &mut InstructionBudget,
+        ) -> Result<ScriptValue, ScriptError> {
+            let frame = argument.get("frame").and_then(ScriptValue::as_integer);
+            if frame == Some(3) {
+                return Err(ScriptError::Runtime {
+                    script: name.into(),
+                    message: "frame 3".into(),
+                });
+            }
+
+            Ok(ScriptValue::List(vec![ScriptValue::Table(vec![
+                ("kind".into(), ScriptValue::String("sound".into())),
+                (
+                    "cue".into(),
+                    ScriptValue::Integer(frame.unwrap_or_default()),
+                ),
+            ])]))
+        }
+    }
+
+    fn frame_sound_scripts() -> Scripts<Box<dyn ScriptEngine>> {
+        let engine: Box<dyn ScriptEngine> = Box::new(FrameSoundEngine);
+        let mut scripts = Scripts::new(engine, BUDGET_STEP).unwrap();
+        scripts.add("sound", "").unwrap();
+        scripts
+    }
+
+    #[test]
+    fn scripts_run_on_every_tick() {
+        let (mut a, mut b) = games(LoopbackConfig {
+            latency: 0.05.into(),
+            ..Default::default()
+        });
+        a.set_scripts(frame_sound_scripts());
+        b.set_scripts(frame_sound_scripts());
+        run(&mut a, &mut b, 30);
+
+        for game in [&mut a, &mut b] {
+            let confirmed: Vec<_> = core::iter::from_fn(|| game.poll_event())
+                .filter_map(|notice| match notice {
+                    EventNotice::Confirmed(e) => Some(e.event),
+                    _ => None,
+                })
+                .filter(|e| matches!(e, Event::Sound { .. }))
+                .collect();
+            let expected: Vec<_> = (0..game.confirmed_state().frame().inner())
+                .filter(|frame| *frame != 3)
+                .map(|cue| Event::Sound { cue })
+                .collect();
+            assert_eq!(expected, confirmed);
+
+            assert!(matches!(
+                game.take_script_error(),
+                Some(ScriptError::Runtime { .. })
+            ));
+            assert_eq!(None, game.take_script_error());
+        }
+    }
+
+    #[test]
+    fn games_with_scripts_are_not_recorded() {
+        let (mut a, _) = games(LoopbackConfig::default());
+        let mut recorder = ReplayRecorder::new(&a).unwrap();
+
+        a.set_scripts(frame_sound_scripts());
+        assert_eq!(Err(ReplayError::ScriptsNotRecorded), recorder.record(&a));
+        assert!(matches!(
+            ReplayRecorder::new(&a),
+            Err(ReplayError::ScriptsNotRecorded)
+        ));
+    }
+
+    #[test]
+    fn join_on_confirmed_frame_fails() {
+        let (mut a, _) = games_with_session(
+            LoopbackConfig::default(),
+            Session::from_players(&[0.into()]),
+        );
+        for _ in 0..10 {
+            a.update(DELTA_T);
+        }
+
+        assert_eq!(
+            Err(SessionError::FrameConfirmed(5.into())),
+            a.join(1.into(), 5.into(), PlayerSettings::default())
+        );
+    }
+
+    #[test]
+    fn leave_after_disconnect_resumes_game() {
+        let (mut a, mut b) = games(LoopbackConfig {
+            latency: 0.03.into(),
+            ..Default::default()
+        });
+        let (a_states, b_states) = run(&mut a, &mut b, 60);
+
+        // b disconnects, so a stalls
+        for _ in 0..60 {
+            a.update(DELTA_T);
+        }
+        let stalled_frame = a.confirmed_state().frame();
+        let leave_frame = a.controls.last_confirmed_frame().unwrap().increment();
+        assert_eq!(stalled_frame, leave_frame);
+
+        // Leaving on a frame that was predicted rolls back and resimulates without b
+        a.leave(1.into(), leave_frame).unwrap();
+        for _ in 0..60 {
+            a.update(DELTA_T);
+        }
+
+        assert!(a.confirmed_state().frame().is_after(stalled_frame + 50));
+        assert_eq!(a.confirmed_state(), a.current_state());
+        assert_matching(&a_states, &b_states);
+    }
+
+    #[test]
+    fn spectator_follows_without_sending() {
+        let mut session = Session::from_players(&[0.into()]);
+        let spectator = PlayerSettings {
+            kind: SlotKind::Spectator,
+            input_delay: 0,
+        };
+        session.join(1.into(), 0.into(), spectator).unwrap();
+
+        let (mut a, mut b) = games_with_session(
+            LoopbackConfig {
+                latency: 0.03.into(),
+                packet_loss: 0.2,
+                ..Default::default()
+            },
+            session,
+        );
+        let (a_states, b_states) = run(&mut a, &mut b, 300);
+
+        assert!(b.confirmed_state().frame().inner() >= 250);
+        assert_eq!(None, a.transport.receive());
+        assert_matching(&a_states, &b_states);
+    }
+
+    #[test]
+    fn game_stalls_without_remote_input() {
+        let (a, _b) = LoopbackTransport::pair(LoopbackConfig {
+            packet_loss: 1.0,
+            ..Default::default()
+        });
+        let session = Session::from_players(&[0.into(), 1.into()]);
+        let mut game = Game::new(DELTA_T.into(), 0.into(), session, a);
+        for _ in 0..100 {
+            game.update(DELTA_T);
+        }
+
+        let report = game.update(DELTA_T);
+        assert_eq!(0, report.ticks);
+        assert_eq!(1, report.stalled_ticks);
+        assert_eq!(MAX_PREDICTION_FRAMES, game.current_state().frame().inner());
+        assert_eq!(0, game.confirmed_state().frame().inner());
+    }
+
+    #[test]
+    fn update_reports_dropped_ticks() {
+        let (a, _b) = LoopbackTransport::pair(LoopbackConfig::default());
+        let session = Session::from_players(&[0.into()]);
+        let mut game = Game::new(DELTA_T.into(), 0.into(), session, a);
+
+        let report = game.update(DELTA_T * 2.5);
+        assert_eq!(2, report.ticks);
+        assert_eq!(0, report.stalled_ticks);
+        assert_eq!(0, report.dropped_ticks);
+        assert!((game.interpolation_alpha() - 0.5).abs() < 0.01);
+
+        let report = game.update(DELTA_T * 25.0);
+        assert_eq!(MAX_TICKS_PER_UPDATE, report.ticks);
+        assert_eq!(15, report.dropped_ticks);
+        assert_eq!(12, game.current_state().frame().inner());
+    }
+
+    #[test]
+    fn previous_state_is_one_frame_behind() {
+        let (mut a, mut b) = games(LoopbackConfig::default());
+        run(&mut a, &mut b, 20);
+
+        let previous = a.previous_state().unwrap();
+        assert_eq!(a.current_state().frame(), previous.frame().increment());
+    }
+
+    #[test]
+    fn peer_that_started_early_slows_down() {
+        let (mut a, mut b) = games(LoopbackConfig {
+            latency: 0.05.into(),
+            ..Default::default()
+        });
+        for _ in 0..6 {
+            a.update(DELTA_T);
+        }
 
-            state.apply_input(player, input);
+        let mut a_dilation: f32 = 1.0;
+        let mut b_dilation: f32 = 1.0;
+        for _ in 0..300 {
+            a.update(DELTA_T);
+            b.update(DELTA_T);
+            a_dilation = a_dilation.max(a.time_dilation());
+            b_dilation = b_dilation.min(b.time_dilation());
         }
 
-        state.tick();
+        assert!(a_dilation > 1.0);
+        assert!(b_dilation < 1.0);
+        let a_frame = a.current_state().frame().inner();
+        let b_frame = b.current_state().frame().inner();
+        assert!(a_frame.abs_diff(b_frame) <= 1);
     }
 }
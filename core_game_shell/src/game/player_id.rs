@@ -1,2 +1,18 @@
+/// The maximum number of players a game can hold.
+pub const MAX_PLAYERS: usize = 12;
+
+type N = u8;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub struct PlayerId;
+pub struct PlayerId(N);
+impl PlayerId {
+    /// Returns the index of the player.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+impl From<u8> for PlayerId {
+    fn from(id: N) -> Self {
+        Self(id)
+    }
+}
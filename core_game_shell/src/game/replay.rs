@@ -0,0 +1,461 @@
+use super::{systems, tick, Frame, Game, GameScripts, PlayerId, RollbackControls, Session, State};
+use crate::{
+    bytes::{ByteReader, ByteWriter},
+    events::EventQueue,
+    player_input::PlayerInput,
+    time::TickRate,
+};
+use std::{fs, io, path::Path, vec::Vec};
+
+const MAGIC: &[u8; 4] = b"CGRP";
+const VERSION: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayError {
+    Io(io::ErrorKind),
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    Truncated,
+    TrailingBytes,
+    /// The state after ticking `frame` did not match the recorded checksum.
+    ChecksumMismatch {
+        frame: Frame,
+        expected: u32,
+        actual: u32,
+    },
+    /// The game runs scripts, which replays can't record.
+    ScriptsNotRecorded,
+}
+
+/// A recording of all confirmed input for a game, which can be replayed without a network.
+/// Scripts aren't recorded, so games that run scripts can't be recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    tick_rate: TickRate,
+    /// The session as of the initial state, which is confirmed frame by frame as the replay is played.
+    session: Session,
+    initial_state: State,
+    /// The input for each frame, for every active player ordered by id.
+    inputs: Vec<PlayerInput>,
+    /// The checksum of the state after each frame was ticked.
+    checksums: Vec<Option<u32>>,
+}
+impl Replay {
+    /// Returns the rate the game was ticked at.
+    pub fn tick_rate(&self) -> TickRate {
+        self.tick_rate
+    }
+
+    /// Returns the session the replay was recorded with.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Returns the state the replay starts from.
+    pub fn initial_state(&self) -> &State {
+        &self.initial_state
+    }
+
+    /// Returns the number of frames recorded.
+    pub fn len(&self) -> usize {
+        self.checksums.len()
+    }
+
+    /// Returns whether no frames were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.checksums.is_empty()
+    }
+
+    /// Returns all recorded input.
+    pub fn inputs(&self) -> impl Iterator<Item = (Frame, PlayerId, PlayerInput)> + '_ {
+        let mut session = self.session.clone();
+        let mut frame = self.initial_state.frame();
+        (0..self.len())
+            .flat_map(move |_| {
+                let players: Vec<_> = session
+                    .active_players(frame)
+                    .map(|player| (frame, player))
+                    .collect();
+                session.confirm(frame);
+                frame = frame.increment();
+                players
+            })
+            .zip(self.inputs.iter())
+            .map(|((frame, player), input)| (frame, player, *input))
+    }
+
+    /// Replays all frames, returning the final state.
+    pub fn play(&self) -> State {
+        match self.run(|_, _, _| Ok(())) {
+            Ok(state) => state,
+            Err(_) => unreachable!("playing without verification can't fail"),
+        }
+    }
+
+    /// Replays all frames, checking the state against the recorded checksums at every frame.
+    pub fn verify(&self) -> Result<State, ReplayError> {
+        self.run(|frame, state, expected| match expected {
+            Some(expected) if expected != state.checksum() => Err(ReplayError::ChecksumMismatch {
+                frame,
+                expected,
+                actual: state.checksum(),
+            }),
+            _ => Ok(()),
+        })
+    }
+
+    fn run<F>(&self, mut on_frame: F) -> Result<State, ReplayError>
+    where
+        F: FnMut(Frame, &State, Option<u32>) -> Result<(), ReplayError>,
+    {
+        let mut controls = RollbackControls::new();
+        for player in self.session.players() {
+            if let Some((joined, left)) = self.session.frames(player) {
+                // Players who join during the replay have no input before then.
+                let start = if self.session.is_joining(player, joined) {
+                    joined
+                } else {
+                    self.initial_state.frame()
+                };
+                controls.add_player(player, start);
+                if let Some(left) = left {
+                    controls.remove_player(player, left);
+                }
+            }
+        }
+
+        let mut inputs = self.inputs.iter();
+        let schedule = systems::schedule();
+        let mut session = self.session.clone();
+        let mut state = self.initial_state;
+        for checksum in self.checksums.iter() {
+            let frame = state.frame();
+            for player in session.active_players(frame) {
+                let input = *inputs.next().ok_or(ReplayError::Truncated)?;
+                controls.add_local_input(player, frame, input);
+            }
+
+            tick(
+                &mut state,
+                &session,
+                &controls,
+                &schedule,
+                &mut GameScripts::default(),
+                &mut EventQueue::new(),
+            );
+            session.confirm(frame);
+            on_frame(frame, &state, *checksum)?;
+        }
+
+        Ok(state)
+    }
+
+    /// Serializes the replay.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write(MAGIC);
+        bytes.write_u8(VERSION);
+        bytes.write_f32(self.tick_rate.to_f32_seconds());
+
+        self.session.write_bytes(&mut bytes);
+        self.initial_state.write_bytes(&mut bytes);
+
+        bytes.write_u32(self.len() as u32);
+        let mut inputs = self.inputs.iter();
+        let mut session = self.session.clone();
+        let mut frame = self.initial_state.frame();
+        for checksum in self.checksums.iter() {
+            for (_, input) in session.active_players(frame).zip(&mut inputs) {
+                input.write_bytes(&mut bytes);
+            }
+            session.confirm(frame);
+            frame = frame.increment();
+
+            match checksum {
+                Some(checksum) => {
+                    bytes.write_u8(1);
+                    bytes.write_u32(*checksum);
+                }
+                None => bytes.write_u8(0),
+            }
+        }
+
+        bytes
+    }
+
+    /// Deserializes a replay.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.read(MAGIC.len()) != Some(MAGIC.as_slice()) {
+            return Err(ReplayError::InvalidHeader);
+        }
+
+        let version = reader.read_u8().ok_or(ReplayError::Truncated)?;
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let tick_rate = reader.read_f32().ok_or(ReplayError::Truncated)?.into();
+
+        let session = Session::read_bytes(&mut reader).ok_or(ReplayError::Truncated)?;
+        let initial_state = State::read_bytes(&mut reader).ok_or(ReplayError::Truncated)?;
+
+        let frames = reader.read_u32().ok_or(ReplayError::Truncated)?;
+        let mut inputs = Vec::new();
+        let mut checksums = Vec::new();
+        let mut active = session.clone();
+        let mut frame = initial_state.frame();
+        for _ in 0..frames {
+            for _ in active.active_players(frame) {
+                inputs.push(PlayerInput::read_bytes(&mut reader).ok_or(ReplayError::Truncated)?);
+            }
+            active.confirm(frame);
+            frame = frame.increment();
+
+            let checksum = match reader.read_u8().ok_or(ReplayError::Truncated)? {
+                0 => None,
+                _ => Some(reader.read_u32().ok_or(ReplayError::Truncated)?),
+            };
+            checksums.push(checksum);
+        }
+
+        if !reader.is_empty() {
+            return Err(ReplayError::TrailingBytes);
+        }
+
+        Ok(Self {
+            tick_rate,
+            session,
+            initial_state,
+            inputs,
+            checksums,
+        })
+    }
+
+    /// Writes the replay to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ReplayError> {
+        fs::write(path, self.to_bytes()).map_err(|e| ReplayError::Io(e.kind()))
+    }
+
+    /// Reads a replay from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ReplayError> {
+        let bytes = fs::read(path).map_err(|e| ReplayError::Io(e.kind()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Records the confirmed input of a game into a replay.
+pub struct ReplayRecorder {
+    replay: Replay,
+    /// The session as of `next_frame`.
+    session: Session,
+    next_frame: Frame,
+}
+impl ReplayRecorder {
+    /// Starts recording from the game's current confirmed state.
+    pub fn new<T>(game: &Game<T>) -> Result<Self, ReplayError> {
+        if game.scripts.is_set() {
+            return Err(ReplayError::ScriptsNotRecorded);
+        }
+
+        Ok(Self {
+            replay: Replay {
+                tick_rate: game.tick_rate,
+                session: game.session.clone(),
+                initial_state: game.confirmed_state,
+                inputs: Vec::new(),
+                checksums: Vec::new(),
+            },
+            session: game.session.clone(),
+            next_frame: game.confirmed_state.frame(),
+        })
+    }
+
+    /// Records all frames confirmed since the last call.
+    /// Should be called after every update so that no input falls out of the game's history.
+    pub fn record<T>(&mut self, game: &Game<T>) -> Result<(), ReplayError> {
+        if game.scripts.is_set() {
+            return Err(ReplayError::ScriptsNotRecorded);
+        }
+
+        // Joins and leaves can't happen on confirmed frames, so the session is final for them.
+        // The game has already confirmed up to its own frame, so only the new joins and leaves are taken.
+        self.replay.session.sync(&game.session);
+        self.session.sync(&game.session);
+
+        let confirmed_frame = game.confirmed_state.frame();
+        while confirmed_frame.is_after(self.next_frame) {
+            for player in self.session.active_players(self.next_frame) {
+                let input = game.controls.get_player_input(player, self.next_frame);
+                self.replay.inputs.push(input);
+            }
+
+            self.session.confirm(self.next_frame);
+            self.next_frame = self.next_frame.increment();
+            self.replay
+                .checksums
+                .push(game.checksums.get(self.next_frame));
+        }
+
+        Ok(())
+    }
+
+    /// Stops recording, returning the replay.
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{LoopbackConfig, LoopbackTransport, PlayerSettings},
+        player_input::Button,
+    };
+
+    const DELTA_T: f32 = 1.0 / 60.0;
+
+    /// Creates games for two peers over a lossy network.
+    fn games(session: Session) -> (Game<LoopbackTransport>, Game<LoopbackTransport>) {
+        let (a, b) = LoopbackTransport::pair(LoopbackConfig {
+            latency: 0.05.into(),
+            jitter: 0.02.into(),
+            packet_loss: 0.1,
+            seed: 7,
+        });
+        (
+            Game::new(DELTA_T.into(), 0.into(), session.clone(), a),
+            Game::new(DELTA_T.into(), 1.into(), session, b),
+        )
+    }
+
+    /// Records a game between two peers, returning the replay and the recorded peer's final confirmed state.
+    fn record() -> (Replay, State) {
+        let (mut a, mut b) = games(Session::from_players(&[0.into(), 1.into()]));
+        for game in [&mut a, &mut b] {
+            game.leave(1.into(), 150.into()).unwrap();
+        }
+
+        record_games(a, b)
+    }
+
+    /// Records 200 updates of the games, returning the replay and the first game's final confirmed state.
+    fn record_games(
+        mut a: Game<LoopbackTransport>,
+        mut b: Game<LoopbackTransport>,
+    ) -> (Replay, State) {
+        let mut recorder = ReplayRecorder::new(&a).unwrap();
+        for i in 0..200u32 {
+            a.set_local_input((i as u64 % 16).into());
+            b.set_local_input((i as u64 / 5 % 16).into());
+            a.update(DELTA_T);
+            b.update(DELTA_T);
+            recorder.record(&a).unwrap();
+        }
+
+        (recorder.finish(), *a.confirmed_state())
+    }
+
+    #[test]
+    fn records_every_confirmed_frame() {
+        let (replay, state) = record();
+        assert_eq!(state.frame().inner() as usize, replay.len());
+        assert_eq!(150 * 2 + (replay.len() - 150), replay.inputs().count());
+        assert!(replay.checksums.iter().all(|c| c.is_some()));
+    }
+
+    #[test]
+    fn inputs_are_tagged_with_frame_and_player() {
+        let (replay, _) = record();
+        let inputs: Vec<_> = replay.inputs().take(4).collect();
+        assert_eq!(
+            vec![
+                (0.into(), 0.into(), replay.inputs[0]),
+                (0.into(), 1.into(), replay.inputs[1]),
+                (1.into(), 0.into(), replay.inputs[2]),
+                (1.into(), 1.into(), replay.inputs[3]),
+            ],
+            inputs
+        );
+    }
+
+    #[test]
+    fn verify_reproduces_identical_state() {
+        let (replay, state) = record();
+        assert_eq!(Ok(state), replay.verify());
+        assert_eq!(state, replay.play());
+    }
+
+    #[test]
+    fn verify_replays_players_joining_later() {
+        let (mut a, mut b) = games(Session::from_players(&[0.into()]));
+        for game in [&mut a, &mut b] {
+            game.join(1.into(), 100.into(), PlayerSettings::default())
+                .unwrap();
+        }
+
+        let (replay, state) = record_games(a, b);
+        assert_eq!(100 + (replay.len() - 100) * 2, replay.inputs().count());
+        assert_eq!(Ok(state), replay.verify());
+    }
+
+    #[test]
+    fn verify_detects_changed_input() {
+        let (mut replay, _) = record();
+        let held = replay.inputs[10].is_held(Button::A);
+        replay.inputs[10].set_button(Button::A, !held);
+
+        let result = replay.verify();
+        match result {
+            Err(ReplayError::ChecksumMismatch { frame, .. }) => assert_eq!(Frame::from(5), frame),
+            _ => panic!("expected a checksum mismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let (replay, _) = record();
+        assert_eq!(Ok(replay.clone()), Replay::from_bytes(&replay.to_bytes()));
+    }
+
+    #[test]
+    fn from_bytes_rejects_invalid_data() {
+        let (replay, _) = record();
+        let bytes = replay.to_bytes();
+
+        assert_eq!(
+            Err(ReplayError::InvalidHeader),
+            Replay::from_bytes(&bytes[1..])
+        );
+        assert_eq!(
+            Err(ReplayError::Truncated),
+            Replay::from_bytes(&bytes[..bytes.len() - 1])
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Err(ReplayError::TrailingBytes),
+            Replay::from_bytes(&trailing)
+        );
+
+        let mut version = bytes.clone();
+        version[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            Err(ReplayError::UnsupportedVersion(VERSION + 1)),
+            Replay::from_bytes(&version)
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (replay, _) = record();
+        let path = std::env::temp_dir().join("core_game_replay_save_and_load.bin");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(Ok(replay), loaded);
+    }
+}
@@ -1,20 +1,407 @@
-use super::{frame::Frame, player_id::PlayerId};
+use super::{
+    frame::Frame,
+    player_id::{PlayerId, MAX_PLAYERS},
+};
 use crate::player_input::PlayerInput;
 
-pub struct RollbackControls;
+/// The number of frames of input kept for each player.
+/// Must divide `u16::MAX + 1` so slots stay stable when frames wrap.
+pub const INPUT_HISTORY_LEN: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputResult {
+    /// The input was stored.
+    Accepted,
+    /// The input for that frame was already confirmed.
+    Duplicate,
+    /// The frame is too far ahead of the last confirmed frame to be stored.
+    TooFarAhead,
+    /// The player is not being tracked.
+    UnknownPlayer,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollbackControls {
+    players: [Option<InputHistory>; MAX_PLAYERS],
+    first_incorrect_frame: Option<Frame>,
+}
 impl RollbackControls {
-    pub fn poll_remote(&mut self) {
-        todo!("poll for remote input")
+    /// Creates a new instance of the rollback controls.
+    pub fn new() -> Self {
+        Self {
+            players: [None; MAX_PLAYERS],
+            first_incorrect_frame: None,
+        }
+    }
+
+    /// Starts tracking input for the given player, beginning at the given frame.
+    pub fn add_player(&mut self, id: PlayerId, start_frame: Frame) {
+        self.players[id.index()] = Some(InputHistory::new(start_frame));
     }
+
+    /// Stops waiting on input for the given player, beginning at the given frame.
+    pub fn remove_player(&mut self, id: PlayerId, frame: Frame) {
+        if let Some(history) = &mut self.players[id.index()] {
+            history.left_frame = Some(frame);
+        }
+    }
+
+    /// Adds input from a local player. Local input is never predicted.
+    pub fn add_local_input(
+        &mut self,
+        id: PlayerId,
+        frame: Frame,
+        input: PlayerInput,
+    ) -> InputResult {
+        match &mut self.players[id.index()] {
+            Some(history) => {
+                let result = history.add(frame, input);
+                history.confirm();
+                result
+            }
+            None => InputResult::UnknownPlayer,
+        }
+    }
+
+    /// Adds input from a remote player, tracking any frames that were mispredicted.
+    pub fn add_remote_input(
+        &mut self,
+        id: PlayerId,
+        frame: Frame,
+        input: PlayerInput,
+    ) -> InputResult {
+        let (result, incorrect_frame) = match &mut self.players[id.index()] {
+            Some(history) => {
+                let result = history.add(frame, input);
+                (result, history.confirm())
+            }
+            None => (InputResult::UnknownPlayer, None),
+        };
+
+        if let Some(frame) = incorrect_frame {
+            self.first_incorrect_frame = Some(earliest(self.first_incorrect_frame, frame));
+        }
+
+        result
+    }
+
+    /// Returns whether the input for the given frame is confirmed for all players.
     pub fn is_confirmed(&self, frame: Frame) -> bool {
-        todo!("return whether input is confirmed")
+        self.players
+            .iter()
+            .filter_map(|p| p.as_ref())
+            .all(|p| p.is_confirmed(frame))
     }
-    pub fn last_confirmed_frame(&self) -> Frame {
-        todo!()
+
+    /// Returns the last frame that has confirmed input for all players.
+    /// Players that have left and have no more input to confirm are ignored.
+    pub fn last_confirmed_frame(&self) -> Option<Frame> {
+        self.players
+            .iter()
+            .filter_map(|p| p.as_ref())
+            .filter(|p| !p.is_finished())
+            .fold(None, |last, p| Some(earliest(last, p.last_confirmed_frame)))
+    }
+
+    /// Returns the earliest frame where a predicted input turned out to be wrong.
+    pub fn first_incorrect_frame(&self) -> Option<Frame> {
+        self.first_incorrect_frame
     }
+
+    /// Marks the frame as needing to be resimulated, such as when a player joins or leaves.
+    pub fn invalidate(&mut self, frame: Frame) {
+        self.first_incorrect_frame = Some(earliest(self.first_incorrect_frame, frame));
+    }
+
+    /// Clears the incorrect frame after the mispredicted frames have been resimulated.
+    pub fn clear_incorrect_frame(&mut self) {
+        self.first_incorrect_frame = None;
+    }
+
+    /// Returns the input for the player at the given frame.
+    /// If no input has been confirmed for that frame, the last confirmed input is repeated.
     pub fn get_player_input(&self, id: PlayerId, frame: Frame) -> PlayerInput {
-        // get last input if no new input exists.
-        // If new inputs,
-        todo!()
+        match &self.players[id.index()] {
+            Some(history) => history.input(frame),
+            None => PlayerInput::new(),
+        }
+    }
+}
+
+/// Returns the earliest of the two frames.
+fn earliest(a: Option<Frame>, b: Frame) -> Frame {
+    match a {
+        Some(a) if b.is_after(a) => a,
+        _ => b,
+    }
+}
+
+/// Ring buffer of the input for a single player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct InputHistory {
+    inputs: [Option<(Frame, PlayerInput)>; INPUT_HISTORY_LEN],
+    last_confirmed_frame: Frame,
+    left_frame: Option<Frame>,
+}
+impl InputHistory {
+    fn new(start_frame: Frame) -> Self {
+        Self {
+            inputs: [None; INPUT_HISTORY_LEN],
+            last_confirmed_frame: start_frame.decrement(),
+            left_frame: None,
+        }
+    }
+
+    fn slot(frame: Frame) -> usize {
+        frame.inner() as usize % INPUT_HISTORY_LEN
+    }
+
+    /// Returns the input received for the given frame, if any.
+    fn received(&self, frame: Frame) -> Option<PlayerInput> {
+        match self.inputs[Self::slot(frame)] {
+            Some((f, input)) if f == frame => Some(input),
+            _ => None,
+        }
+    }
+
+    fn is_confirmed(&self, frame: Frame) -> bool {
+        // Frames after leaving have no input, but are only confirmed once all earlier input is.
+        let frame = match self.left_frame {
+            Some(left) if !left.is_after(frame) => left.decrement(),
+            _ => frame,
+        };
+
+        !frame.is_after(self.last_confirmed_frame)
+    }
+
+    /// Returns whether the player has left and all their input has been confirmed.
+    fn is_finished(&self) -> bool {
+        match self.left_frame {
+            Some(left) => self.is_confirmed(left),
+            None => false,
+        }
+    }
+
+    /// Predicts input by repeating the last confirmed input.
+    fn predicted(&self) -> PlayerInput {
+        self.received(self.last_confirmed_frame)
+            .unwrap_or_else(PlayerInput::new)
+    }
+
+    fn input(&self, frame: Frame) -> PlayerInput {
+        match self.received(frame) {
+            Some(input) if self.is_confirmed(frame) => input,
+            _ => self.predicted(),
+        }
+    }
+
+    fn add(&mut self, frame: Frame, input: PlayerInput) -> InputResult {
+        if self.is_confirmed(frame) {
+            return InputResult::Duplicate;
+        }
+
+        // The slot for the last confirmed frame holds the prediction, so it can't be overwritten.
+        let distance = frame.frames_since(self.last_confirmed_frame) as usize;
+        if distance >= INPUT_HISTORY_LEN {
+            return InputResult::TooFarAhead;
+        }
+
+        self.inputs[Self::slot(frame)] = Some((frame, input));
+        InputResult::Accepted
+    }
+
+    /// Confirms all contiguous frames that were received.
+    /// Returns the first confirmed frame that differs from the prediction.
+    fn confirm(&mut self) -> Option<Frame> {
+        let mut first_incorrect_frame = None;
+        loop {
+            let next = self.last_confirmed_frame.increment();
+            let input = match self.received(next) {
+                Some(input) => input,
+                None => break,
+            };
+
+            // Until the input changes, the prediction is the same as the previous input.
+            if first_incorrect_frame.is_none() && input != self.predicted() {
+                first_incorrect_frame = Some(next);
+            }
+
+            self.last_confirmed_frame = next;
+        }
+
+        first_incorrect_frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(x: f32) -> PlayerInput {
+        let mut i = PlayerInput::new();
+        i.set_primary_x_axis(x.into());
+        i
+    }
+
+    fn controls() -> RollbackControls {
+        let mut c = RollbackControls::new();
+        c.add_player(0.into(), 0.into());
+        c.add_player(1.into(), 0.into());
+        c
+    }
+
+    #[test]
+    fn new_has_no_confirmed_frame() {
+        let c = RollbackControls::new();
+        assert_eq!(None, c.last_confirmed_frame());
+    }
+
+    #[test]
+    fn get_player_input_returns_confirmed_input() {
+        let mut c = controls();
+        assert_eq!(
+            InputResult::Accepted,
+            c.add_local_input(0.into(), 0.into(), input(1.0))
+        );
+        assert_eq!(input(1.0), c.get_player_input(0.into(), 0.into()));
+    }
+
+    #[test]
+    fn get_player_input_predicts_last_input() {
+        let mut c = controls();
+        c.add_remote_input(1.into(), 0.into(), input(1.0));
+        c.add_remote_input(1.into(), 1.into(), input(-1.0));
+        assert_eq!(input(-1.0), c.get_player_input(1.into(), 5.into()));
+    }
+
+    #[test]
+    fn get_player_input_without_history_returns_default() {
+        let c = controls();
+        assert_eq!(PlayerInput::new(), c.get_player_input(1.into(), 3.into()));
+    }
+
+    #[test]
+    fn unknown_player_is_rejected() {
+        let mut c = controls();
+        assert_eq!(
+            InputResult::UnknownPlayer,
+            c.add_remote_input(5.into(), 0.into(), input(1.0))
+        );
+    }
+
+    #[test]
+    fn duplicate_input_is_rejected() {
+        let mut c = controls();
+        c.add_remote_input(1.into(), 0.into(), input(1.0));
+        assert_eq!(
+            InputResult::Duplicate,
+            c.add_remote_input(1.into(), 0.into(), input(-1.0))
+        );
+        assert_eq!(input(1.0), c.get_player_input(1.into(), 0.into()));
+    }
+
+    #[test]
+    fn input_too_far_ahead_is_rejected() {
+        let mut c = controls();
+        let frame = (INPUT_HISTORY_LEN as u16 - 1).into();
+        assert_eq!(
+            InputResult::TooFarAhead,
+            c.add_remote_input(1.into(), frame, input(1.0))
+        );
+    }
+
+    #[test]
+    fn last_confirmed_frame_is_minimum_of_players() {
+        let mut c = controls();
+        for frame in 0..5u16 {
+            c.add_local_input(0.into(), frame.into(), input(0.0));
+        }
+        c.add_remote_input(1.into(), 0.into(), input(0.0));
+        c.add_remote_input(1.into(), 1.into(), input(0.0));
+
+        assert_eq!(Some(1.into()), c.last_confirmed_frame());
+        assert!(c.is_confirmed(1.into()));
+        assert!(!c.is_confirmed(2.into()));
+    }
+
+    #[test]
+    fn out_of_order_input_confirms_once_gap_filled() {
+        let mut c = controls();
+        c.add_local_input(0.into(), 0.into(), input(0.0));
+        c.add_local_input(0.into(), 1.into(), input(0.0));
+        c.add_remote_input(1.into(), 1.into(), input(1.0));
+        assert!(!c.is_confirmed(1.into()));
+
+        c.add_remote_input(1.into(), 0.into(), input(0.0));
+        assert!(c.is_confirmed(1.into()));
+        assert_eq!(input(1.0), c.get_player_input(1.into(), 1.into()));
+    }
+
+    #[test]
+    fn first_incorrect_frame_is_reported() {
+        let mut c = controls();
+        c.add_remote_input(1.into(), 0.into(), input(0.0));
+        c.add_remote_input(1.into(), 1.into(), input(0.0));
+        c.add_remote_input(1.into(), 2.into(), input(1.0));
+        c.add_remote_input(1.into(), 3.into(), input(-1.0));
+        assert_eq!(Some(2.into()), c.first_incorrect_frame());
+
+        c.clear_incorrect_frame();
+        assert_eq!(None, c.first_incorrect_frame());
+    }
+
+    #[test]
+    fn correct_predictions_are_not_reported() {
+        let mut c = controls();
+        c.add_remote_input(1.into(), 0.into(), input(1.0));
+        c.clear_incorrect_frame();
+        c.add_remote_input(1.into(), 1.into(), input(1.0));
+        c.add_remote_input(1.into(), 2.into(), input(1.0));
+        assert_eq!(None, c.first_incorrect_frame());
+    }
+
+    #[test]
+    fn local_input_is_never_incorrect() {
+        let mut c = controls();
+        c.add_local_input(0.into(), 0.into(), input(1.0));
+        c.add_local_input(0.into(), 1.into(), input(-1.0));
+        assert_eq!(None, c.first_incorrect_frame());
+    }
+
+    #[test]
+    fn history_survives_frame_wrap() {
+        let mut c = RollbackControls::new();
+        c.add_player(0.into(), u16::MAX.into());
+        c.add_remote_input(0.into(), u16::MAX.into(), input(1.0));
+        c.add_remote_input(0.into(), 0.into(), input(-1.0));
+        assert_eq!(Some(0.into()), c.last_confirmed_frame());
+        assert_eq!(input(1.0), c.get_player_input(0.into(), u16::MAX.into()));
+        assert_eq!(input(-1.0), c.get_player_input(0.into(), 0.into()));
+    }
+
+    #[test]
+    fn removed_player_does_not_block_confirmation() {
+        let mut c = controls();
+        c.remove_player(1.into(), 2.into());
+        for frame in 0..5u16 {
+            c.add_local_input(0.into(), frame.into(), input(0.0));
+        }
+        c.add_remote_input(1.into(), 0.into(), input(0.0));
+        assert!(!c.is_confirmed(1.into()));
+        assert!(!c.is_confirmed(3.into()));
+        assert_eq!(Some(0.into()), c.last_confirmed_frame());
+
+        c.add_remote_input(1.into(), 1.into(), input(0.0));
+        assert!(c.is_confirmed(4.into()));
+        assert_eq!(Some(4.into()), c.last_confirmed_frame());
+    }
+
+    #[test]
+    fn invalidate_keeps_earliest_frame() {
+        let mut c = controls();
+        c.invalidate(5.into());
+        c.invalidate(3.into());
+        c.invalidate(4.into());
+        assert_eq!(Some(3.into()), c.first_incorrect_frame());
     }
 }
@@ -0,0 +1,51 @@
+use super::State;
+#[cfg(any(test, feature = "std"))]
+use crate::script::{ScriptEngine, ScriptError, Scripts};
+use crate::{ecs::Schedule, events::EventQueue};
+#[cfg(any(test, feature = "std"))]
+use std::boxed::Box;
+
+/// The scripts a game runs after its systems every tick.
+/// Scripts need `std`, so without it the state is only ticked.
+#[derive(Default)]
+pub struct GameScripts {
+    #[cfg(any(test, feature = "std"))]
+    scripts: Option<Scripts<Box<dyn ScriptEngine>>>,
+    /// The first error scripts raised since it was last taken.
+    #[cfg(any(test, feature = "std"))]
+    error: Option<ScriptError>,
+}
+impl GameScripts {
+    #[cfg(any(test, feature = "std"))]
+    pub fn new(scripts: Scripts<Box<dyn ScriptEngine>>) -> Self {
+        Self {
+            scripts: Some(scripts),
+            error: None,
+        }
+    }
+
+    /// Returns whether the game runs any scripts.
+    #[cfg(any(test, feature = "std"))]
+    pub fn is_set(&self) -> bool {
+        self.scripts.is_some()
+    }
+
+    /// Returns the first error scripts raised since the last call.
+    #[cfg(any(test, feature = "std"))]
+    pub fn take_error(&mut self) -> Option<ScriptError> {
+        self.error.take()
+    }
+
+    /// Ticks the state, running the scripts after the systems.
+    pub fn tick(&mut self, state: &mut State, schedule: &Schedule, events: &mut EventQueue) {
+        #[cfg(any(test, feature = "std"))]
+        if let Some(scripts) = &mut self.scripts {
+            if let Err(error) = state.tick_with_scripts(schedule, events, scripts) {
+                self.error.get_or_insert(error);
+            }
+            return;
+        }
+
+        state.tick(schedule, events);
+    }
+}
@@ -0,0 +1,405 @@
+use super::{
+    frame::Frame,
+    player_id::{PlayerId, MAX_PLAYERS},
+};
+use crate::bytes::{ByteReader, ByteWriter};
+
+/// The maximum number of frames a player's input can be delayed by.
+pub const MAX_INPUT_DELAY: u8 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlotKind {
+    /// Sends input that is applied to the state.
+    Player,
+    /// Receives input from players but never sends any.
+    Spectator,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerSettings {
+    pub kind: SlotKind,
+    /// The number of frames local input is delayed by before it is applied.
+    /// Trades responsiveness for fewer rollbacks.
+    pub input_delay: u8,
+}
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            kind: SlotKind::Player,
+            input_delay: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionError {
+    /// The slot is or was already used by someone else.
+    SlotTaken(PlayerId),
+    UnknownPlayer(PlayerId),
+    AlreadyLeft(PlayerId),
+    /// The frame has already been confirmed so it can't be changed.
+    FrameConfirmed(Frame),
+    /// The input delay is above `MAX_INPUT_DELAY`.
+    InvalidInputDelay(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Slot {
+    settings: PlayerSettings,
+    joined: Frame,
+    left: Option<Frame>,
+    /// Set once the join frame is confirmed.
+    /// Frames wrap, so they can only be compared against frames close to them.
+    join_confirmed: bool,
+    /// Set once the leave frame is confirmed.
+    leave_confirmed: bool,
+}
+impl Slot {
+    fn new(settings: PlayerSettings, joined: Frame) -> Self {
+        Self {
+            settings,
+            joined,
+            left: None,
+            join_confirmed: false,
+            leave_confirmed: false,
+        }
+    }
+
+    fn is_active(&self, frame: Frame) -> bool {
+        let has_joined = self.join_confirmed || !self.joined.is_after(frame);
+        let has_left = self.leave_confirmed
+            || match self.left {
+                Some(left) => !left.is_after(frame),
+                None => false,
+            };
+
+        has_joined && !has_left
+    }
+}
+
+/// Everyone taking part in a game, along with the frames they joined and left on.
+/// Joins and leaves are keyed by frame so that resimulating applies them identically.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    slots: [Option<Slot>; MAX_PLAYERS],
+}
+impl Session {
+    /// Creates a new empty session.
+    pub fn new() -> Self {
+        Self {
+            slots: [None; MAX_PLAYERS],
+        }
+    }
+
+    /// Creates a session where all players join on the first frame with default settings.
+    pub fn from_players(players: &[PlayerId]) -> Self {
+        let mut session = Self::new();
+        for player in players {
+            session.slots[player.index()] = Some(Slot::new(PlayerSettings::default(), 0.into()));
+        }
+
+        session
+    }
+
+    /// Adds someone to the session, starting at the given frame.
+    pub fn join(
+        &mut self,
+        id: PlayerId,
+        frame: Frame,
+        settings: PlayerSettings,
+    ) -> Result<(), SessionError> {
+        if settings.input_delay > MAX_INPUT_DELAY {
+            return Err(SessionError::InvalidInputDelay(settings.input_delay));
+        }
+
+        let slot = &mut self.slots[id.index()];
+        if slot.is_some() {
+            return Err(SessionError::SlotTaken(id));
+        }
+
+        *slot = Some(Slot::new(settings, frame));
+        Ok(())
+    }
+
+    /// Removes someone from the session, starting at the given frame.
+    pub fn leave(&mut self, id: PlayerId, frame: Frame) -> Result<(), SessionError> {
+        match &mut self.slots[id.index()] {
+            Some(Slot { left: Some(_), .. }) => Err(SessionError::AlreadyLeft(id)),
+            Some(slot) => {
+                slot.left = Some(frame);
+                Ok(())
+            }
+            None => Err(SessionError::UnknownPlayer(id)),
+        }
+    }
+
+    /// Marks the frame as confirmed, making any joins and leaves on it permanent.
+    /// Must be called for every frame in order, so that frames far in the past aren't compared after wrapping.
+    pub fn confirm(&mut self, frame: Frame) {
+        for slot in self.slots.iter_mut().flatten() {
+            if slot.joined == frame {
+                slot.join_confirmed = true;
+            }
+            if slot.left == Some(frame) {
+                slot.leave_confirmed = true;
+            }
+        }
+    }
+
+    /// Copies any joins and leaves added to `other`, keeping which of them this session has confirmed.
+    pub fn sync(&mut self, other: &Session) {
+        for (slot, other) in self.slots.iter_mut().zip(&other.slots) {
+            match (slot, other) {
+                (Some(slot), Some(other)) => slot.left = other.left,
+                (slot @ None, Some(other)) => {
+                    *slot = Some(Slot {
+                        left: other.left,
+                        ..Slot::new(other.settings, other.joined)
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns whether the given slot joins on the frame, and the join hasn't been confirmed yet.
+    pub fn is_joining(&self, id: PlayerId, frame: Frame) -> bool {
+        matches!(self.slots[id.index()], Some(s) if !s.join_confirmed && s.joined == frame)
+    }
+
+    /// Returns whether the given slot leaves on the frame, and the leave hasn't been confirmed yet.
+    pub fn is_leaving(&self, id: PlayerId, frame: Frame) -> bool {
+        matches!(self.slots[id.index()], Some(s) if !s.leave_confirmed && s.left == Some(frame))
+    }
+
+    /// Returns the settings for the given slot.
+    pub fn settings(&self, id: PlayerId) -> Option<PlayerSettings> {
+        self.slots[id.index()].map(|s| s.settings)
+    }
+
+    /// Returns the frame the given slot joined and left on.
+    pub fn frames(&self, id: PlayerId) -> Option<(Frame, Option<Frame>)> {
+        self.slots[id.index()].map(|s| (s.joined, s.left))
+    }
+
+    /// Returns whether the given id is a player.
+    pub fn is_player(&self, id: PlayerId) -> bool {
+        matches!(self.settings(id), Some(s) if s.kind == SlotKind::Player)
+    }
+
+    /// Returns every player that has taken part, including ones that left.
+    pub fn players(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| matches!(s, Some(s) if s.settings.kind == SlotKind::Player))
+            .map(|(i, _)| (i as u8).into())
+    }
+
+    /// Returns the players whose input is applied on the given frame.
+    pub fn active_players(&self, frame: Frame) -> impl Iterator<Item = PlayerId> + '_ {
+        self.players().filter(move |p| match self.slots[p.index()] {
+            Some(slot) => slot.is_active(frame),
+            None => false,
+        })
+    }
+
+    /// Writes the session as bytes that are stable across platforms.
+    pub fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        for slot in &self.slots {
+            let slot = match slot {
+                Some(slot) => slot,
+                None => {
+                    writer.write_u8(0);
+                    continue;
+                }
+            };
+
+            writer.write_u8(1);
+            writer.write_u8(match slot.settings.kind {
+                SlotKind::Player => 0,
+                SlotKind::Spectator => 1,
+            });
+            writer.write_u8(slot.settings.input_delay);
+            writer.write_u8(slot.join_confirmed as u8 | (slot.leave_confirmed as u8) << 1);
+            writer.write_u16(slot.joined.inner());
+            match slot.left {
+                Some(left) => {
+                    writer.write_u8(1);
+                    writer.write_u16(left.inner());
+                }
+                None => writer.write_u8(0),
+            }
+        }
+    }
+
+    /// Reads a session that was written with `write_bytes`.
+    pub fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let mut session = Self::new();
+        for slot in session.slots.iter_mut() {
+            if reader.read_u8()? == 0 {
+                continue;
+            }
+
+            let kind = match reader.read_u8()? {
+                0 => SlotKind::Player,
+                1 => SlotKind::Spectator,
+                _ => return None,
+            };
+            let input_delay = reader.read_u8()?;
+            let confirmed = reader.read_u8()?;
+            let joined = reader.read_u16()?.into();
+            let left = match reader.read_u8()? {
+                0 => None,
+                _ => Some(reader.read_u16()?.into()),
+            };
+
+            *slot = Some(Slot {
+                settings: PlayerSettings { kind, input_delay },
+                joined,
+                left,
+                join_confirmed: confirmed & 1 != 0,
+                leave_confirmed: confirmed & 2 != 0,
+            });
+        }
+
+        Some(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectator() -> PlayerSettings {
+        PlayerSettings {
+            kind: SlotKind::Spectator,
+            input_delay: 0,
+        }
+    }
+
+    #[test]
+    fn from_players_joins_on_first_frame() {
+        let s = Session::from_players(&[0.into(), 2.into()]);
+        let active: Vec<PlayerId> = s.active_players(0.into()).collect();
+        assert_eq!(vec![PlayerId::from(0), 2.into()], active);
+    }
+
+    #[test]
+    fn join_is_active_from_frame() {
+        let mut s = Session::new();
+        s.join(1.into(), 10.into(), PlayerSettings::default())
+            .unwrap();
+        assert_eq!(0, s.active_players(9.into()).count());
+        assert_eq!(1, s.active_players(10.into()).count());
+    }
+
+    #[test]
+    fn leave_is_inactive_from_frame() {
+        let mut s = Session::from_players(&[1.into()]);
+        s.leave(1.into(), 10.into()).unwrap();
+        assert_eq!(1, s.active_players(9.into()).count());
+        assert_eq!(0, s.active_players(10.into()).count());
+        assert_eq!(1, s.players().count());
+    }
+
+    #[test]
+    fn confirmed_join_and_leave_survive_frame_wrap() {
+        let mut s = Session::new();
+        s.join(0.into(), 10.into(), PlayerSettings::default())
+            .unwrap();
+        s.join(1.into(), 10.into(), PlayerSettings::default())
+            .unwrap();
+        s.leave(1.into(), 20.into()).unwrap();
+        assert_eq!(0, s.active_players(40_000.into()).count());
+
+        for frame in 0..=20u16 {
+            s.confirm(frame.into());
+        }
+        let active: Vec<PlayerId> = s.active_players(40_000.into()).collect();
+        assert_eq!(vec![PlayerId::from(0)], active);
+        assert!(!s.is_joining(0.into(), 10.into()));
+        assert!(!s.is_leaving(1.into(), 20.into()));
+    }
+
+    #[test]
+    fn sync_keeps_confirmed_frames() {
+        let mut s = Session::from_players(&[0.into()]);
+        let mut other = s.clone();
+        other.confirm(0.into());
+        other.join(1.into(), 5.into(), spectator()).unwrap();
+        other.leave(0.into(), 6.into()).unwrap();
+        other.confirm(5.into());
+
+        s.sync(&other);
+        assert!(s.is_joining(0.into(), 0.into()));
+        assert!(s.is_joining(1.into(), 5.into()));
+        assert!(s.is_leaving(0.into(), 6.into()));
+        assert_eq!(other.frames(1.into()), s.frames(1.into()));
+    }
+
+    #[test]
+    fn spectators_are_never_players() {
+        let mut s = Session::from_players(&[0.into()]);
+        s.join(1.into(), 0.into(), spectator()).unwrap();
+        assert!(s.is_player(0.into()));
+        assert!(!s.is_player(1.into()));
+        assert_eq!(1, s.active_players(5.into()).count());
+    }
+
+    #[test]
+    fn join_taken_slot_fails() {
+        let mut s = Session::from_players(&[0.into()]);
+        assert_eq!(
+            Err(SessionError::SlotTaken(0.into())),
+            s.join(0.into(), 5.into(), PlayerSettings::default())
+        );
+    }
+
+    #[test]
+    fn join_with_large_delay_fails() {
+        let mut s = Session::new();
+        let settings = PlayerSettings {
+            input_delay: MAX_INPUT_DELAY + 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(SessionError::InvalidInputDelay(MAX_INPUT_DELAY + 1)),
+            s.join(0.into(), 0.into(), settings)
+        );
+    }
+
+    #[test]
+    fn leave_twice_fails() {
+        let mut s = Session::from_players(&[0.into()]);
+        s.leave(0.into(), 3.into()).unwrap();
+        assert_eq!(
+            Err(SessionError::AlreadyLeft(0.into())),
+            s.leave(0.into(), 4.into())
+        );
+        assert_eq!(
+            Err(SessionError::UnknownPlayer(1.into())),
+            s.leave(1.into(), 4.into())
+        );
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut s = Session::from_players(&[0.into(), 3.into()]);
+        s.join(
+            5.into(),
+            7.into(),
+            PlayerSettings {
+                kind: SlotKind::Spectator,
+                input_delay: 2,
+            },
+        )
+        .unwrap();
+        s.leave(3.into(), 40.into()).unwrap();
+        s.confirm(7.into());
+
+        let mut bytes = vec![];
+        s.write_bytes(&mut bytes);
+        assert_eq!(Some(s), Session::read_bytes(&mut ByteReader::new(&bytes)));
+    }
+}
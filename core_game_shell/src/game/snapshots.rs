@@ -0,0 +1,543 @@
+use super::{frame::Frame, state::State};
+use crate::{
+    bytes::{ByteReader, SliceWriter},
+    math::hash::Fnv1a32,
+};
+
+/// Gaps between changed bytes shorter than this are merged into one run,
+/// as each run costs 4 bytes of header.
+const MIN_RUN_GAP: usize = 4;
+const RUN_HEADER_SIZE: usize = 4;
+// Run headers store offsets into the state as u16.
+const _: () = assert!(State::MAX_SIZE <= u16::MAX as usize);
+
+/// The memory used by a single snapshot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnapshotMemory {
+    pub frame: Frame,
+    /// The size of the state when fully serialized.
+    pub state_bytes: usize,
+    /// The bytes used to store the snapshot.
+    /// The newest snapshot is stored in full, older ones as a delta against the next.
+    pub stored_bytes: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    frame: Frame,
+    checksum: u32,
+    /// The length of the serialized state.
+    len: usize,
+    /// Where the delta that turns the previous entry into this one starts in the pool.
+    delta_start: usize,
+    /// The length of that delta. Zero for the oldest entry, as there is nothing before it.
+    delta_len: usize,
+}
+
+/// Keeps the states for the last `FRAMES` frames in a fixed amount of memory.
+///
+/// Only the newest state is stored in full. Every other state is stored as the
+/// XOR of its bytes against the next one, encoded as runs of changed bytes.
+/// Frames usually change little, so the deltas are much smaller than the state.
+/// Restoring walks back from the newest state, undoing one delta per frame.
+///
+/// If the deltas outgrow `POOL_BYTES` the oldest snapshots are dropped early.
+pub struct SnapshotRing<const FRAMES: usize, const POOL_BYTES: usize> {
+    entries: [Option<Entry>; FRAMES],
+    entries_start: usize,
+    entries_len: usize,
+    /// Ring buffer of deltas, in the same order as the entries.
+    pool: [u8; POOL_BYTES],
+    pool_start: usize,
+    pool_len: usize,
+    /// The serialized newest state.
+    latest: [u8; State::MAX_SIZE],
+}
+impl<const FRAMES: usize, const POOL_BYTES: usize> SnapshotRing<FRAMES, POOL_BYTES> {
+    /// Creates a new empty ring.
+    pub fn new() -> Self {
+        Self {
+            entries: [None; FRAMES],
+            entries_start: 0,
+            entries_len: 0,
+            pool: [0; POOL_BYTES],
+            pool_start: 0,
+            pool_len: 0,
+            latest: [0; State::MAX_SIZE],
+        }
+    }
+
+    /// Removes all snapshots.
+    pub fn clear(&mut self) {
+        self.entries = [None; FRAMES];
+        self.entries_start = 0;
+        self.entries_len = 0;
+        self.pool_start = 0;
+        self.pool_len = 0;
+    }
+
+    /// Returns the number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.entries_len
+    }
+
+    /// Returns the oldest and newest frames held.
+    pub fn frames(&self) -> Option<(Frame, Frame)> {
+        let oldest = self.entry(0)?;
+        let newest = self.entry(self.entries_len - 1)?;
+        Some((oldest.frame, newest.frame))
+    }
+
+    /// Stores a snapshot of the state.
+    /// Any snapshots on or after the state's frame are replaced, so the ring always
+    /// holds a contiguous run of frames.
+    pub fn push(&mut self, state: &State) {
+        let frame = state.frame();
+        self.truncate(frame);
+        if let Some((_, newest)) = self.frames() {
+            if newest.increment() != frame {
+                // A gap would break the chain of deltas.
+                self.clear();
+            }
+        }
+
+        let mut bytes = [0; State::MAX_SIZE];
+        let mut writer = SliceWriter::new(&mut bytes);
+        state.write_bytes(&mut writer);
+        let len = writer.len();
+
+        let mut hasher = Fnv1a32::new();
+        hasher.write(&bytes[..len]);
+        let checksum = hasher.finish();
+
+        let mut delta_start = 0;
+        let mut delta_len = 0;
+        if let Some(newest) = self.newest() {
+            let compare_len = len.max(newest.len);
+            let size = encoded_size(&bytes[..compare_len], &self.latest[..compare_len]);
+
+            if self.entries_len == FRAMES {
+                self.pop_oldest();
+            }
+            while self.entries_len > 0 && POOL_BYTES - self.pool_len < size {
+                self.pop_oldest();
+            }
+
+            // Dropping every older snapshot means there is nothing to delta against.
+            if self.entries_len > 0 {
+                delta_start = (self.pool_start + self.pool_len) % POOL_BYTES;
+                self.write_delta(&bytes[..compare_len]);
+                delta_len = size;
+            }
+        }
+
+        self.latest = bytes;
+        let index = (self.entries_start + self.entries_len) % FRAMES;
+        self.entries[index] = Some(Entry {
+            frame,
+            checksum,
+            len,
+            delta_start,
+            delta_len,
+        });
+        self.entries_len += 1;
+    }
+
+    /// Returns the checksum of the state at the frame, if held.
+    pub fn checksum(&self, frame: Frame) -> Option<u32> {
+        let index = self.index_of(frame)?;
+        self.entry(index).map(|e| e.checksum)
+    }
+
+    /// Returns a copy of the state at the frame, if held.
+    pub fn get(&self, frame: Frame) -> Option<State> {
+        let index = self.index_of(frame)?;
+        let mut bytes = self.latest;
+        for i in (index + 1..self.entries_len).rev() {
+            self.undo_delta(i, &mut bytes);
+        }
+
+        let entry = self.entry(index)?;
+        State::read_bytes(&mut ByteReader::new(&bytes[..entry.len]))
+    }
+
+    /// Restores the state at the frame, dropping all newer snapshots.
+    pub fn restore(&mut self, frame: Frame) -> Option<State> {
+        self.index_of(frame)?;
+        self.truncate(frame.increment());
+
+        let entry = self.newest()?;
+        State::read_bytes(&mut ByteReader::new(&self.latest[..entry.len]))
+    }
+
+    /// Returns the memory used by every snapshot, oldest first.
+    pub fn memory(&self) -> impl Iterator<Item = SnapshotMemory> + '_ {
+        (0..self.entries_len).filter_map(move |i| {
+            let entry = self.entry(i)?;
+            // Each delta is stored with the snapshot after it.
+            let stored_bytes = if i + 1 == self.entries_len {
+                entry.len
+            } else {
+                self.entry(i + 1)?.delta_len
+            };
+
+            Some(SnapshotMemory {
+                frame: entry.frame,
+                state_bytes: entry.len,
+                stored_bytes,
+            })
+        })
+    }
+
+    /// Returns the number of pool bytes used by deltas.
+    pub fn pool_used(&self) -> usize {
+        self.pool_len
+    }
+
+    fn entry(&self, i: usize) -> Option<Entry> {
+        if i >= self.entries_len {
+            return None;
+        }
+        self.entries[(self.entries_start + i) % FRAMES]
+    }
+
+    fn newest(&self) -> Option<Entry> {
+        self.entry(self.entries_len.checked_sub(1)?)
+    }
+
+    fn index_of(&self, frame: Frame) -> Option<usize> {
+        let (oldest, _) = self.frames()?;
+        let index = frame.frames_since(oldest) as usize;
+        if index < self.entries_len {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Drops all snapshots on or after the frame, rewinding the newest state to match.
+    fn truncate(&mut self, frame: Frame) {
+        while let Some(newest) = self.newest() {
+            if frame.is_after(newest.frame) {
+                break;
+            }
+
+            if self.entries_len == 1 {
+                self.clear();
+                break;
+            }
+
+            let mut latest = self.latest;
+            self.undo_delta(self.entries_len - 1, &mut latest);
+            self.latest = latest;
+            self.pool_len -= newest.delta_len;
+            self.entries_len -= 1;
+            let index = (self.entries_start + self.entries_len) % FRAMES;
+            self.entries[index] = None;
+        }
+    }
+
+    /// Drops the oldest snapshot, along with the delta the next one no longer needs.
+    fn pop_oldest(&mut self) {
+        self.entries[self.entries_start] = None;
+        self.entries_start = (self.entries_start + 1) % FRAMES;
+        self.entries_len -= 1;
+
+        if let Some(next) = self.entries[self.entries_start].as_mut() {
+            self.pool_start = (self.pool_start + next.delta_len) % POOL_BYTES;
+            self.pool_len -= next.delta_len;
+            next.delta_len = 0;
+        }
+    }
+
+    /// Encodes the delta between `bytes` and the newest state onto the end of the pool.
+    fn write_delta(&mut self, bytes: &[u8]) {
+        let latest = self.latest;
+        for_each_run(bytes, &latest[..bytes.len()], |skip, start, end| {
+            self.pool_write(&(skip as u16).to_le_bytes());
+            self.pool_write(&((end - start) as u16).to_le_bytes());
+            for i in start..end {
+                self.pool_write(&[bytes[i] ^ latest[i]]);
+            }
+        });
+    }
+
+    fn pool_write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.pool[(self.pool_start + self.pool_len) % POOL_BYTES] = *b;
+            self.pool_len += 1;
+        }
+    }
+
+    /// Applies the delta of the entry at `i`, turning its state into the one before it.
+    fn undo_delta(&self, i: usize, bytes: &mut [u8; State::MAX_SIZE]) {
+        let (delta_start, delta_len) = match self.entry(i) {
+            Some(entry) => (entry.delta_start, entry.delta_len),
+            None => return,
+        };
+
+        let read = |n: usize| self.pool[(delta_start + n) % POOL_BYTES];
+        let mut n = 0;
+        let mut position = 0;
+        while n < delta_len {
+            let skip = u16::from_le_bytes([read(n), read(n + 1)]) as usize;
+            let len = u16::from_le_bytes([read(n + 2), read(n + 3)]) as usize;
+            n += RUN_HEADER_SIZE;
+            position += skip;
+            for b in bytes[position..position + len].iter_mut() {
+                *b ^= read(n);
+                n += 1;
+            }
+            position += len;
+        }
+    }
+}
+
+/// Calls `f` with the number of equal bytes skipped, then the start and end of every run of bytes that differ.
+fn for_each_run<F>(a: &[u8], b: &[u8], mut f: F)
+where
+    F: FnMut(usize, usize, usize),
+{
+    let mut position = 0;
+    let mut i = 0;
+    while i < a.len() {
+        // Most bytes are unchanged, so skip over them a word at a time.
+        if i + 8 <= a.len() && a[i..i + 8] == b[i..i + 8] {
+            i += 8;
+            continue;
+        }
+        if a[i] == b[i] {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut end = i + 1;
+        let mut j = end;
+        while j < a.len() && j - end < MIN_RUN_GAP {
+            if a[j] != b[j] {
+                end = j + 1;
+            }
+            j += 1;
+        }
+
+        f(start - position, start, end);
+        position = end;
+        i = end;
+    }
+}
+
+/// Returns the number of bytes the delta between `a` and `b` takes.
+fn encoded_size(a: &[u8], b: &[u8]) -> usize {
+    let mut size = 0;
+    for_each_run(a, b, |_, start, end| size += RUN_HEADER_SIZE + end - start);
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{Event, EventQueue},
+        game::systems,
+        player_input::PlayerInput,
+    };
+
+    type Ring = SnapshotRing<8, 4096>;
+
+    /// Returns the states for `count` frames, with players joining and moving.
+    fn states(start: u16, count: usize) -> Vec<State> {
+        let schedule = systems::schedule();
+        // States only advance a frame at a time, so set the start through the bytes.
+        let mut bytes = vec![];
+        State::new().write_bytes(&mut bytes);
+        bytes[..2].copy_from_slice(&start.to_le_bytes());
+        let mut state = State::read_bytes(&mut ByteReader::new(&bytes)).unwrap();
+
+        let mut states = vec![state];
+        for i in 0..count - 1 {
+            let mut events = EventQueue::new();
+            events.begin(state.frame());
+            if i < 4 {
+                events.emit(Event::PlayerJoined {
+                    player: (i as u8).into(),
+                });
+            }
+
+            let mut input = PlayerInput::new();
+            input.set_primary_x_axis(((i % 3) as f32 - 1.0).into());
+            state.apply_input(0.into(), input);
+            state.tick(&schedule, &mut events);
+            states.push(state);
+        }
+
+        states
+    }
+
+    #[test]
+    fn get_returns_every_held_state() {
+        let states = states(0, 8);
+        let mut ring = Ring::new();
+        for state in &states {
+            ring.push(state);
+        }
+
+        assert_eq!(8, ring.len());
+        assert_eq!(Some((0.into(), 7.into())), ring.frames());
+        for state in &states {
+            assert_eq!(Some(*state), ring.get(state.frame()));
+            assert_eq!(Some(state.checksum()), ring.checksum(state.frame()));
+        }
+        assert_eq!(None, ring.get(8.into()));
+    }
+
+    #[test]
+    fn oldest_frames_are_evicted() {
+        let states = states(0, 20);
+        let mut ring = Ring::new();
+        for state in &states {
+            ring.push(state);
+        }
+
+        assert_eq!(Some((12.into(), 19.into())), ring.frames());
+        assert_eq!(None, ring.get(11.into()));
+        assert_eq!(Some(states[12]), ring.get(12.into()));
+    }
+
+    #[test]
+    fn restore_drops_newer_frames() {
+        let states = states(0, 8);
+        let mut ring = Ring::new();
+        for state in &states {
+            ring.push(state);
+        }
+
+        assert_eq!(Some(states[3]), ring.restore(3.into()));
+        assert_eq!(Some((0.into(), 3.into())), ring.frames());
+        assert_eq!(None, ring.get(4.into()));
+
+        // Resimulated frames replace the dropped ones.
+        ring.push(&states[4]);
+        assert_eq!(Some(states[4]), ring.get(4.into()));
+        assert_eq!(Some(states[0]), ring.get(0.into()));
+    }
+
+    #[test]
+    fn push_replaces_same_and_later_frames() {
+        let states = states(0, 6);
+        let mut ring = Ring::new();
+        for state in &states {
+            ring.push(state);
+        }
+
+        let mut changed = states[2];
+        changed.apply_input(1.into(), PlayerInput::from(u64::MAX));
+        ring.push(&changed);
+
+        assert_eq!(Some((0.into(), 2.into())), ring.frames());
+        assert_eq!(Some(changed), ring.get(2.into()));
+        assert_eq!(Some(states[1]), ring.get(1.into()));
+    }
+
+    #[test]
+    fn gap_clears_ring() {
+        let states = states(0, 6);
+        let mut ring = Ring::new();
+        ring.push(&states[0]);
+        ring.push(&states[1]);
+        ring.push(&states[5]);
+
+        assert_eq!(Some((5.into(), 5.into())), ring.frames());
+        assert_eq!(0, ring.pool_used());
+    }
+
+    #[test]
+    fn frames_wrap_around() {
+        let states = states(u16::MAX - 3, 8);
+        let mut ring = Ring::new();
+        for state in &states {
+            ring.push(state);
+        }
+
+        assert_eq!(Some(((u16::MAX - 3).into(), 3.into())), ring.frames());
+        for state in &states {
+            assert_eq!(Some(*state), ring.get(state.frame()));
+        }
+        assert_eq!(
+            Some(states[2]),
+            ring.restore(u16::MAX.wrapping_sub(1).into())
+        );
+    }
+
+    #[test]
+    fn small_pool_evicts_early() {
+        let states = states(0, 8);
+        let mut ring = SnapshotRing::<8, 64>::new();
+        for state in &states {
+            ring.push(state);
+            assert!(ring.pool_used() <= 64);
+        }
+
+        assert!(ring.len() < 8);
+        let (oldest, newest) = ring.frames().unwrap();
+        assert_eq!(Frame::from(7), newest);
+        for state in &states[oldest.inner() as usize..] {
+            assert_eq!(Some(*state), ring.get(state.frame()));
+        }
+    }
+
+    #[test]
+    fn deltas_are_smaller_than_states() {
+        let states = states(0, 8);
+        let mut ring = Ring::new();
+        for state in &states {
+            ring.push(state);
+        }
+
+        let memory: Vec<_> = ring.memory().collect();
+        assert_eq!(8, memory.len());
+        let (newest, older) = memory.split_last().unwrap();
+        assert_eq!(newest.state_bytes, newest.stored_bytes);
+        for m in older {
+            assert!(m.stored_bytes < m.state_bytes);
+        }
+        let deltas: usize = older.iter().map(|m| m.stored_bytes).sum();
+        assert_eq!(deltas, ring.pool_used());
+    }
+    /// Times a tick of the ring as `Game` uses it, pushing the new state and reading the oldest,
+    /// against keeping full copies of every state.
+    /// Run with `cargo test --release -- --ignored --nocapture ring_timing`.
+    #[test]
+    #[ignore = "timing, only meaningful in release"]
+    fn ring_timing() {
+        use std::{hint::black_box, time::Instant};
+
+        const FRAMES: usize = 32;
+        let states = states(0, 4096);
+
+        let mut ring = SnapshotRing::<FRAMES, { 64 * 1024 }>::new();
+        let start = Instant::now();
+        for state in &states {
+            ring.push(black_box(state));
+            let (oldest, _) = ring.frames().unwrap();
+            black_box(ring.get(oldest));
+        }
+        let ring_time = start.elapsed();
+
+        let mut copies = [State::new(); FRAMES];
+        let start = Instant::now();
+        for (i, state) in states.iter().enumerate() {
+            copies[i % FRAMES] = *black_box(state);
+            black_box(state.checksum());
+            black_box(copies[(i + 1) % FRAMES]);
+        }
+        let copy_time = start.elapsed();
+
+        let per_tick = |t: std::time::Duration| t.as_nanos() / states.len() as u128;
+        std::println!(
+            "ring: {} ns/tick, full copies: {} ns/tick",
+            per_tick(ring_time),
+            per_tick(copy_time)
+        );
+    }
+}
@@ -1,24 +1,208 @@
-use crate::player_input::PlayerInput;
+#[cfg(any(test, feature = "std"))]
+use crate::script::{ScriptEngine, ScriptError, Scripts};
+use crate::{
+    bytes::{ByteReader, ByteWriter},
+    ecs::{Registry, Schedule},
+    events::EventQueue,
+    math::hash::Fnv1a32,
+    player_input::PlayerInput,
+    rand::Rng,
+};
 
-use super::{frame::Frame, player_id::PlayerId};
+use super::{
+    frame::Frame,
+    player_id::{PlayerId, MAX_PLAYERS},
+};
 
-#[derive(Clone, Debug, PartialEq)]
+/// Everything that is simulated.
+/// All data is plain and pre-allocated, so copying a state for rollback is cheap.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct State {
     frame: Frame,
+    inputs: [PlayerInput; MAX_PLAYERS],
+    registry: Registry,
+    rng: Rng,
 }
 impl State {
+    /// The largest number of bytes `write_bytes` writes.
+    pub const MAX_SIZE: usize =
+        2 + MAX_PLAYERS * PlayerInput::MAX_SIZE + Registry::MAX_SIZE + Rng::SIZE;
+
+    /// Creates a new state starting at the first frame.
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Creates a new state starting at the first frame, with the random number generator seeded.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            frame: 0.into(),
+            inputs: [PlayerInput::new(); MAX_PLAYERS],
+            registry: Registry::new(),
+            rng: Rng::new(seed),
+        }
+    }
     pub fn frame(&self) -> Frame {
         self.frame
     }
+    /// Returns the entities and components being simulated.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+    pub fn registry_mut(&mut self) -> &mut Registry {
+        &mut self.registry
+    }
+    /// Returns the random number generator systems fork their streams from.
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
     pub fn copy_from(&mut self, other: &Self) {
-        *self = other.clone()
+        *self = *other
     }
     pub fn apply_input(self: &mut Self, player: PlayerId, input: PlayerInput) {
-        todo!("apply input to the state")
+        self.inputs[player.index()] = input;
+    }
+    /// Runs the systems for the current frame, then advances to the next one.
+    /// Systems report what happened by emitting to `events`.
+    pub fn tick(&mut self, schedule: &Schedule, events: &mut EventQueue) {
+        self.run_systems(schedule, events);
+        self.advance();
+    }
+
+    /// Ticks like `tick`, running the scripts after the systems.
+    /// The frame is ticked even if a script fails, and the first failure is returned.
+    #[cfg(any(test, feature = "std"))]
+    pub fn tick_with_scripts<E: ScriptEngine>(
+        &mut self,
+        schedule: &Schedule,
+        events: &mut EventQueue,
+        scripts: &mut Scripts<E>,
+    ) -> Result<(), ScriptError> {
+        self.run_systems(schedule, events);
+        let result = scripts.run(self.frame, &self.inputs, &self.rng, events);
+        self.advance();
+        result
     }
-    pub fn tick(&mut self) {
+
+    fn run_systems(&mut self, schedule: &Schedule, events: &mut EventQueue) {
+        schedule.run(
+            &mut self.registry,
+            self.frame,
+            &self.inputs,
+            events,
+            &self.rng,
+        );
+    }
+
+    fn advance(&mut self) {
+        // Advance so that every frame forks different streams.
+        self.rng.u64();
         self.frame = self.frame.increment();
+    }
+
+    /// Returns a checksum of the state that is stable across platforms.
+    pub fn checksum(&self) -> u32 {
+        let mut hasher = Fnv1a32::new();
+        self.write_bytes(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Writes the state as bytes that are stable across platforms.
+    pub fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        writer.write_u16(self.frame.inner());
+        for input in &self.inputs {
+            input.write_bytes(writer);
+        }
+        self.registry.write_bytes(writer);
+        self.rng.write_bytes(writer);
+    }
+
+    /// Reads a state that was written with `write_bytes`.
+    pub fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let mut state = Self::new();
+        state.frame = reader.read_u16()?.into();
+        for input in state.inputs.iter_mut() {
+            *input = PlayerInput::read_bytes(reader)?;
+        }
+        state.registry = Registry::read_bytes(reader)?;
+        state.rng = Rng::read_bytes(reader)?;
+
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::Position;
+
+    #[test]
+    fn checksum_is_stable() {
+        assert_eq!(State::new().checksum(), State::new().checksum());
+    }
+
+    #[test]
+    fn checksum_changes_with_frame() {
+        let a = State::new();
+        let mut b = State::new();
+        b.tick(&Schedule::new(), &mut EventQueue::new());
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut state = State::new();
+        state.apply_input(3.into(), 1.into());
+        state.tick(&Schedule::new(), &mut EventQueue::new());
+
+        let mut bytes = vec![];
+        state.write_bytes(&mut bytes);
+        assert_eq!(Some(state), State::read_bytes(&mut ByteReader::new(&bytes)));
+    }
+
+    #[test]
+    fn bytes_round_trip_with_entities() {
+        let mut state = State::new();
+        let registry = state.registry_mut();
+        let id = registry.spawn().unwrap();
+        registry.insert(id, Position([1.into(), 2.into()]));
+        registry.spawn().unwrap();
+        registry.despawn(id);
+
+        let mut bytes = vec![];
+        state.write_bytes(&mut bytes);
+        assert_eq!(Some(state), State::read_bytes(&mut ByteReader::new(&bytes)));
+    }
+
+    #[test]
+    fn checksum_changes_with_entities() {
+        let a = State::new();
+        let mut b = State::new();
+        b.registry_mut().spawn();
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn checksum_changes_with_input() {
+        let a = State::new();
+        let mut b = State::new();
+        b.apply_input(3.into(), 1.into());
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn checksum_changes_with_seed() {
+        assert_ne!(
+            State::with_seed(1).checksum(),
+            State::with_seed(2).checksum()
+        );
+    }
 
-        todo!("run through systems")
+    #[test]
+    fn rng_advances_each_tick() {
+        let mut state = State::with_seed(1);
+        let before = *state.rng();
+        state.tick(&Schedule::new(), &mut EventQueue::new());
+        assert_ne!(before, *state.rng());
     }
 }
@@ -0,0 +1,188 @@
+use crate::{
+    ecs::{Access, ComponentKind, Controller, Position, Schedule, System, SystemContext, Velocity},
+    events::Event,
+    math::DNum,
+    player_input::AxisPrecision,
+};
+
+/// How far a player moves each tick with the stick fully pressed.
+const PLAYER_SPEED: DNum = DNum::from_raw(1 << 14);
+
+/// Returns the systems the game runs each tick, in order.
+pub fn schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+    for system in [SPAWN_PLAYERS, MOVE_PLAYERS, INTEGRATE] {
+        schedule
+            .add(system)
+            .expect("default systems should have unique names");
+    }
+
+    schedule
+}
+
+const SPAWN_PLAYERS: System = System {
+    name: "spawn_players",
+    reads: Access::NONE,
+    writes: Access::NONE
+        .with(ComponentKind::Controller)
+        .with(ComponentKind::Position)
+        .with(ComponentKind::Velocity)
+        .with_entities(),
+    run: spawn_players,
+};
+
+/// Spawns an entity for every player that joined and despawns them once they leave.
+fn spawn_players(ctx: &mut SystemContext) {
+    let events = ctx.events();
+    for event in events.iter() {
+        match *event {
+            Event::PlayerJoined { player } => {
+                if let Some(id) = ctx.spawn() {
+                    let x = DNum::from(player.index() as i64 * 2);
+                    ctx.insert(id, Controller(player));
+                    ctx.insert(id, Position([x, DNum::ZERO]));
+                    ctx.insert(id, Velocity([DNum::ZERO; 2]));
+                }
+            }
+            Event::PlayerLeft { player } => {
+                let controlled = ctx
+                    .query::<Controller>()
+                    .find(|(_, c)| c.0 == player)
+                    .map(|(id, _)| id);
+                if let Some(id) = controlled {
+                    ctx.despawn(id);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+const MOVE_PLAYERS: System = System {
+    name: "move_players",
+    reads: Access::NONE.with(ComponentKind::Controller),
+    writes: Access::NONE.with(ComponentKind::Velocity),
+    run: move_players,
+};
+
+/// Sets the velocity of player controlled entities from the primary stick.
+fn move_players(ctx: &mut SystemContext) {
+    let inputs = ctx.inputs();
+    ctx.for_each::<Controller, Velocity, _>(|_, controller, velocity| {
+        let input = inputs[controller.0.index()];
+        let x = axis(input.primary_x_axis_steps(), input.precision());
+        let y = axis(input.primary_y_axis_steps(), input.precision());
+        velocity.0 = [x * PLAYER_SPEED, y * PLAYER_SPEED];
+    });
+}
+
+/// Converts a stored axis to a number from -1 to 1, without going through a float.
+fn axis(steps: i8, precision: AxisPrecision) -> DNum {
+    DNum::from(steps as i64) / DNum::from(precision.max_value() as i64)
+}
+
+const INTEGRATE: System = System {
+    name: "integrate",
+    reads: Access::NONE.with(ComponentKind::Velocity),
+    writes: Access::NONE.with(ComponentKind::Position),
+    run: integrate,
+};
+
+/// Moves every entity by its velocity.
+fn integrate(ctx: &mut SystemContext) {
+    ctx.for_each::<Velocity, Position, _>(|_, velocity, position| {
+        position.0[0] += velocity.0[0];
+        position.0[1] += velocity.0[1];
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::EventQueue,
+        game::{PlayerId, State},
+        player_input::PlayerInput,
+    };
+
+    fn tick(state: &mut State, events: &[Event]) {
+        let mut queue = EventQueue::new();
+        queue.begin(state.frame());
+        for event in events {
+            queue.emit(*event);
+        }
+        state.tick(&schedule(), &mut queue);
+    }
+
+    fn player_position(state: &State, player: PlayerId) -> Option<Position> {
+        let registry = state.registry();
+        let (id, _) = registry
+            .query::<Controller>()
+            .find(|(_, c)| c.0 == player)?;
+        registry.get::<Position>(id).copied()
+    }
+
+    #[test]
+    fn joining_spawns_player() {
+        let mut state = State::new();
+        tick(
+            &mut state,
+            &[
+                Event::PlayerJoined { player: 0.into() },
+                Event::PlayerJoined { player: 3.into() },
+            ],
+        );
+
+        assert_eq!(2, state.registry().entities().len());
+        assert_eq!(
+            Some(Position([6.into(), DNum::ZERO])),
+            player_position(&state, 3.into())
+        );
+    }
+
+    #[test]
+    fn leaving_despawns_player() {
+        let mut state = State::new();
+        tick(&mut state, &[Event::PlayerJoined { player: 1.into() }]);
+        tick(&mut state, &[Event::PlayerLeft { player: 1.into() }]);
+
+        assert_eq!(0, state.registry().entities().len());
+        assert_eq!(None, player_position(&state, 1.into()));
+    }
+
+    #[test]
+    fn input_moves_player() {
+        let mut state = State::new();
+        tick(&mut state, &[Event::PlayerJoined { player: 0.into() }]);
+
+        let mut input = PlayerInput::new();
+        input.set_primary_x_axis(1.0.into());
+        input.set_primary_y_axis((-1.0).into());
+        state.apply_input(0.into(), input);
+        for _ in 0..4 {
+            tick(&mut state, &[]);
+        }
+
+        assert_eq!(
+            Some(Position([DNum::ONE, -DNum::ONE])),
+            player_position(&state, 0.into())
+        );
+    }
+    #[test]
+    fn partial_input_moves_by_a_fixed_point_fraction() {
+        let mut state = State::new();
+        tick(&mut state, &[Event::PlayerJoined { player: 0.into() }]);
+
+        let mut input = PlayerInput::new();
+        input.set_primary_x_axis((3.0 / 7.0).into());
+        assert_eq!(3, input.primary_x_axis_steps());
+        state.apply_input(0.into(), input);
+        tick(&mut state, &[]);
+
+        // A quarter of 3/7, with both steps rounded down.
+        assert_eq!(
+            Some(Position([DNum::from_raw(7021), DNum::ZERO])),
+            player_position(&state, 0.into())
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use super::{InputPacket, Transport};
+use crate::{rand::Rng, time::Seconds};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+/// Network conditions to simulate for a loopback transport.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoopbackConfig {
+    /// The minimum time before a packet is delivered.
+    pub latency: Seconds,
+    /// The maximum random time added on top of the latency.
+    pub jitter: Seconds,
+    /// The chance from 0 to 1 that a packet is dropped.
+    pub packet_loss: f32,
+    /// The seed used for jitter and packet loss. Must not be 0.
+    pub seed: u64,
+}
+impl Default for LoopbackConfig {
+    fn default() -> Self {
+        Self {
+            latency: 0.0.into(),
+            jitter: 0.0.into(),
+            packet_loss: 0.0,
+            seed: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InFlight {
+    remaining: f32,
+    packet: InputPacket,
+}
+
+type Queue = Rc<RefCell<VecDeque<InFlight>>>;
+
+/// An in-process transport connected to exactly one other loopback transport.
+pub struct LoopbackTransport {
+    config: LoopbackConfig,
+    rng: Rng,
+    outgoing: Queue,
+    incoming: Queue,
+}
+impl LoopbackTransport {
+    /// Creates a pair of transports connected to each other.
+    pub fn pair(config: LoopbackConfig) -> (Self, Self) {
+        let a_to_b = Queue::default();
+        let b_to_a = Queue::default();
+
+        let a = Self {
+            config,
+            rng: Rng::new(config.seed),
+            outgoing: a_to_b.clone(),
+            incoming: b_to_a.clone(),
+        };
+        let b = Self {
+            config,
+            rng: Rng::new(config.seed.wrapping_add(1)),
+            outgoing: b_to_a,
+            incoming: a_to_b,
+        };
+
+        (a, b)
+    }
+}
+impl Transport for LoopbackTransport {
+    fn update(&mut self, delta_t: Seconds) {
+        for in_flight in self.incoming.borrow_mut().iter_mut() {
+            in_flight.remaining -= delta_t.inner();
+        }
+    }
+
+    fn send(&mut self, packet: &InputPacket) {
+        if self.rng.f32() < self.config.packet_loss {
+            return;
+        }
+
+        let remaining = self.config.latency.inner() + self.rng.f32() * self.config.jitter.inner();
+        self.outgoing.borrow_mut().push_back(InFlight {
+            remaining,
+            packet: *packet,
+        });
+    }
+
+    fn receive(&mut self) -> Option<InputPacket> {
+        // Jitter may cause later packets to arrive first.
+        let mut incoming = self.incoming.borrow_mut();
+        let index = incoming.iter().position(|p| p.remaining <= 0.0)?;
+        incoming.remove(index).map(|p| p.packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(player: u8) -> InputPacket {
+        InputPacket::new(player.into(), 0.into(), 0.into())
+    }
+
+    #[test]
+    fn pair_delivers_both_ways() {
+        let (mut a, mut b) = LoopbackTransport::pair(LoopbackConfig::default());
+        a.send(&packet(0));
+        b.send(&packet(1));
+        assert_eq!(Some(packet(0)), b.receive());
+        assert_eq!(Some(packet(1)), a.receive());
+        assert_eq!(None, a.receive());
+        assert_eq!(None, b.receive());
+    }
+
+    #[test]
+    fn latency_delays_delivery() {
+        let (mut a, mut b) = LoopbackTransport::pair(LoopbackConfig {
+            latency: 0.1.into(),
+            ..Default::default()
+        });
+        a.send(&packet(0));
+        b.update(0.05.into());
+        assert_eq!(None, b.receive());
+
+        b.update(0.05.into());
+        assert_eq!(Some(packet(0)), b.receive());
+    }
+
+    #[test]
+    fn full_packet_loss_drops_everything() {
+        let (mut a, mut b) = LoopbackTransport::pair(LoopbackConfig {
+            packet_loss: 1.0,
+            ..Default::default()
+        });
+        for _ in 0..10 {
+            a.send(&packet(0));
+        }
+        b.update(1.0.into());
+        assert_eq!(None, b.receive());
+    }
+
+    #[test]
+    fn jitter_reorders_packets() {
+        let (mut a, mut b) = LoopbackTransport::pair(LoopbackConfig {
+            jitter: 1.0.into(),
+            ..Default::default()
+        });
+        for player in 0..10 {
+            a.send(&packet(player));
+        }
+
+        let mut received = vec![];
+        for _ in 0..100 {
+            b.update(0.01.into());
+            while let Some(p) = b.receive() {
+                received.push(p.player().index());
+            }
+        }
+
+        assert_eq!(10, received.len());
+        assert_ne!((0..10).collect::<Vec<_>>(), received);
+    }
+}
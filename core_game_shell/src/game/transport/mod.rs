@@ -0,0 +1,236 @@
+#[cfg(any(test, feature = "std"))]
+mod loopback;
+#[cfg(any(test, feature = "std"))]
+mod udp;
+
+#[cfg(any(test, feature = "std"))]
+pub use loopback::*;
+#[cfg(any(test, feature = "std"))]
+pub use udp::*;
+
+use super::{frame::Frame, player_id::PlayerId};
+use crate::{
+    bytes::{ByteReader, ByteWriter, SliceWriter},
+    player_input::PlayerInput,
+    time::Seconds,
+};
+
+/// Something that can send and receive player input between peers.
+pub trait Transport {
+    /// Advances any time based behavior of the transport.
+    fn update(&mut self, _delta_t: Seconds) {}
+
+    /// Sends the packet to all remote peers.
+    fn send(&mut self, packet: &InputPacket);
+
+    /// Returns the next packet received from a remote peer, if any.
+    fn receive(&mut self) -> Option<InputPacket>;
+}
+
+/// A run of inputs for a single player, starting at a given frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputPacket {
+    player: PlayerId,
+    ack: Frame,
+    checksum_frame: Frame,
+    checksum: u32,
+    frame: Frame,
+    frame_advantage: i8,
+    start_frame: Frame,
+    len: usize,
+    inputs: [PlayerInput; Self::MAX_INPUTS],
+}
+impl InputPacket {
+    /// The maximum number of inputs a single packet can hold.
+    pub const MAX_INPUTS: usize = 32;
+    const HEADER_SIZE: usize = 15;
+    /// The maximum number of bytes a serialized packet takes.
+    pub const MAX_SIZE: usize = Self::HEADER_SIZE + Self::MAX_INPUTS * PlayerInput::MAX_SIZE;
+
+    /// Creates a new empty packet.
+    /// `ack` is the last frame the sender has confirmed input for all players.
+    pub fn new(player: PlayerId, ack: Frame, start_frame: Frame) -> Self {
+        Self {
+            player,
+            ack,
+            checksum_frame: start_frame,
+            checksum: 0,
+            frame: start_frame,
+            frame_advantage: 0,
+            start_frame,
+            len: 0,
+            inputs: [PlayerInput::new(); Self::MAX_INPUTS],
+        }
+    }
+
+    /// Returns the player the inputs belong to.
+    pub fn player(&self) -> PlayerId {
+        self.player
+    }
+
+    /// Returns the last frame the sender has confirmed input for all players.
+    pub fn ack(&self) -> Frame {
+        self.ack
+    }
+
+    /// Sets the checksum of the sender's state at the given confirmed frame.
+    pub fn set_checksum(&mut self, frame: Frame, checksum: u32) {
+        self.checksum_frame = frame;
+        self.checksum = checksum;
+    }
+
+    /// Returns the checksum of the sender's state at a confirmed frame.
+    pub fn checksum(&self) -> (Frame, u32) {
+        (self.checksum_frame, self.checksum)
+    }
+
+    /// Sets the sender's current frame and how many frames it is ahead of the receiver.
+    /// Used by both peers to adjust their tick rate so neither runs ahead.
+    pub fn set_frame_advantage(&mut self, frame: Frame, frame_advantage: i8) {
+        self.frame = frame;
+        self.frame_advantage = frame_advantage;
+    }
+
+    /// Returns the sender's current frame and how many frames it was ahead of the receiver.
+    pub fn frame_advantage(&self) -> (Frame, i8) {
+        (self.frame, self.frame_advantage)
+    }
+
+    /// Returns whether the packet holds no inputs.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds the input for the next frame. Returns false if the packet is full.
+    pub fn push(&mut self, input: PlayerInput) -> bool {
+        if self.len < Self::MAX_INPUTS {
+            self.inputs[self.len] = input;
+            self.len += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns all inputs along with the frame they are for.
+    pub fn inputs(&self) -> impl Iterator<Item = (Frame, PlayerInput)> + '_ {
+        let mut frame = self.start_frame;
+        self.inputs[..self.len].iter().map(move |input| {
+            let current = frame;
+            frame = frame.increment();
+            (current, *input)
+        })
+    }
+
+    /// Serializes the packet into the buffer, returning the number of bytes written.
+    pub fn to_bytes(self, buffer: &mut [u8; Self::MAX_SIZE]) -> usize {
+        let mut writer = SliceWriter::new(buffer);
+        writer.write_u8(self.player.index() as u8);
+        writer.write_u16(self.ack.inner());
+        writer.write_u16(self.start_frame.inner());
+        writer.write_u16(self.checksum_frame.inner());
+        writer.write_u32(self.checksum);
+        writer.write_u16(self.frame.inner());
+        writer.write_u8(self.frame_advantage as u8);
+        writer.write_u8(self.len as u8);
+
+        for input in &self.inputs[..self.len] {
+            input.write_bytes(&mut writer);
+        }
+
+        writer.len()
+    }
+
+    /// Attempts to deserialize a packet from the given bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let player = reader.read_u8()?;
+        let ack = reader.read_u16()?;
+        let start_frame = reader.read_u16()?;
+        let checksum_frame = reader.read_u16()?;
+        let checksum = reader.read_u32()?;
+        let frame = reader.read_u16()?;
+        let frame_advantage = reader.read_u8()? as i8;
+        let len = reader.read_u8()? as usize;
+        if player as usize >= super::player_id::MAX_PLAYERS || len > Self::MAX_INPUTS {
+            return None;
+        }
+
+        let mut packet = Self::new(player.into(), ack.into(), start_frame.into());
+        packet.set_checksum(checksum_frame.into(), checksum);
+        packet.set_frame_advantage(frame.into(), frame_advantage);
+        for _ in 0..len {
+            packet.push(PlayerInput::read_bytes(&mut reader)?);
+        }
+
+        if reader.is_empty() {
+            Some(packet)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet() -> InputPacket {
+        let mut p = InputPacket::new(3.into(), 9.into(), 10.into());
+        p.push(1.into());
+        p.push(2.into());
+        p.push(u64::MAX.into());
+        p.set_checksum(8.into(), 0xDEAD_BEEF);
+        p.set_frame_advantage(11.into(), -3);
+        p
+    }
+
+    #[test]
+    fn inputs_are_tagged_with_frames() {
+        let p = packet();
+        let inputs: Vec<_> = p.inputs().collect();
+        assert_eq!(
+            vec![
+                (10.into(), 1.into()),
+                (11.into(), 2.into()),
+                (12.into(), u64::MAX.into())
+            ],
+            inputs
+        );
+    }
+
+    #[test]
+    fn push_returns_false_when_full() {
+        let mut p = InputPacket::new(0.into(), 0.into(), 0.into());
+        for _ in 0..InputPacket::MAX_INPUTS {
+            assert!(p.push(PlayerInput::new()));
+        }
+        assert!(!p.push(PlayerInput::new()));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let p = packet();
+        let mut buffer = [0; InputPacket::MAX_SIZE];
+        let len = p.to_bytes(&mut buffer);
+        assert_eq!(Some(p), InputPacket::from_bytes(&buffer[..len]));
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_packets() {
+        let p = packet();
+        let mut buffer = [0; InputPacket::MAX_SIZE];
+        let len = p.to_bytes(&mut buffer);
+        assert_eq!(None, InputPacket::from_bytes(&buffer[..len - 1]));
+        assert_eq!(None, InputPacket::from_bytes(&buffer[..3]));
+    }
+
+    #[test]
+    fn from_bytes_rejects_invalid_player() {
+        let p = packet();
+        let mut buffer = [0; InputPacket::MAX_SIZE];
+        let len = p.to_bytes(&mut buffer);
+        buffer[0] = 200;
+        assert_eq!(None, InputPacket::from_bytes(&buffer[..len]));
+    }
+}
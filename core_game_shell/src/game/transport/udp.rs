@@ -0,0 +1,138 @@
+use super::{InputPacket, PlayerId, Transport};
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    vec::Vec,
+};
+
+/// A non-blocking transport that sends packets to all peers over UDP.
+/// Each peer address is bound to the player it sends input for.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peers: Vec<(SocketAddr, PlayerId)>,
+}
+impl UdpTransport {
+    /// Binds a new transport to the given address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            peers: Vec::new(),
+        })
+    }
+
+    /// Returns the address the transport is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Adds a peer to send packets to and receive packets from.
+    /// Only packets for `player` are accepted from the address, so a peer can't send input for others.
+    pub fn add_peer(&mut self, addr: SocketAddr, player: PlayerId) {
+        match self.peers.iter_mut().find(|(a, _)| *a == addr) {
+            Some(peer) => peer.1 = player,
+            None => self.peers.push((addr, player)),
+        }
+    }
+
+    /// Returns the player bound to the address.
+    fn peer_player(&self, addr: SocketAddr) -> Option<PlayerId> {
+        self.peers
+            .iter()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, player)| *player)
+    }
+}
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &InputPacket) {
+        let mut buffer = [0; InputPacket::MAX_SIZE];
+        let len = packet.to_bytes(&mut buffer);
+
+        // Lost packets are resent by the game until they are acknowledged.
+        for (peer, _) in &self.peers {
+            let _ = self.socket.send_to(&buffer[..len], peer);
+        }
+    }
+
+    fn receive(&mut self) -> Option<InputPacket> {
+        let mut buffer = [0; InputPacket::MAX_SIZE];
+        loop {
+            // Anything other than a valid packet from a known peer, for that peer's player, is discarded.
+            let (len, from) = self.socket.recv_from(&mut buffer).ok()?;
+            let player = match self.peer_player(from) {
+                Some(player) => player,
+                None => continue,
+            };
+
+            match InputPacket::from_bytes(&buffer[..len]) {
+                Some(packet) if packet.player() == player => return Some(packet),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_and_receives_between_peers() {
+        let mut a = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let mut b = UdpTransport::bind("127.0.0.1:0").unwrap();
+        a.add_peer(b.local_addr().unwrap(), 2.into());
+        b.add_peer(a.local_addr().unwrap(), 1.into());
+
+        let mut packet = InputPacket::new(1.into(), 4.into(), 5.into());
+        packet.push(7.into());
+        a.send(&packet);
+
+        let mut received = None;
+        for _ in 0..1000 {
+            received = b.receive();
+            if received.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert_eq!(Some(packet), received);
+    }
+
+    #[test]
+    fn ignores_unknown_peers() {
+        let mut a = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let mut b = UdpTransport::bind("127.0.0.1:0").unwrap();
+        a.add_peer(b.local_addr().unwrap(), 2.into());
+
+        a.send(&InputPacket::new(1.into(), 4.into(), 5.into()));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        assert_eq!(None, b.receive());
+    }
+
+    #[test]
+    fn ignores_packets_for_other_players() {
+        let mut a = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let mut b = UdpTransport::bind("127.0.0.1:0").unwrap();
+        a.add_peer(b.local_addr().unwrap(), 2.into());
+        b.add_peer(a.local_addr().unwrap(), 1.into());
+
+        // a is bound to player 1, so input it claims is from player 0 is dropped.
+        a.send(&InputPacket::new(0.into(), 4.into(), 5.into()));
+        let packet = InputPacket::new(1.into(), 4.into(), 5.into());
+        a.send(&packet);
+
+        let mut received = vec![];
+        for _ in 0..100 {
+            if let Some(packet) = b.receive() {
+                received.push(packet);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert_eq!(vec![packet], received);
+    }
+}
@@ -0,0 +1,452 @@
+use super::{
+    character::Character,
+    characteristics::Characteristic,
+    dice::{roll_check, roll_killing_damage, roll_normal_damage, CheckResult, DamageDice},
+    dice::{DiceError, KillingDamage, NormalDamage},
+    Kilograms, Meters, Number, PositiveNumber,
+};
+use crate::rand::Rng;
+
+/// The base roll needed to hit, before OCV and DCV.
+const BASE_TO_HIT: i64 = 11;
+/// Targets up to this far away have no range penalty.
+const RANGE_BAND: i64 = 8;
+/// The mass knockback is worked out for. Each doubling above adds a die to resist it.
+const STANDARD_MASS: i64 = 100;
+/// The dice rolled to resist knockback at the standard mass.
+const KNOCKBACK_DICE: u64 = 2;
+/// Every point of knockback moves the target this many meters.
+const METERS_PER_KNOCKBACK: i64 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DamageKind {
+    Normal,
+    Killing,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DefenseType {
+    Physical,
+    Energy,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Attack {
+    pub dice: DamageDice,
+    pub kind: DamageKind,
+    pub defense: DefenseType,
+    /// Halves the defenses the attack is applied against.
+    pub armor_piercing: bool,
+}
+
+/// The defenses that reduce damage.
+/// Resistant defenses also count as normal defenses, and are the only ones that stop killing BODY.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Defenses {
+    pub physical: PositiveNumber,
+    pub energy: PositiveNumber,
+    pub resistant_physical: PositiveNumber,
+    pub resistant_energy: PositiveNumber,
+}
+impl Defenses {
+    /// Returns the PD and ED of the character, with resistant defenses from powers such as Armor.
+    pub fn of(character: &Character) -> Self {
+        let mut defenses = Self {
+            physical: character.characteristic(Characteristic::PhysicalDefense),
+            energy: character.characteristic(Characteristic::EnergyDefense),
+            resistant_physical: 0.into(),
+            resistant_energy: 0.into(),
+        };
+        for power in character.powers() {
+            let (physical, energy) = power.resistant_defenses();
+            defenses.resistant_physical += physical;
+            defenses.resistant_energy += energy;
+        }
+
+        defenses
+    }
+
+    /// Returns the normal and resistant defense against the type.
+    fn against(&self, defense: DefenseType) -> (u64, u64) {
+        let (normal, resistant) = match defense {
+            DefenseType::Physical => (self.physical, self.resistant_physical),
+            DefenseType::Energy => (self.energy, self.resistant_energy),
+        };
+        (normal.inner() + resistant.inner(), resistant.inner())
+    }
+}
+
+/// Everything about the target of an attack.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Target {
+    pub dcv: PositiveNumber,
+    pub defenses: Defenses,
+    pub mass: Kilograms,
+}
+impl Target {
+    pub fn new(character: &Character, mass: Kilograms) -> Self {
+        Self {
+            dcv: character.characteristic(Characteristic::DefensiveCombatValue),
+            defenses: Defenses::of(character),
+            mass,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DamageRoll {
+    Normal(NormalDamage),
+    Killing(KillingDamage),
+}
+impl DamageRoll {
+    /// Returns the STUN and BODY rolled, before defenses.
+    pub fn rolled(&self) -> (PositiveNumber, PositiveNumber) {
+        match self {
+            DamageRoll::Normal(d) => (d.stun, d.body),
+            DamageRoll::Killing(d) => (d.stun, d.body),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Knockback {
+    /// The dice rolled to resist knockback, which depend on the target's mass.
+    pub dice: u64,
+    pub roll: PositiveNumber,
+    pub distance: Meters,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hit {
+    pub damage: DamageRoll,
+    /// The STUN that got through defenses.
+    pub stun: PositiveNumber,
+    /// The BODY that got through defenses.
+    pub body: PositiveNumber,
+    pub knockback: Knockback,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AttackResult {
+    pub to_hit: CheckResult,
+    /// What the attack did, or `None` if it missed.
+    pub hit: Option<Hit>,
+}
+
+/// Returns the OCV modifier for attacking something the distance away.
+/// Up to 8m there is no penalty, then every doubling of the distance is another -2.
+pub fn range_modifier(distance: Meters) -> Number {
+    let mut band = RANGE_BAND;
+    let mut modifier = 0;
+    while distance.inner() > band {
+        band *= 2;
+        modifier -= 2;
+    }
+
+    modifier.into()
+}
+
+/// Returns the roll needed on 3d6 to hit, which is 11 + OCV - DCV plus modifiers.
+pub fn to_hit_target(ocv: PositiveNumber, dcv: PositiveNumber, modifier: Number) -> PositiveNumber {
+    let target = BASE_TO_HIT + ocv.inner() as i64 - dcv.inner() as i64 + modifier.inner();
+    (target.max(0) as u64).into()
+}
+
+/// Returns the dice rolled to resist knockback. Heavier targets roll more, lighter ones fewer.
+pub fn knockback_dice(mass: Kilograms, kind: DamageKind) -> u64 {
+    let mut dice = KNOCKBACK_DICE as i64;
+    if mass.inner() > STANDARD_MASS {
+        let mut m = STANDARD_MASS * 2;
+        while mass.inner() >= m {
+            dice += 1;
+            m *= 2;
+        }
+    } else {
+        let mut m = STANDARD_MASS / 2;
+        while mass.inner() <= m && dice > 0 {
+            dice -= 1;
+            m /= 2;
+        }
+    }
+
+    // Killing attacks rarely knock anything back.
+    if kind == DamageKind::Killing {
+        dice += 1;
+    }
+
+    dice as u64
+}
+
+/// Makes the attack, rolling to hit, for damage and for knockback.
+pub fn resolve_attack(
+    rng: &mut Rng,
+    ocv: PositiveNumber,
+    attack: &Attack,
+    target: &Target,
+    distance: Meters,
+) -> Result<AttackResult, DiceError> {
+    let to_hit = roll_check(
+        rng,
+        to_hit_target(ocv, target.dcv, range_modifier(distance)),
+    );
+    if !to_hit.success {
+        return Ok(AttackResult { to_hit, hit: None });
+    }
+
+    let (mut normal_defense, mut resistant_defense) = target.defenses.against(attack.defense);
+    if attack.armor_piercing {
+        normal_defense /= 2;
+        resistant_defense /= 2;
+    }
+    let (damage, stun, body) = match attack.kind {
+        DamageKind::Normal => {
+            let damage = roll_normal_damage(rng, attack.dice)?;
+            let stun = damage.stun.inner().saturating_sub(normal_defense);
+            let body = damage.body.inner().saturating_sub(normal_defense);
+            (DamageRoll::Normal(damage), stun, body)
+        }
+        DamageKind::Killing => {
+            let damage = roll_killing_damage(rng, attack.dice)?;
+            let stun = damage.stun.inner().saturating_sub(normal_defense);
+            let body = damage.body.inner().saturating_sub(resistant_defense);
+            (DamageRoll::Killing(damage), stun, body)
+        }
+    };
+
+    // Knockback uses the BODY rolled, not what got through defenses.
+    let dice = knockback_dice(target.mass, attack.kind);
+    let roll = rng.dice(dice as u32, 6) as u64;
+    let (_, rolled_body) = damage.rolled();
+    let distance = rolled_body.inner().saturating_sub(roll) as i64 * METERS_PER_KNOCKBACK;
+
+    Ok(AttackResult {
+        to_hit,
+        hit: Some(Hit {
+            damage,
+            stun: stun.into(),
+            body: body.into(),
+            knockback: Knockback {
+                dice,
+                roll: roll.into(),
+                distance: distance.into(),
+            },
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hero_system::character::CharacterBuilder;
+
+    fn attack(kind: DamageKind, dice: u64) -> Attack {
+        Attack {
+            dice: dice.into(),
+            kind,
+            defense: DefenseType::Physical,
+            armor_piercing: false,
+        }
+    }
+
+    fn target(pd: u64, resistant: u64) -> Target {
+        let mut b = CharacterBuilder::new(100.into());
+        if pd > 2 {
+            b.buy(Characteristic::PhysicalDefense, (pd - 2).into())
+                .unwrap();
+        }
+        let mut target = Target::new(&b.build(), 100.into());
+        target.defenses.resistant_physical = resistant.into();
+        target
+    }
+
+    #[test]
+    fn range_modifier_doubles() {
+        let modifiers: Vec<_> = [0, 8, 9, 16, 17, 32, 33, 64, 65]
+            .iter()
+            .map(|d| range_modifier((*d).into()).inner())
+            .collect();
+        assert_eq!(vec![0, 0, -2, -2, -4, -4, -6, -6, -8], modifiers);
+    }
+
+    #[test]
+    fn to_hit_is_11_plus_ocv_minus_dcv() {
+        assert_eq!(
+            PositiveNumber::from(11),
+            to_hit_target(3.into(), 3.into(), 0.into())
+        );
+        assert_eq!(
+            PositiveNumber::from(12),
+            to_hit_target(5.into(), 2.into(), (-2).into())
+        );
+        assert_eq!(
+            PositiveNumber::from(0),
+            to_hit_target(0.into(), 20.into(), 0.into())
+        );
+    }
+
+    #[test]
+    fn misses_do_no_damage() {
+        let mut rng = Rng::new(1);
+        let target = Target {
+            dcv: 30.into(),
+            ..target(5, 0)
+        };
+        for _ in 0..100 {
+            let result = resolve_attack(
+                &mut rng,
+                3.into(),
+                &attack(DamageKind::Normal, 6),
+                &target,
+                0.into(),
+            )
+            .unwrap();
+            // Only a roll of 3 can hit.
+            assert_eq!(result.to_hit.success, result.hit.is_some());
+            assert_eq!(result.to_hit.total.inner() == 3, result.to_hit.success);
+        }
+    }
+
+    #[test]
+    fn normal_damage_subtracts_defenses() {
+        let mut rng = Rng::new(2);
+        let target = Target {
+            dcv: 0.into(),
+            ..target(5, 0)
+        };
+        let mut hits = 0;
+        for _ in 0..200 {
+            let result = resolve_attack(
+                &mut rng,
+                10.into(),
+                &attack(DamageKind::Normal, 6),
+                &target,
+                0.into(),
+            )
+            .unwrap();
+            if let Some(Hit {
+                damage, stun, body, ..
+            }) = result.hit
+            {
+                let (rolled_stun, rolled_body) = damage.rolled();
+                assert_eq!(rolled_stun.inner().saturating_sub(5), stun.inner());
+                assert_eq!(rolled_body.inner().saturating_sub(5), body.inner());
+                hits += 1;
+            }
+        }
+        assert!(hits > 150);
+    }
+
+    #[test]
+    fn killing_body_only_stopped_by_resistant_defenses() {
+        let mut rng = Rng::new(3);
+        let target = Target {
+            dcv: 0.into(),
+            ..target(8, 2)
+        };
+        for _ in 0..200 {
+            let result = resolve_attack(
+                &mut rng,
+                10.into(),
+                &attack(DamageKind::Killing, 3),
+                &target,
+                0.into(),
+            )
+            .unwrap();
+            if let Some(Hit {
+                damage, stun, body, ..
+            }) = result.hit
+            {
+                assert!(matches!(damage, DamageRoll::Killing(_)));
+                let (rolled_stun, rolled_body) = damage.rolled();
+                assert_eq!(rolled_stun.inner().saturating_sub(10), stun.inner());
+                assert_eq!(rolled_body.inner().saturating_sub(2), body.inner());
+            }
+        }
+    }
+
+    #[test]
+    fn armor_piercing_halves_defenses() {
+        let mut rng = Rng::new(6);
+        let target = Target {
+            dcv: 0.into(),
+            ..target(8, 3)
+        };
+        let attack = Attack {
+            armor_piercing: true,
+            ..attack(DamageKind::Killing, 3)
+        };
+        for _ in 0..200 {
+            let result = resolve_attack(&mut rng, 10.into(), &attack, &target, 0.into()).unwrap();
+            if let Some(Hit {
+                damage, stun, body, ..
+            }) = result.hit
+            {
+                let (rolled_stun, rolled_body) = damage.rolled();
+                assert_eq!(rolled_stun.inner().saturating_sub(5), stun.inner());
+                assert_eq!(rolled_body.inner().saturating_sub(1), body.inner());
+            }
+        }
+    }
+
+    #[test]
+    fn knockback_dice_follow_mass() {
+        let dice = |kg: i64| knockback_dice(kg.into(), DamageKind::Normal);
+        assert_eq!(2, dice(100));
+        assert_eq!(2, dice(199));
+        assert_eq!(3, dice(200));
+        assert_eq!(4, dice(400));
+        assert_eq!(1, dice(50));
+        assert_eq!(0, dice(25));
+        assert_eq!(0, dice(1));
+        assert_eq!(3, knockback_dice(100.into(), DamageKind::Killing));
+    }
+
+    #[test]
+    fn knockback_is_body_over_roll() {
+        let mut rng = Rng::new(4);
+        let target = Target {
+            dcv: 0.into(),
+            mass: 1.into(),
+            ..target(2, 0)
+        };
+        for _ in 0..100 {
+            let result = resolve_attack(
+                &mut rng,
+                10.into(),
+                &attack(DamageKind::Normal, 10),
+                &target,
+                0.into(),
+            )
+            .unwrap();
+            if let Some(Hit {
+                damage, knockback, ..
+            }) = result.hit
+            {
+                // Light targets roll no dice, so every point of BODY knocks them back.
+                assert_eq!(0, knockback.dice);
+                let (_, body) = damage.rolled();
+                assert_eq!(body.inner() as i64 * 2, knockback.distance.inner());
+            }
+        }
+    }
+
+    #[test]
+    fn attacks_are_deterministic() {
+        let target = target(3, 0);
+        let a = resolve_attack(
+            &mut Rng::new(5),
+            5.into(),
+            &attack(DamageKind::Normal, 4),
+            &target,
+            20.into(),
+        );
+        let b = resolve_attack(
+            &mut Rng::new(5),
+            5.into(),
+            &attack(DamageKind::Normal, 4),
+            &target,
+            20.into(),
+        );
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,487 @@
+use super::{Character, CharacteristicValue, Characteristics, MovementCharacteristics, MAX_POWERS};
+use crate::hero_system::{
+    characteristics::{CharacterPoints, Characteristic, MovementType},
+    powers::Power,
+    Meters, PositiveNumber,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BuildError {
+    /// Purchases must raise the value by at least one.
+    NothingPurchased,
+    /// The purchase costs more than is left in the budget.
+    OverBudget {
+        cost: CharacterPoints,
+        remaining: CharacterPoints,
+    },
+    /// The character already has `MAX_POWERS` powers.
+    TooManyPowers,
+}
+
+/// A single line of the point breakdown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Purchase {
+    Characteristic {
+        characteristic: Characteristic,
+        bought: PositiveNumber,
+        cost: CharacterPoints,
+    },
+    Movement {
+        movement: MovementType,
+        bought: Meters,
+        cost: CharacterPoints,
+    },
+    Power {
+        power: Power,
+        cost: CharacterPoints,
+    },
+}
+impl Purchase {
+    pub fn cost(&self) -> CharacterPoints {
+        match self {
+            Purchase::Characteristic { cost, .. } => *cost,
+            Purchase::Movement { cost, .. } => *cost,
+            Purchase::Power { cost, .. } => *cost,
+        }
+    }
+}
+
+/// Builds a character by buying characteristics and movement on top of the base values, and powers.
+/// Costs are worked out on the total bought for each item, so buying END 3 then 2 costs the same as buying 5.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CharacterBuilder {
+    budget: CharacterPoints,
+    characteristics: [u64; Characteristic::ALL.len()],
+    movement: [u64; MovementType::ALL.len()],
+    powers: [Option<Power>; MAX_POWERS],
+}
+impl CharacterBuilder {
+    /// Creates a new builder with everything at its base value.
+    pub fn new(budget: CharacterPoints) -> Self {
+        Self {
+            budget,
+            characteristics: [0; Characteristic::ALL.len()],
+            movement: [0; MovementType::ALL.len()],
+            powers: [None; MAX_POWERS],
+        }
+    }
+
+    /// Raises the characteristic by `amount`, returning what it cost.
+    pub fn buy(
+        &mut self,
+        characteristic: Characteristic,
+        amount: PositiveNumber,
+    ) -> Result<CharacterPoints, BuildError> {
+        let i = characteristic as usize;
+        let cost = characteristic.cost();
+        let (total, added) = self.spend(
+            amount.inner(),
+            raise(
+                self.characteristics[i],
+                amount.inner(),
+                u64::MAX - characteristic.base_value().inner(),
+                |n| cost.cost_of(n.into()),
+            ),
+        )?;
+
+        self.characteristics[i] = total;
+        Ok(added.into())
+    }
+
+    /// Raises the movement by `meters`, returning what it cost.
+    pub fn buy_movement(
+        &mut self,
+        movement: MovementType,
+        meters: Meters,
+    ) -> Result<CharacterPoints, BuildError> {
+        let amount = meters.inner().max(0) as u64;
+        let i = movement as usize;
+        let cost = movement.cost();
+        let most = (i64::MAX - movement.base_value().inner()) as u64;
+        let (total, added) = self.spend(
+            amount,
+            raise(self.movement[i], amount, most, |n| cost.cost_of(n)),
+        )?;
+
+        self.movement[i] = total;
+        Ok(added.into())
+    }
+
+    /// Adds the power, returning its real cost.
+    pub fn buy_power(&mut self, power: Power) -> Result<CharacterPoints, BuildError> {
+        let slot = self
+            .powers
+            .iter()
+            .position(|p| p.is_none())
+            .ok_or(BuildError::TooManyPowers)?;
+        let active = counted(power.active_cost()).inner();
+        let cost = power.real_cost().map(|c| (0, c.inner()));
+
+        let (_, cost) = self.spend(active, cost)?;
+        self.powers[slot] = Some(power);
+        Ok(cost.into())
+    }
+
+    /// Checks that `amount` can be bought with the budget left, passing back the new total and its cost.
+    /// Purchases whose total or cost are too large to count are always over budget.
+    fn spend(&self, amount: u64, raised: Option<(u64, u64)>) -> Result<(u64, u64), BuildError> {
+        if amount == 0 {
+            return Err(BuildError::NothingPurchased);
+        }
+
+        let remaining = self.remaining();
+        match raised {
+            Some((total, cost)) if cost <= remaining.inner() => Ok((total, cost)),
+            _ => Err(BuildError::OverBudget {
+                cost: counted(raised.map(|(_, cost)| cost.into())),
+                remaining,
+            }),
+        }
+    }
+
+    pub fn budget(&self) -> CharacterPoints {
+        self.budget
+    }
+
+    /// Returns the total points spent.
+    pub fn spent(&self) -> CharacterPoints {
+        self.breakdown()
+            .map(|p| p.cost().inner())
+            .sum::<u64>()
+            .into()
+    }
+
+    /// Returns the points left to spend.
+    pub fn remaining(&self) -> CharacterPoints {
+        (self.budget.inner() - self.spent().inner()).into()
+    }
+
+    /// Returns what was bought and its cost, for every item that was raised.
+    pub fn breakdown(&self) -> impl Iterator<Item = Purchase> + '_ {
+        let characteristics = Characteristic::ALL
+            .iter()
+            .zip(self.characteristics.iter())
+            .filter(|(_, bought)| **bought > 0)
+            .map(|(characteristic, bought)| Purchase::Characteristic {
+                characteristic: *characteristic,
+                bought: (*bought).into(),
+                cost: counted(characteristic.cost().cost_of((*bought).into())),
+            });
+        let movement = MovementType::ALL
+            .iter()
+            .zip(self.movement.iter())
+            .filter(|(_, bought)| **bought > 0)
+            .map(|(movement, bought)| Purchase::Movement {
+                movement: *movement,
+                bought: (*bought as i64).into(),
+                cost: counted(movement.cost().cost_of(*bought)),
+            });
+
+        let powers = self.powers.iter().flatten().map(|power| Purchase::Power {
+            power: *power,
+            cost: counted(power.real_cost()),
+        });
+
+        characteristics.chain(movement).chain(powers)
+    }
+
+    /// Returns the value the characteristic will have, including the base value.
+    pub fn value(&self, characteristic: Characteristic) -> PositiveNumber {
+        let bought = self.characteristics[characteristic as usize];
+        (characteristic.base_value().inner() + bought).into()
+    }
+
+    /// Returns the meters the movement will have, including the base value.
+    pub fn movement(&self, movement: MovementType) -> Meters {
+        let bought = self.movement[movement as usize];
+        (movement.base_value().inner() + bought as i64).into()
+    }
+
+    /// Creates the character, with every characteristic at full.
+    pub fn build(&self) -> Character {
+        let c = |characteristic| {
+            let value = self.value(characteristic).inner();
+            CharacteristicValue::from((value, value))
+        };
+        let m = |movement| {
+            let value = self.movement(movement).inner() as u64;
+            CharacteristicValue::from((value, value))
+        };
+
+        Character {
+            characteristics: Characteristics {
+                strength: c(Characteristic::Strength),
+                dexterity: c(Characteristic::Dexterity),
+                constitution: c(Characteristic::Constitution),
+                intelligence: c(Characteristic::Intelligence),
+                ego: c(Characteristic::Ego),
+                presence: c(Characteristic::Presence),
+                offensive_combat_value: c(Characteristic::OffensiveCombatValue),
+                defensive_combat_value: c(Characteristic::DefensiveCombatValue),
+                offensive_mental_combat_value: c(Characteristic::OffensiveMentalCombatValue),
+                defensive_mental_comat_value: c(Characteristic::DefensiveMentalCombatValue),
+                speed: c(Characteristic::Speed),
+                physical_defense: c(Characteristic::PhysicalDefense),
+                energy_defense: c(Characteristic::EnergyDefense),
+                recovery: c(Characteristic::Recovery),
+                endurance: c(Characteristic::Endurance),
+                body: c(Characteristic::Body),
+                stun: c(Characteristic::Stun),
+            },
+            movement_characteristics: MovementCharacteristics {
+                running: m(MovementType::Running),
+                swimming: m(MovementType::Swimming),
+                leaping: m(MovementType::Leaping),
+                flight: m(MovementType::Flight),
+            },
+            powers: self.powers,
+        }
+    }
+}
+
+/// Returns the cost, or the most points there are if it's too large to count.
+/// Everything bought fit in the budget, so only costs that were refused can't be counted.
+fn counted(cost: Option<CharacterPoints>) -> CharacterPoints {
+    cost.unwrap_or(u64::MAX.into())
+}
+
+/// Returns the total after buying `amount` more and the cost of the extra.
+/// Returns `None` if the total is above `most` or the cost is too large to count.
+fn raise<F>(bought: u64, amount: u64, most: u64, cost_of: F) -> Option<(u64, u64)>
+where
+    F: Fn(u64) -> Option<CharacterPoints>,
+{
+    let total = bought.checked_add(amount).filter(|total| *total <= most)?;
+    let added = cost_of(total)?.inner() - cost_of(bought)?.inner();
+    Some((total, added))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_at_base_values() {
+        let b = CharacterBuilder::new(100.into());
+        assert_eq!(CharacterPoints::from(0), b.spent());
+        assert_eq!(0, b.breakdown().count());
+        for c in Characteristic::ALL {
+            assert_eq!(c.base_value(), b.value(c));
+        }
+
+        let c = b.build();
+        assert_eq!(
+            Characteristic::Strength.base_value(),
+            c.characteristic(Characteristic::Strength)
+        );
+        assert_eq!(
+            MovementType::Running.base_value(),
+            c.movement(MovementType::Running)
+        );
+    }
+
+    #[test]
+    fn buying_spends_points() {
+        let mut b = CharacterBuilder::new(50.into());
+        assert_eq!(Ok(10.into()), b.buy(Characteristic::Strength, 10.into()));
+        assert_eq!(Ok(6.into()), b.buy(Characteristic::Dexterity, 3.into()));
+        assert_eq!(
+            Ok(4.into()),
+            b.buy_movement(MovementType::Running, 4.into())
+        );
+
+        assert_eq!(CharacterPoints::from(20), b.spent());
+        assert_eq!(CharacterPoints::from(30), b.remaining());
+        assert_eq!(
+            vec![
+                Purchase::Characteristic {
+                    characteristic: Characteristic::Strength,
+                    bought: 10.into(),
+                    cost: 10.into(),
+                },
+                Purchase::Characteristic {
+                    characteristic: Characteristic::Dexterity,
+                    bought: 3.into(),
+                    cost: 6.into(),
+                },
+                Purchase::Movement {
+                    movement: MovementType::Running,
+                    bought: 4.into(),
+                    cost: 4.into(),
+                },
+            ],
+            b.breakdown().collect::<Vec<_>>()
+        );
+
+        let c = b.build();
+        assert_eq!(
+            PositiveNumber::from(20),
+            c.characteristic(Characteristic::Strength)
+        );
+        assert_eq!(Meters::from(16), c.movement(MovementType::Running));
+    }
+
+    #[test]
+    fn fractional_costs_use_total_bought() {
+        let mut b = CharacterBuilder::new(10.into());
+        assert_eq!(Ok(1.into()), b.buy(Characteristic::Endurance, 3.into()));
+        assert_eq!(Ok(0.into()), b.buy(Characteristic::Endurance, 2.into()));
+        assert_eq!(Ok(1.into()), b.buy(Characteristic::Endurance, 3.into()));
+        assert_eq!(CharacterPoints::from(2), b.spent());
+        assert_eq!(PositiveNumber::from(28), b.value(Characteristic::Endurance));
+
+        assert_eq!(
+            Ok(1.into()),
+            b.buy_movement(MovementType::Swimming, 2.into())
+        );
+    }
+
+    #[test]
+    fn over_budget_is_refused() {
+        let mut b = CharacterBuilder::new(15.into());
+        b.buy(Characteristic::Strength, 10.into()).unwrap();
+
+        assert_eq!(
+            Err(BuildError::OverBudget {
+                cost: 10.into(),
+                remaining: 5.into(),
+            }),
+            b.buy(Characteristic::Speed, 1.into())
+        );
+        // Refused purchases change nothing.
+        assert_eq!(
+            Characteristic::Speed.base_value(),
+            b.value(Characteristic::Speed)
+        );
+        assert_eq!(CharacterPoints::from(10), b.spent());
+    }
+
+    #[test]
+    fn huge_purchases_are_over_budget() {
+        let mut b = CharacterBuilder::new(u64::MAX.into());
+        let over_budget = Err(BuildError::OverBudget {
+            cost: u64::MAX.into(),
+            remaining: u64::MAX.into(),
+        });
+        assert_eq!(over_budget, b.buy(Characteristic::Speed, u64::MAX.into()));
+
+        b.buy(Characteristic::Strength, 1.into()).unwrap();
+        let over_budget = Err(BuildError::OverBudget {
+            cost: u64::MAX.into(),
+            remaining: (u64::MAX - 1).into(),
+        });
+        assert_eq!(
+            over_budget,
+            b.buy(Characteristic::Strength, u64::MAX.into())
+        );
+        assert_eq!(PositiveNumber::from(11), b.value(Characteristic::Strength));
+
+        let most = i64::MAX - MovementType::Running.base_value().inner();
+        assert_eq!(
+            Ok((most as u64).into()),
+            b.buy_movement(MovementType::Running, most.into())
+        );
+        assert_eq!(
+            Err(BuildError::OverBudget {
+                cost: u64::MAX.into(),
+                remaining: (u64::MAX - 1 - most as u64).into(),
+            }),
+            b.buy_movement(MovementType::Running, 1.into())
+        );
+        assert_eq!(
+            Meters::from(i64::MAX),
+            b.build().movement(MovementType::Running)
+        );
+    }
+
+    #[test]
+    fn items_are_indexed_in_declaration_order() {
+        for (i, c) in Characteristic::ALL.iter().enumerate() {
+            assert_eq!(i, *c as usize);
+        }
+        for (i, m) in MovementType::ALL.iter().enumerate() {
+            assert_eq!(i, *m as usize);
+        }
+    }
+
+    #[test]
+    fn empty_purchases_are_refused() {
+        let mut b = CharacterBuilder::new(15.into());
+        assert_eq!(
+            Err(BuildError::NothingPurchased),
+            b.buy(Characteristic::Body, 0.into())
+        );
+        assert_eq!(
+            Err(BuildError::NothingPurchased),
+            b.buy_movement(MovementType::Leaping, (-2).into())
+        );
+    }
+
+    #[test]
+    fn powers_add_movement_and_defenses() {
+        use crate::hero_system::{
+            attack::Defenses,
+            powers::{Effect, Focus, Limitation},
+        };
+
+        let flight = Power::new(Effect::Movement {
+            movement: MovementType::Flight,
+            meters: 20.into(),
+        });
+        let running = Power::new(Effect::Movement {
+            movement: MovementType::Running,
+            meters: 6.into(),
+        });
+        let armor = Power::new(Effect::Armor {
+            physical: 8.into(),
+            energy: 4.into(),
+        })
+        .with_limitation(Limitation::Focus(Focus::ObviousInaccessible))
+        .unwrap();
+
+        let mut b = CharacterBuilder::new(30.into());
+        assert_eq!(Ok(10.into()), b.buy_power(flight));
+        assert_eq!(Ok(6.into()), b.buy_power(running));
+        assert_eq!(Ok(12.into()), b.buy_power(armor));
+        assert_eq!(CharacterPoints::from(28), b.spent());
+        assert_eq!(
+            Some(Purchase::Power {
+                power: armor,
+                cost: 12.into()
+            }),
+            b.breakdown().last()
+        );
+
+        let c = b.build();
+        assert_eq!(Meters::from(20), c.movement(MovementType::Flight));
+        assert_eq!(Meters::from(18), c.movement(MovementType::Running));
+
+        let defenses = Defenses::of(&c);
+        assert_eq!(PositiveNumber::from(8), defenses.resistant_physical);
+        assert_eq!(PositiveNumber::from(4), defenses.resistant_energy);
+    }
+
+    #[test]
+    fn powers_are_limited() {
+        use crate::hero_system::powers::Effect;
+
+        let power = Power::new(Effect::Movement {
+            movement: MovementType::Flight,
+            meters: 2.into(),
+        });
+        let mut b = CharacterBuilder::new(100.into());
+        for _ in 0..MAX_POWERS {
+            b.buy_power(power).unwrap();
+        }
+        assert_eq!(Err(BuildError::TooManyPowers), b.buy_power(power));
+
+        let nothing = Power::new(Effect::Movement {
+            movement: MovementType::Flight,
+            meters: 0.into(),
+        });
+        assert_eq!(
+            Err(BuildError::NothingPurchased),
+            CharacterBuilder::new(10.into()).buy_power(nothing)
+        );
+    }
+}
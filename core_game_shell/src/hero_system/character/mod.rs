@@ -1,10 +1,22 @@
 use core::ops::Rem;
 
 use super::{
-    characteristics::{CharacterPoints, Characteristic},
-    Kilograms, PositiveNumber, Quantity, D6,
+    characteristics::{CharacterPoints, Characteristic, MovementType},
+    powers::Power,
+    Kilograms, Meters, PositiveNumber, Quantity, D6,
 };
 
+/// The most powers a character can have.
+pub const MAX_POWERS: usize = 8;
+
+mod builder;
+#[cfg(any(test, feature = "text"))]
+mod text;
+
+pub use builder::*;
+#[cfg(any(test, feature = "text"))]
+pub use text::*;
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum UseResult {
     Success,
@@ -15,11 +27,45 @@ pub enum UseResult {
     },
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Character {
     characteristics: Characteristics,
     movement_characteristics: MovementCharacteristics,
+    powers: [Option<Power>; MAX_POWERS],
 }
 impl Character {
+    /// Returns the current value of the characteristic.
+    pub fn characteristic(&self, characteristic: Characteristic) -> PositiveNumber {
+        self.characteristics.get(characteristic).active_value
+    }
+
+    /// Returns the full value of the characteristic, before anything was spent.
+    pub fn max_characteristic(&self, characteristic: Characteristic) -> PositiveNumber {
+        self.characteristics
+            .get(characteristic)
+            .points
+            .inner()
+            .into()
+    }
+
+    /// Regains endurance, up to the full value.
+    pub fn recover_endurance(&mut self, amount: PositiveNumber) {
+        let endurance = &mut self.characteristics.endurance;
+        let recovered = endurance.active_value.inner() + amount.inner();
+        endurance.active_value = recovered.min(endurance.points.inner()).into();
+    }
+
+    /// Returns the current meters the character can move, including any from powers.
+    pub fn movement(&self, movement: MovementType) -> Meters {
+        let value = self.movement_characteristics.get(movement);
+        let from_powers: i64 = self.powers().map(|p| p.movement(movement).inner()).sum();
+        (value.active_value.inner() as i64 + from_powers).into()
+    }
+
+    pub fn powers(&self) -> impl Iterator<Item = &Power> + '_ {
+        self.powers.iter().flatten()
+    }
+
     /// Returns the lifting capacity of the character.
     pub fn lifting_capacity(&self) -> Kilograms {
         let value = self.characteristics.strength.points.inner() * 20;
@@ -51,6 +97,7 @@ impl Character {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 struct CharacteristicValue {
     points: CharacterPoints,
     active_value: PositiveNumber,
@@ -64,6 +111,7 @@ impl From<(u64, u64)> for CharacteristicValue {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 struct Characteristics {
     strength: CharacteristicValue,
     dexterity: CharacteristicValue,
@@ -84,10 +132,78 @@ struct Characteristics {
     stun: CharacteristicValue,
 }
 
+impl Characteristics {
+    fn get(&self, characteristic: Characteristic) -> &CharacteristicValue {
+        match characteristic {
+            Characteristic::Strength => &self.strength,
+            Characteristic::Dexterity => &self.dexterity,
+            Characteristic::Constitution => &self.constitution,
+            Characteristic::Intelligence => &self.intelligence,
+            Characteristic::Ego => &self.ego,
+            Characteristic::Presence => &self.presence,
+            Characteristic::OffensiveCombatValue => &self.offensive_combat_value,
+            Characteristic::DefensiveCombatValue => &self.defensive_combat_value,
+            Characteristic::OffensiveMentalCombatValue => &self.offensive_mental_combat_value,
+            Characteristic::DefensiveMentalCombatValue => &self.defensive_mental_comat_value,
+            Characteristic::Speed => &self.speed,
+            Characteristic::PhysicalDefense => &self.physical_defense,
+            Characteristic::EnergyDefense => &self.energy_defense,
+            Characteristic::Recovery => &self.recovery,
+            Characteristic::Endurance => &self.endurance,
+            Characteristic::Body => &self.body,
+            Characteristic::Stun => &self.stun,
+        }
+    }
+
+    fn get_mut(&mut self, characteristic: Characteristic) -> &mut CharacteristicValue {
+        match characteristic {
+            Characteristic::Strength => &mut self.strength,
+            Characteristic::Dexterity => &mut self.dexterity,
+            Characteristic::Constitution => &mut self.constitution,
+            Characteristic::Intelligence => &mut self.intelligence,
+            Characteristic::Ego => &mut self.ego,
+            Characteristic::Presence => &mut self.presence,
+            Characteristic::OffensiveCombatValue => &mut self.offensive_combat_value,
+            Characteristic::DefensiveCombatValue => &mut self.defensive_combat_value,
+            Characteristic::OffensiveMentalCombatValue => &mut self.offensive_mental_combat_value,
+            Characteristic::DefensiveMentalCombatValue => &mut self.defensive_mental_comat_value,
+            Characteristic::Speed => &mut self.speed,
+            Characteristic::PhysicalDefense => &mut self.physical_defense,
+            Characteristic::EnergyDefense => &mut self.energy_defense,
+            Characteristic::Recovery => &mut self.recovery,
+            Characteristic::Endurance => &mut self.endurance,
+            Characteristic::Body => &mut self.body,
+            Characteristic::Stun => &mut self.stun,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 struct MovementCharacteristics {
     running: CharacteristicValue,
     swimming: CharacteristicValue,
     leaping: CharacteristicValue,
+    flight: CharacteristicValue,
+}
+
+impl MovementCharacteristics {
+    fn get(&self, movement: MovementType) -> &CharacteristicValue {
+        match movement {
+            MovementType::Running => &self.running,
+            MovementType::Swimming => &self.swimming,
+            MovementType::Leaping => &self.leaping,
+            MovementType::Flight => &self.flight,
+        }
+    }
+
+    fn get_mut(&mut self, movement: MovementType) -> &mut CharacteristicValue {
+        match movement {
+            MovementType::Running => &mut self.running,
+            MovementType::Swimming => &mut self.swimming,
+            MovementType::Leaping => &mut self.leaping,
+            MovementType::Flight => &mut self.flight,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -119,7 +235,9 @@ mod tests {
                 running: (12, 10).into(),
                 swimming: (4, 4).into(),
                 leaping: (4, 4).into(),
+                flight: (0, 0).into(),
             },
+            powers: [None; MAX_POWERS],
         }
     }
 
@@ -199,4 +317,26 @@ mod tests {
         };
         assert_eq!(expected, c.use_strength(value));
     }
+
+    #[test]
+    fn recover_endurance_is_capped() {
+        let mut c = character();
+        c.use_strength(30.into());
+        assert_eq!(
+            PositiveNumber::from(7),
+            c.characteristic(Characteristic::Endurance)
+        );
+
+        c.recover_endurance(2.into());
+        assert_eq!(
+            PositiveNumber::from(9),
+            c.characteristic(Characteristic::Endurance)
+        );
+
+        c.recover_endurance(5.into());
+        assert_eq!(
+            c.max_characteristic(Characteristic::Endurance),
+            c.characteristic(Characteristic::Endurance)
+        );
+    }
 }
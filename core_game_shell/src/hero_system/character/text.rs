@@ -0,0 +1,552 @@
+use super::{Character, CharacterBuilder, CharacteristicValue, MAX_POWERS};
+use crate::hero_system::{
+    attack::DefenseType,
+    characteristics::{Characteristic, MovementType},
+    dice::DamageDice,
+    powers::{Advantage, Effect, Focus, Limitation, ModifierValue, Power},
+};
+use std::{
+    fmt::{self, Write},
+    string::String,
+};
+
+/// The name powers are listed under.
+const POWER: &str = "Power";
+
+/// Why a line of a character could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextErrorKind {
+    /// The line isn't `NAME: VALUE`.
+    Syntax,
+    UnknownName(String),
+    Duplicate(String),
+    /// The value isn't a whole number, or a movement is missing its `m`.
+    InvalidNumber(String),
+    /// The active value is greater than the full value.
+    ActiveAboveFull(String),
+    /// The characteristic or movement never appeared.
+    Missing(&'static str),
+    /// The effect or a modifier of a power couldn't be read.
+    InvalidPower(String),
+    TooManyPowers,
+    TooManyModifiers,
+}
+
+/// An error reading a character, along with the line it happened on, starting at 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextError {
+    line: usize,
+    kind: TextErrorKind,
+}
+impl TextError {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn kind(&self) -> &TextErrorKind {
+        &self.kind
+    }
+}
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            TextErrorKind::Syntax => write!(f, "expected `NAME: VALUE`"),
+            TextErrorKind::UnknownName(name) => write!(f, "unknown name `{}`", name),
+            TextErrorKind::Duplicate(name) => write!(f, "`{}` was already given", name),
+            TextErrorKind::InvalidNumber(value) => write!(f, "invalid value `{}`", value),
+            TextErrorKind::ActiveAboveFull(name) => {
+                write!(f, "active value of `{}` is above its full value", name)
+            }
+            TextErrorKind::Missing(name) => write!(f, "`{}` is missing", name),
+            TextErrorKind::InvalidPower(power) => write!(f, "invalid power `{}`", power),
+            TextErrorKind::TooManyPowers => write!(f, "more than {} powers", MAX_POWERS),
+            TextErrorKind::TooManyModifiers => write!(f, "power has too many modifiers"),
+        }
+    }
+}
+impl std::error::Error for TextError {}
+
+impl Character {
+    /// Writes the character as text that `from_text` reads back.
+    ///
+    /// Each line is `NAME: VALUE`, where the value is the full value of the characteristic.
+    /// When the active value differs, such as after spending END, it follows in brackets: `END: 20 (14)`.
+    /// Movement is written in meters: `Running: 12m`.
+    /// Powers follow as `Power: EFFECT, MODIFIER, ...`, such as `Power: Blast 8d6 ED, Armor Piercing, OAF`.
+    pub fn to_text(self) -> String {
+        let mut text = String::new();
+        for characteristic in Characteristic::ALL {
+            let value = self.characteristics.get(characteristic);
+            write_line(&mut text, characteristic.abbreviation(), value, "");
+        }
+        for movement in MovementType::ALL {
+            let value = self.movement_characteristics.get(movement);
+            write_line(&mut text, movement.name(), value, "m");
+        }
+        for power in self.powers() {
+            write_power(&mut text, power);
+        }
+
+        text
+    }
+
+    /// Reads a character written by `to_text`.
+    /// Lines may be in any order, though powers keep the order they are listed in.
+    /// Blank lines and anything after a `#` are ignored.
+    /// Every characteristic and movement must be given exactly once.
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut characteristics = [None; Characteristic::ALL.len()];
+        let mut movements = [None; MovementType::ALL.len()];
+        let mut powers = [None; MAX_POWERS];
+
+        let mut last_line = 0;
+        for (i, line) in text.lines().enumerate() {
+            last_line = i + 1;
+            let error = |kind| TextError {
+                line: last_line,
+                kind,
+            };
+
+            let line = match line.split_once('#') {
+                Some((line, _comment)) => line,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line.split_once(':').ok_or(error(TextErrorKind::Syntax))?;
+            let (name, value) = (name.trim(), value.trim());
+
+            if name.eq_ignore_ascii_case(POWER) {
+                let slot = powers
+                    .iter_mut()
+                    .find(|p: &&mut Option<Power>| p.is_none())
+                    .ok_or_else(|| error(TextErrorKind::TooManyPowers))?;
+                *slot = Some(parse_power(value).map_err(error)?);
+                continue;
+            }
+
+            let (slot, unit) = if let Some(c) = Characteristic::ALL
+                .iter()
+                .position(|c| c.abbreviation().eq_ignore_ascii_case(name))
+            {
+                (&mut characteristics[c], "")
+            } else if let Some(m) = MovementType::ALL
+                .iter()
+                .position(|m| m.name().eq_ignore_ascii_case(name))
+            {
+                (&mut movements[m], "m")
+            } else {
+                return Err(error(TextErrorKind::UnknownName(name.into())));
+            };
+
+            if slot.is_some() {
+                return Err(error(TextErrorKind::Duplicate(name.into())));
+            }
+            let value = parse_value(value, unit)
+                .ok_or_else(|| error(TextErrorKind::InvalidNumber(value.into())))?;
+            if value.active_value.inner() > value.points.inner() {
+                return Err(error(TextErrorKind::ActiveAboveFull(name.into())));
+            }
+            *slot = Some(value);
+        }
+
+        // Missing entries are reported just past the end, where they would be added.
+        let missing = |name| TextError {
+            line: last_line + 1,
+            kind: TextErrorKind::Missing(name),
+        };
+
+        let mut character = CharacterBuilder::new(0.into()).build();
+        for (characteristic, value) in Characteristic::ALL.into_iter().zip(characteristics) {
+            *character.characteristics.get_mut(characteristic) =
+                value.ok_or_else(|| missing(characteristic.abbreviation()))?;
+        }
+        for (movement, value) in MovementType::ALL.into_iter().zip(movements) {
+            *character.movement_characteristics.get_mut(movement) =
+                value.ok_or_else(|| missing(movement.name()))?;
+        }
+        character.powers = powers;
+
+        Ok(character)
+    }
+}
+
+fn write_line(text: &mut String, name: &str, value: &CharacteristicValue, unit: &str) {
+    let full = value.points.inner();
+    let active = value.active_value.inner();
+    // Writing to a string never fails.
+    let _ = if full == active {
+        writeln!(text, "{}: {}{}", name, full, unit)
+    } else {
+        writeln!(text, "{}: {}{} ({}{})", name, full, unit, active, unit)
+    };
+}
+
+fn write_power(text: &mut String, power: &Power) {
+    let _ = write!(text, "{}: ", POWER);
+    let _ = match power.effect() {
+        Effect::Blast { dice, defense } => {
+            write!(text, "Blast {} {}", dice_text(dice), defense_text(defense))
+        }
+        Effect::KillingAttack { dice, defense } => write!(
+            text,
+            "Killing Attack {} {}",
+            dice_text(dice),
+            defense_text(defense)
+        ),
+        Effect::Movement { movement, meters } => {
+            write!(text, "{} {}m", movement.name(), meters.inner())
+        }
+        Effect::Armor { physical, energy } => {
+            write!(text, "Armor {}/{}", physical.inner(), energy.inner())
+        }
+    };
+
+    for advantage in power.advantages() {
+        let _ = match advantage {
+            Advantage::ArmorPiercing => write!(text, ", Armor Piercing"),
+            Advantage::Other(value) => write!(text, ", +{}", quarters_text(value)),
+        };
+    }
+    for limitation in power.limitations() {
+        let _ = match limitation {
+            Limitation::Focus(focus) => write!(text, ", {}", focus_text(focus)),
+            Limitation::Other(value) => write!(text, ", -{}", quarters_text(value)),
+        };
+    }
+    text.push('\n');
+}
+
+/// Writes dice such as `2d6` or `2½d6`.
+fn dice_text(dice: DamageDice) -> String {
+    let half = if dice.half_die { "½" } else { "" };
+    format!("{}{}d6", dice.dice.number.inner(), half)
+}
+
+fn defense_text(defense: DefenseType) -> &'static str {
+    match defense {
+        DefenseType::Physical => "PD",
+        DefenseType::Energy => "ED",
+    }
+}
+
+fn focus_text(focus: Focus) -> &'static str {
+    match focus {
+        Focus::ObviousAccessible => "OAF",
+        Focus::ObviousInaccessible => "OIF",
+        Focus::InobviousAccessible => "IAF",
+        Focus::InobviousInaccessible => "IIF",
+    }
+}
+
+/// Writes a modifier the way the rules do, such as `1/2` or `1 1/4`.
+fn quarters_text(value: ModifierValue) -> String {
+    let (whole, quarters) = (value.quarters() / 4, value.quarters() % 4);
+    let fraction = ["", "1/4", "1/2", "3/4"][quarters as usize];
+    match (whole, quarters) {
+        (_, 0) => format!("{}", whole),
+        (0, _) => fraction.into(),
+        _ => format!("{} {}", whole, fraction),
+    }
+}
+
+fn parse_quarters(text: &str) -> Option<ModifierValue> {
+    let mut quarters: u64 = 0;
+    for part in text.split_whitespace() {
+        let part = match part {
+            "1/4" => 1,
+            "1/2" => 2,
+            "3/4" => 3,
+            whole => whole.parse::<u64>().ok()?.checked_mul(4)?,
+        };
+        quarters = quarters.checked_add(part)?;
+    }
+
+    Some(ModifierValue::from_quarters(quarters))
+}
+
+fn parse_power(text: &str) -> Result<Power, TextErrorKind> {
+    let invalid = |part: &str| TextErrorKind::InvalidPower(part.into());
+
+    let mut parts = text.split(',').map(str::trim);
+    let effect = parts.next().unwrap_or_default();
+    let mut power = Power::new(parse_effect(effect).ok_or_else(|| invalid(effect))?);
+
+    for part in parts {
+        let modified = if part.eq_ignore_ascii_case("Armor Piercing") {
+            power.with_advantage(Advantage::ArmorPiercing)
+        } else if let Some(value) = part.strip_prefix('+') {
+            let value = parse_quarters(value).ok_or_else(|| invalid(part))?;
+            power.with_advantage(Advantage::Other(value))
+        } else if let Some(value) = part.strip_prefix('-') {
+            let value = parse_quarters(value).ok_or_else(|| invalid(part))?;
+            power.with_limitation(Limitation::Other(value))
+        } else {
+            let focus = [
+                Focus::ObviousAccessible,
+                Focus::ObviousInaccessible,
+                Focus::InobviousAccessible,
+                Focus::InobviousInaccessible,
+            ]
+            .into_iter()
+            .find(|f| focus_text(*f).eq_ignore_ascii_case(part))
+            .ok_or_else(|| invalid(part))?;
+            power.with_limitation(Limitation::Focus(focus))
+        };
+        power = modified.map_err(|_| TextErrorKind::TooManyModifiers)?;
+    }
+
+    Ok(power)
+}
+
+fn parse_effect(text: &str) -> Option<Effect> {
+    let (name, value) = text.rsplit_once(' ')?;
+    let (name, value) = (name.trim(), value.trim());
+
+    if let Some(movement) = MovementType::ALL
+        .into_iter()
+        .find(|m| m.name().eq_ignore_ascii_case(name))
+    {
+        let meters = value.strip_suffix('m')?.parse::<i64>().ok()?;
+        return Some(Effect::Movement {
+            movement,
+            meters: meters.into(),
+        });
+    }
+
+    if name.eq_ignore_ascii_case("Armor") {
+        let (physical, energy) = value.split_once('/')?;
+        return Some(Effect::Armor {
+            physical: physical.parse::<u64>().ok()?.into(),
+            energy: energy.parse::<u64>().ok()?.into(),
+        });
+    }
+
+    let defense = match value {
+        "PD" => DefenseType::Physical,
+        "ED" => DefenseType::Energy,
+        _ => return None,
+    };
+    let (name, dice) = name.rsplit_once(' ')?;
+    let dice = dice.strip_suffix("d6")?;
+    let (number, half_die) = match dice.strip_suffix('½') {
+        Some(number) => (number, true),
+        None => (dice, false),
+    };
+    let dice = DamageDice {
+        half_die,
+        ..number.parse::<u64>().ok()?.into()
+    };
+
+    match name.trim() {
+        n if n.eq_ignore_ascii_case("Blast") => Some(Effect::Blast { dice, defense }),
+        n if n.eq_ignore_ascii_case("Killing Attack") => {
+            Some(Effect::KillingAttack { dice, defense })
+        }
+        _ => None,
+    }
+}
+
+/// Parses `FULL` or `FULL (ACTIVE)`, where each number has the unit after it.
+fn parse_value(value: &str, unit: &str) -> Option<CharacteristicValue> {
+    let number = |s: &str| -> Option<u64> { s.trim().strip_suffix(unit)?.trim().parse().ok() };
+
+    match value.split_once('(') {
+        Some((full, active)) => {
+            let full = number(full)?;
+            let active = number(active.strip_suffix(')')?)?;
+            Some((full, active).into())
+        }
+        None => {
+            let full = number(value)?;
+            Some((full, full).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hero_system::PositiveNumber;
+
+    fn character() -> Character {
+        let mut b = CharacterBuilder::new(100.into());
+        b.buy(Characteristic::Strength, 10.into()).unwrap();
+        b.buy(Characteristic::Endurance, 8.into()).unwrap();
+        b.buy_movement(MovementType::Running, 4.into()).unwrap();
+        b.buy_power(
+            Power::new(Effect::Blast {
+                dice: DamageDice {
+                    half_die: true,
+                    ..6.into()
+                },
+                defense: DefenseType::Energy,
+            })
+            .with_advantage(Advantage::ArmorPiercing)
+            .unwrap()
+            .with_advantage(Advantage::Other(ModifierValue::from_quarters(5)))
+            .unwrap()
+            .with_limitation(Limitation::Focus(Focus::ObviousAccessible))
+            .unwrap(),
+        )
+        .unwrap();
+        b.buy_power(Power::new(Effect::Movement {
+            movement: MovementType::Flight,
+            meters: 10.into(),
+        }))
+        .unwrap();
+
+        let mut c = b.build();
+        c.use_strength(30.into());
+        c
+    }
+
+    #[test]
+    fn round_trips() {
+        let c = character();
+        let text = c.to_text();
+        assert!(text.contains("STR: 20\n"));
+        assert!(text.contains("END: 28 (25)\n"));
+        assert!(text.contains("Running: 16m\n"));
+        assert!(text.contains("Flight: 0m\n"));
+        assert!(text.contains("Power: Blast 6½d6 ED, Armor Piercing, +1 1/4, OAF\n"));
+        assert!(text.contains("Power: Flight 10m\n"));
+
+        assert_eq!(Ok(c), Character::from_text(&text));
+    }
+
+    #[test]
+    fn ignores_comments_blank_lines_and_order() {
+        let c = character();
+        let (powers, mut lines): (Vec<_>, Vec<_>) = c
+            .to_text()
+            .lines()
+            .map(String::from)
+            .partition(|l| l.starts_with(POWER));
+        lines.reverse();
+        lines.extend(powers);
+        let text = format!("# Roster entry\n\n{}  # inline\n", lines.join("\n\n"));
+
+        assert_eq!(Ok(c), Character::from_text(&text));
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let text = character().to_text();
+        let with_line = |line: usize, replacement: &str| {
+            let mut lines: Vec<_> = text.lines().collect();
+            lines[line - 1] = replacement;
+            lines.join("\n")
+        };
+
+        let error = |text: &str| Character::from_text(text).unwrap_err();
+        assert_eq!(
+            TextError {
+                line: 2,
+                kind: TextErrorKind::Syntax
+            },
+            error(&with_line(2, "DEX 10"))
+        );
+        assert_eq!(
+            TextError {
+                line: 3,
+                kind: TextErrorKind::UnknownName("FOO".into())
+            },
+            error(&with_line(3, "FOO: 1"))
+        );
+        assert_eq!(
+            TextError {
+                line: 4,
+                kind: TextErrorKind::Duplicate("str".into())
+            },
+            error(&with_line(4, "str: 1"))
+        );
+        assert_eq!(
+            TextError {
+                line: 5,
+                kind: TextErrorKind::InvalidNumber("-1".into())
+            },
+            error(&with_line(5, "EGO: -1"))
+        );
+        assert_eq!(
+            TextError {
+                line: 18,
+                kind: TextErrorKind::InvalidNumber("12".into())
+            },
+            error(&with_line(18, "Running: 12"))
+        );
+        assert_eq!(
+            TextError {
+                line: 6,
+                kind: TextErrorKind::ActiveAboveFull("PRE".into())
+            },
+            error(&with_line(6, "PRE: 10 (11)"))
+        );
+        assert_eq!(
+            "line 2: expected `NAME: VALUE`",
+            error(&with_line(2, "DEX 10")).to_string()
+        );
+    }
+
+    #[test]
+    fn invalid_powers_are_reported() {
+        let error = |power: &str| {
+            let text = format!("{}Power: {}\n", character().to_text(), power);
+            Character::from_text(&text).unwrap_err()
+        };
+
+        let invalid = |part: &str| TextError {
+            line: 24,
+            kind: TextErrorKind::InvalidPower(part.into()),
+        };
+        assert_eq!(invalid("Blast 6d6"), error("Blast 6d6"));
+        assert_eq!(invalid("Blast 6d8 PD"), error("Blast 6d8 PD"));
+        assert_eq!(invalid("Gliding 5m"), error("Gliding 5m"));
+        assert_eq!(invalid("Armor 3"), error("Armor 3"));
+        assert_eq!(invalid("XYZ"), error("Flight 5m, XYZ"));
+        assert_eq!(invalid("+1/3"), error("Flight 5m, +1/3"));
+        let huge = format!("+{} 1/4", u64::MAX / 4 + 1);
+        assert_eq!(invalid(&huge), error(&format!("Flight 5m, {}", huge)));
+        assert_eq!(
+            TextError {
+                line: 24,
+                kind: TextErrorKind::TooManyModifiers
+            },
+            error("Armor 1/1, IIF, IIF, IIF, IIF, IIF")
+        );
+    }
+
+    #[test]
+    fn missing_entries_are_reported_after_the_end() {
+        let text: Vec<_> = character()
+            .to_text()
+            .lines()
+            .filter(|l| !l.starts_with("BODY"))
+            .map(String::from)
+            .collect();
+
+        assert_eq!(
+            TextError {
+                line: 23,
+                kind: TextErrorKind::Missing("BODY")
+            },
+            Character::from_text(&text.join("\n")).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn active_value_is_kept_separate() {
+        let c = Character::from_text(&character().to_text().replace("(25)", "(3)")).unwrap();
+        assert_eq!(
+            PositiveNumber::from(3),
+            c.characteristic(Characteristic::Endurance)
+        );
+        assert_eq!(
+            PositiveNumber::from(28),
+            c.max_characteristic(Characteristic::Endurance)
+        );
+    }
+}
@@ -27,6 +27,49 @@ pub enum Characteristic {
     Stun,
 }
 impl Characteristic {
+    pub const ALL: [Characteristic; 17] = [
+        Characteristic::Strength,
+        Characteristic::Dexterity,
+        Characteristic::Constitution,
+        Characteristic::Intelligence,
+        Characteristic::Ego,
+        Characteristic::Presence,
+        Characteristic::OffensiveCombatValue,
+        Characteristic::DefensiveCombatValue,
+        Characteristic::OffensiveMentalCombatValue,
+        Characteristic::DefensiveMentalCombatValue,
+        Characteristic::Speed,
+        Characteristic::PhysicalDefense,
+        Characteristic::EnergyDefense,
+        Characteristic::Recovery,
+        Characteristic::Endurance,
+        Characteristic::Body,
+        Characteristic::Stun,
+    ];
+
+    /// Returns the short name the rules use, such as STR.
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            Characteristic::Strength => "STR",
+            Characteristic::Dexterity => "DEX",
+            Characteristic::Constitution => "CON",
+            Characteristic::Intelligence => "INT",
+            Characteristic::Ego => "EGO",
+            Characteristic::Presence => "PRE",
+            Characteristic::OffensiveCombatValue => "OCV",
+            Characteristic::DefensiveCombatValue => "DCV",
+            Characteristic::OffensiveMentalCombatValue => "OMCV",
+            Characteristic::DefensiveMentalCombatValue => "DMCV",
+            Characteristic::Speed => "SPD",
+            Characteristic::PhysicalDefense => "PD",
+            Characteristic::EnergyDefense => "ED",
+            Characteristic::Recovery => "REC",
+            Characteristic::Endurance => "END",
+            Characteristic::Body => "BODY",
+            Characteristic::Stun => "STUN",
+        }
+    }
+
     pub fn base_value(&self) -> PositiveNumber {
         match self {
             Characteristic::Strength => 10,
@@ -99,6 +142,27 @@ impl CharacteristicCost {
     pub fn points_gained(&self) -> CharacterPoints {
         self.points_gained
     }
+
+    /// Returns the cost of raising the characteristic by `gained`, or `None` if it's too large to count.
+    pub fn cost_of(&self, gained: PositiveNumber) -> Option<CharacterPoints> {
+        ratio_cost(
+            gained.inner(),
+            self.character_points.inner(),
+            self.points_gained.inner(),
+        )
+        .map(CharacterPoints::from)
+    }
+}
+
+/// Returns `gained * character_points / per`, rounded to the nearest point.
+/// Halves round down, in the character's favor. Returns `None` if the cost is too large to count.
+pub(crate) fn ratio_cost(gained: u64, character_points: u64, per: u64) -> Option<u64> {
+    let doubled = gained.checked_mul(character_points)?.checked_mul(2)?;
+    if doubled < per {
+        return Some(0);
+    }
+
+    Some((doubled - per).div_ceil(per.checked_mul(2)?))
 }
 impl From<(u64, u64)> for CharacteristicCost {
     fn from((character_points, points_gained): (u64, u64)) -> Self {
@@ -112,4 +176,40 @@ impl From<(u64, u64)> for CharacteristicCost {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn cost_of_whole_ratio() {
+        assert_eq!(
+            CharacterPoints::from(6),
+            Characteristic::Dexterity.cost().cost_of(3.into()).unwrap()
+        );
+        assert_eq!(
+            CharacterPoints::from(20),
+            Characteristic::Speed.cost().cost_of(2.into()).unwrap()
+        );
+    }
+
+    #[test]
+    fn cost_of_fractional_ratio_rounds_to_nearest() {
+        let endurance = Characteristic::Endurance.cost();
+        let costs: Vec<_> = (0..=8)
+            .map(|n| endurance.cost_of(n.into()).unwrap().inner())
+            .collect();
+        assert_eq!(vec![0, 0, 0, 1, 1, 1, 1, 1, 2], costs);
+    }
+
+    #[test]
+    fn cost_of_halves_round_down() {
+        let stun = Characteristic::Stun.cost();
+        assert_eq!(Some(CharacterPoints::from(0)), stun.cost_of(1.into()));
+        assert_eq!(Some(CharacterPoints::from(1)), stun.cost_of(3.into()));
+        assert_eq!(Some(CharacterPoints::from(2)), stun.cost_of(4.into()));
+    }
+
+    #[test]
+    fn cost_of_huge_amounts_is_none() {
+        let speed = Characteristic::Speed.cost();
+        assert_eq!(None, speed.cost_of(u64::MAX.into()));
+        assert_eq!(None, ratio_cost(u64::MAX, 1, 1));
+    }
 }
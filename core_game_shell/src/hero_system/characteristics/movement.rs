@@ -1,20 +1,38 @@
 use crate::hero_system::Meters;
 
-use super::CharacterPoints;
+use super::{ratio_cost, CharacterPoints};
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum MovementType {
     Running,
     Swimming,
     Leaping,
+    Flight,
 }
 
 impl MovementType {
+    pub const ALL: [MovementType; 4] = [
+        MovementType::Running,
+        MovementType::Swimming,
+        MovementType::Leaping,
+        MovementType::Flight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MovementType::Running => "Running",
+            MovementType::Swimming => "Swimming",
+            MovementType::Leaping => "Leaping",
+            MovementType::Flight => "Flight",
+        }
+    }
+
     pub fn base_value(&self) -> Meters {
         match self {
             MovementType::Running => 12,
             MovementType::Swimming => 4,
             MovementType::Leaping => 4,
+            MovementType::Flight => 0,
         }
         .into()
     }
@@ -24,6 +42,7 @@ impl MovementType {
             MovementType::Running => (1, 1),
             MovementType::Swimming => (1, 2),
             MovementType::Leaping => (1, 2),
+            MovementType::Flight => (1, 2),
         }
         .into()
     }
@@ -41,6 +60,16 @@ impl MovementCost {
     pub fn meters_gained(&self) -> Meters {
         self.meters_gained
     }
+
+    /// Returns the cost of raising the movement by `gained`, or `None` if it's too large to count.
+    pub fn cost_of(&self, gained: u64) -> Option<CharacterPoints> {
+        ratio_cost(
+            gained,
+            self.character_points.inner(),
+            self.meters_gained.inner() as u64,
+        )
+        .map(CharacterPoints::from)
+    }
 }
 impl From<(u64, i64)> for MovementCost {
     fn from((character_points, meters_gained): (u64, i64)) -> Self {
@@ -0,0 +1,684 @@
+use super::{
+    attack::{resolve_attack, Attack, AttackResult, Target},
+    character::{Character, UseResult},
+    characteristics::Characteristic,
+    dice::DiceError,
+    Kilograms, Meters, PositiveNumber,
+};
+use crate::rand::Rng;
+
+/// The most characters a single combat can hold.
+pub const MAX_COMBATANTS: usize = 16;
+pub const SEGMENTS_PER_TURN: u8 = 12;
+
+/// Returns the segments a character with the speed acts in, as bits 1 to 12.
+pub fn phases(speed: PositiveNumber) -> u16 {
+    let segments: &[u8] = match speed.inner() {
+        0 => &[],
+        1 => &[7],
+        2 => &[6, 12],
+        3 => &[4, 8, 12],
+        4 => &[3, 6, 9, 12],
+        5 => &[3, 5, 8, 10, 12],
+        6 => &[2, 4, 6, 8, 10, 12],
+        7 => &[2, 4, 6, 7, 9, 11, 12],
+        8 => &[2, 3, 5, 6, 8, 9, 11, 12],
+        9 => &[2, 3, 4, 6, 7, 8, 10, 11, 12],
+        10 => &[2, 3, 4, 5, 6, 8, 9, 10, 11, 12],
+        11 => &[2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        _ => &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+    };
+
+    segments.iter().fold(0, |bits, segment| bits | 1 << segment)
+}
+
+/// Returns whether a character with the speed acts in the segment.
+pub fn acts_in(speed: PositiveNumber, segment: u8) -> bool {
+    phases(speed) & 1 << segment != 0
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Eq, PartialOrd, Ord)]
+pub struct CombatantId(u8);
+impl CombatantId {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Condition {
+    Normal,
+    /// Took more STUN than their CON in a single hit, and will spend their next phase recovering.
+    Stunned,
+    /// STUN dropped to 0 or below. Takes a recovery every phase until it is back above 0.
+    Unconscious,
+    /// BODY dropped to minus their BODY characteristic.
+    Dead,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CombatError {
+    /// The combat already holds `MAX_COMBATANTS`.
+    Full,
+    UnknownCombatant(CombatantId),
+    /// Someone is still acting, and has to finish their phase first.
+    PhaseInProgress(CombatantId),
+    /// Only the acting combatant can do that.
+    NotActing(CombatantId),
+    /// The combatant has no held action to take.
+    NotHolding(CombatantId),
+    Dice(DiceError),
+}
+impl From<DiceError> for CombatError {
+    fn from(e: DiceError) -> Self {
+        Self::Dice(e)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CombatEvent {
+    SegmentStarted {
+        turn: u32,
+        segment: u8,
+    },
+    /// The combatant can act. Finish with `end_phase`, `hold` or `take_recovery`.
+    Phase(CombatantId),
+    /// The combatant spent their phase recovering from being stunned.
+    StunRecovered(CombatantId),
+    /// The unconscious combatant spent their phase taking a recovery.
+    UnconsciousRecovery {
+        combatant: CombatantId,
+        woke_up: bool,
+    },
+    /// Everyone took a recovery at the end of the turn.
+    PostSegment12,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Combatant {
+    character: Character,
+    stun: i64,
+    body: i64,
+    condition: Condition,
+    holding: bool,
+}
+impl Combatant {
+    /// Regains STUN and END equal to the character's REC.
+    fn recover(&mut self) {
+        let recovery = self.character.characteristic(Characteristic::Recovery);
+        let max_stun = self
+            .character
+            .max_characteristic(Characteristic::Stun)
+            .inner() as i64;
+        self.stun = (self.stun + recovery.inner() as i64).min(max_stun);
+        self.character.recover_endurance(recovery);
+
+        if self.condition == Condition::Unconscious && self.stun > 0 {
+            self.condition = Condition::Normal;
+        }
+    }
+}
+
+/// Runs combat segment by segment on the 12 segment Speed Chart.
+/// Within a segment everyone acts in order of DEX, with ties going to whoever joined first.
+/// Everything is fixed size plain data, so combat can be stored in a rollback state.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Combat {
+    combatants: [Option<Combatant>; MAX_COMBATANTS],
+    turn: u32,
+    segment: u8,
+    segment_started: bool,
+    /// The combatants still to act this segment, in order.
+    queue: [Option<CombatantId>; MAX_COMBATANTS],
+    queue_start: usize,
+    queue_len: usize,
+    acting: Option<CombatantId>,
+}
+impl Combat {
+    /// Creates a new combat. Like the rules say, combat starts on segment 12.
+    pub fn new() -> Self {
+        Self {
+            combatants: [None; MAX_COMBATANTS],
+            turn: 0,
+            segment: SEGMENTS_PER_TURN,
+            segment_started: false,
+            queue: [None; MAX_COMBATANTS],
+            queue_start: 0,
+            queue_len: 0,
+            acting: None,
+        }
+    }
+
+    /// Adds a character to the combat, starting with full STUN and BODY.
+    /// They act from the next segment on.
+    pub fn add(&mut self, character: Character) -> Result<CombatantId, CombatError> {
+        let index = self
+            .combatants
+            .iter()
+            .position(|c| c.is_none())
+            .ok_or(CombatError::Full)?;
+
+        self.combatants[index] = Some(Combatant {
+            character,
+            stun: character.characteristic(Characteristic::Stun).inner() as i64,
+            body: character.characteristic(Characteristic::Body).inner() as i64,
+            condition: Condition::Normal,
+            holding: false,
+        });
+        Ok(CombatantId(index as u8))
+    }
+
+    pub fn turn(&self) -> u32 {
+        self.turn
+    }
+
+    pub fn segment(&self) -> u8 {
+        self.segment
+    }
+
+    /// Returns who is taking their phase, if anyone.
+    pub fn acting(&self) -> Option<CombatantId> {
+        self.acting
+    }
+
+    pub fn character(&self, id: CombatantId) -> Option<&Character> {
+        self.get(id).ok().map(|c| &c.character)
+    }
+
+    /// Returns the current STUN, which can be negative.
+    pub fn stun(&self, id: CombatantId) -> Option<i64> {
+        self.get(id).ok().map(|c| c.stun)
+    }
+
+    /// Returns the current BODY, which can be negative.
+    pub fn body(&self, id: CombatantId) -> Option<i64> {
+        self.get(id).ok().map(|c| c.body)
+    }
+
+    pub fn condition(&self, id: CombatantId) -> Option<Condition> {
+        self.get(id).ok().map(|c| c.condition)
+    }
+
+    pub fn is_holding(&self, id: CombatantId) -> bool {
+        self.get(id).is_ok_and(|c| c.holding)
+    }
+
+    fn get(&self, id: CombatantId) -> Result<&Combatant, CombatError> {
+        self.combatants
+            .get(id.index())
+            .and_then(|c| c.as_ref())
+            .ok_or(CombatError::UnknownCombatant(id))
+    }
+
+    fn get_mut(&mut self, id: CombatantId) -> Result<&mut Combatant, CombatError> {
+        self.combatants
+            .get_mut(id.index())
+            .and_then(|c| c.as_mut())
+            .ok_or(CombatError::UnknownCombatant(id))
+    }
+
+    /// Moves combat forward to the next thing that happens.
+    /// Fails while someone is still taking their phase.
+    pub fn advance(&mut self) -> Result<CombatEvent, CombatError> {
+        if let Some(id) = self.acting {
+            return Err(CombatError::PhaseInProgress(id));
+        }
+
+        if !self.segment_started {
+            self.start_segment();
+            return Ok(CombatEvent::SegmentStarted {
+                turn: self.turn,
+                segment: self.segment,
+            });
+        }
+
+        while self.queue_len > 0 {
+            let id = match self.queue[self.queue_start].take() {
+                Some(id) => id,
+                None => continue,
+            };
+            self.queue_start += 1;
+            self.queue_len -= 1;
+
+            let combatant = self.get_mut(id)?;
+            // Held actions can't be kept past the next phase.
+            combatant.holding = false;
+            match combatant.condition {
+                Condition::Normal => {
+                    self.acting = Some(id);
+                    return Ok(CombatEvent::Phase(id));
+                }
+                Condition::Stunned => {
+                    combatant.condition = Condition::Normal;
+                    return Ok(CombatEvent::StunRecovered(id));
+                }
+                Condition::Unconscious => {
+                    combatant.recover();
+                    return Ok(CombatEvent::UnconsciousRecovery {
+                        combatant: id,
+                        woke_up: combatant.condition == Condition::Normal,
+                    });
+                }
+                Condition::Dead => {}
+            }
+        }
+
+        self.segment_started = false;
+        if self.segment == SEGMENTS_PER_TURN {
+            for combatant in self.combatants.iter_mut().flatten() {
+                if combatant.condition != Condition::Dead {
+                    combatant.recover();
+                }
+            }
+
+            self.turn += 1;
+            self.segment = 1;
+            return Ok(CombatEvent::PostSegment12);
+        }
+
+        self.segment += 1;
+        self.advance()
+    }
+
+    /// Queues everyone who acts this segment, ordered by DEX.
+    fn start_segment(&mut self) {
+        self.segment_started = true;
+        self.queue = [None; MAX_COMBATANTS];
+        self.queue_start = 0;
+        self.queue_len = 0;
+
+        let segment = self.segment;
+        for (index, combatant) in self.combatants.iter().enumerate() {
+            let acts = match combatant {
+                Some(c) => {
+                    c.condition != Condition::Dead
+                        && acts_in(c.character.characteristic(Characteristic::Speed), segment)
+                }
+                None => false,
+            };
+            if acts {
+                self.queue[self.queue_len] = Some(CombatantId(index as u8));
+                self.queue_len += 1;
+            }
+        }
+
+        let combatants = &self.combatants;
+        let dexterity = |id: &Option<CombatantId>| {
+            id.and_then(|id| combatants[id.index()].as_ref())
+                .map_or(0, |c| {
+                    c.character
+                        .characteristic(Characteristic::Dexterity)
+                        .inner()
+                })
+        };
+        // The key includes the id, so the order never depends on the sort.
+        self.queue[..self.queue_len]
+            .sort_unstable_by_key(|id| (core::cmp::Reverse(dexterity(id)), *id));
+    }
+
+    fn check_acting(&self, id: CombatantId) -> Result<(), CombatError> {
+        if self.acting == Some(id) {
+            Ok(())
+        } else {
+            Err(CombatError::NotActing(id))
+        }
+    }
+
+    /// Ends the acting combatant's phase.
+    pub fn end_phase(&mut self, id: CombatantId) -> Result<(), CombatError> {
+        self.check_acting(id)?;
+        self.acting = None;
+        Ok(())
+    }
+
+    /// Ends the acting combatant's phase, keeping their action to take later with `act_held`.
+    /// The held action is lost once their next phase comes around.
+    pub fn hold(&mut self, id: CombatantId) -> Result<(), CombatError> {
+        self.check_acting(id)?;
+        self.get_mut(id)?.holding = true;
+        self.acting = None;
+        Ok(())
+    }
+
+    /// Takes a held action, interrupting the segment.
+    pub fn act_held(&mut self, id: CombatantId) -> Result<(), CombatError> {
+        if let Some(acting) = self.acting {
+            return Err(CombatError::PhaseInProgress(acting));
+        }
+
+        let combatant = self.get_mut(id)?;
+        if !combatant.holding || combatant.condition != Condition::Normal {
+            return Err(CombatError::NotHolding(id));
+        }
+
+        combatant.holding = false;
+        self.acting = Some(id);
+        Ok(())
+    }
+
+    /// Spends the acting combatant's phase regaining STUN and END equal to their REC.
+    pub fn take_recovery(&mut self, id: CombatantId) -> Result<(), CombatError> {
+        self.check_acting(id)?;
+        self.get_mut(id)?.recover();
+        self.acting = None;
+        Ok(())
+    }
+
+    /// Spends the END for the acting combatant to use their strength.
+    pub fn use_strength(
+        &mut self,
+        id: CombatantId,
+        value: PositiveNumber,
+    ) -> Result<UseResult, CombatError> {
+        self.check_acting(id)?;
+        Ok(self.get_mut(id)?.character.use_strength(value))
+    }
+
+    /// Makes an attack from the acting combatant, applying whatever gets through to the target.
+    pub fn attack(
+        &mut self,
+        rng: &mut Rng,
+        attacker: CombatantId,
+        target: CombatantId,
+        attack: &Attack,
+        mass: Kilograms,
+        distance: Meters,
+    ) -> Result<AttackResult, CombatError> {
+        self.check_acting(attacker)?;
+        let ocv = self
+            .get(attacker)?
+            .character
+            .characteristic(Characteristic::OffensiveCombatValue);
+        let defender = Target::new(&self.get(target)?.character, mass);
+
+        let result = resolve_attack(rng, ocv, attack, &defender, distance)?;
+        if let Some(hit) = result.hit {
+            self.apply_damage(target, hit.stun, hit.body)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Applies STUN and BODY from a single hit that got through defenses.
+    /// Returns the condition the combatant is left in.
+    pub fn apply_damage(
+        &mut self,
+        id: CombatantId,
+        stun: PositiveNumber,
+        body: PositiveNumber,
+    ) -> Result<Condition, CombatError> {
+        let combatant = self.get_mut(id)?;
+        if combatant.condition == Condition::Dead {
+            return Ok(Condition::Dead);
+        }
+
+        combatant.stun -= stun.inner() as i64;
+        combatant.body -= body.inner() as i64;
+
+        let character = &combatant.character;
+        let max_body = character.max_characteristic(Characteristic::Body).inner() as i64;
+        let constitution = character.characteristic(Characteristic::Constitution);
+        if combatant.body <= -max_body {
+            combatant.condition = Condition::Dead;
+        } else if combatant.stun <= 0 {
+            combatant.condition = Condition::Unconscious;
+        } else if stun > constitution && combatant.condition == Condition::Normal {
+            combatant.condition = Condition::Stunned;
+        }
+
+        let condition = combatant.condition;
+        if condition != Condition::Normal {
+            combatant.holding = false;
+            if self.acting == Some(id) {
+                self.acting = None;
+            }
+        }
+
+        Ok(condition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hero_system::character::CharacterBuilder;
+
+    fn character(speed: u64, dexterity: u64) -> Character {
+        let mut b = CharacterBuilder::new(500.into());
+        if speed > 2 {
+            b.buy(Characteristic::Speed, (speed - 2).into()).unwrap();
+        }
+        if dexterity > 10 {
+            b.buy(Characteristic::Dexterity, (dexterity - 10).into())
+                .unwrap();
+        }
+        b.build()
+    }
+
+    /// Advances until the next phase, skipping everything else.
+    fn next_phase(combat: &mut Combat) -> (u32, u8, CombatantId) {
+        loop {
+            if let CombatEvent::Phase(id) = combat.advance().unwrap() {
+                return (combat.turn(), combat.segment(), id);
+            }
+        }
+    }
+
+    #[test]
+    fn speed_chart_has_speed_phases() {
+        for speed in 0..=12 {
+            assert_eq!(speed as u32, phases(speed.into()).count_ones());
+        }
+        assert!(acts_in(4.into(), 9));
+        assert!(!acts_in(4.into(), 10));
+        assert!(acts_in(1.into(), 7));
+        assert_eq!(phases(12.into()), phases(20.into()));
+    }
+
+    #[test]
+    fn combat_starts_on_segment_12() {
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 10)).unwrap();
+
+        assert_eq!(
+            Ok(CombatEvent::SegmentStarted {
+                turn: 0,
+                segment: 12
+            }),
+            combat.advance()
+        );
+        assert_eq!(Ok(CombatEvent::Phase(a)), combat.advance());
+        assert_eq!(Err(CombatError::PhaseInProgress(a)), combat.advance());
+        combat.end_phase(a).unwrap();
+
+        assert_eq!(Ok(CombatEvent::PostSegment12), combat.advance());
+        assert_eq!((1, 6, a), next_phase(&mut combat));
+    }
+
+    #[test]
+    fn phases_follow_speed_and_dex() {
+        let mut combat = Combat::new();
+        let slow = combat.add(character(2, 20)).unwrap();
+        let fast = combat.add(character(3, 10)).unwrap();
+        let quick = combat.add(character(3, 15)).unwrap();
+
+        let mut phases = vec![];
+        while combat.turn() < 2 {
+            let (turn, segment, id) = next_phase(&mut combat);
+            combat.end_phase(id).unwrap();
+            if turn == 1 {
+                phases.push((segment, id));
+            }
+        }
+
+        assert_eq!(
+            vec![
+                (4, quick),
+                (4, fast),
+                (6, slow),
+                (8, quick),
+                (8, fast),
+                (12, slow),
+                (12, quick),
+                (12, fast),
+            ],
+            phases
+        );
+    }
+
+    #[test]
+    fn held_action_interrupts_and_is_lost_on_next_phase() {
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 20)).unwrap();
+        let b = combat.add(character(2, 10)).unwrap();
+
+        let (_, _, id) = next_phase(&mut combat);
+        assert_eq!(a, id);
+        combat.hold(a).unwrap();
+        assert!(combat.is_holding(a));
+
+        // A acts before B finishes, then B carries on.
+        assert_eq!(Ok(CombatEvent::Phase(b)), combat.advance());
+        assert_eq!(Err(CombatError::PhaseInProgress(b)), combat.act_held(a));
+        combat.end_phase(b).unwrap();
+        combat.act_held(a).unwrap();
+        assert_eq!(Some(a), combat.acting());
+        combat.end_phase(a).unwrap();
+        assert_eq!(Err(CombatError::NotHolding(a)), combat.act_held(a));
+
+        // Held until the next phase, where it is lost.
+        let (_, segment, id) = next_phase(&mut combat);
+        assert_eq!((6, a), (segment, id));
+        combat.hold(a).unwrap();
+        combat.advance().unwrap();
+        combat.end_phase(b).unwrap();
+        let (_, segment, id) = next_phase(&mut combat);
+        assert_eq!((12, a), (segment, id));
+        assert!(!combat.is_holding(a));
+    }
+
+    #[test]
+    fn big_hits_stun() {
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 10)).unwrap();
+
+        assert_eq!(
+            Ok(Condition::Normal),
+            combat.apply_damage(a, 5.into(), 2.into())
+        );
+        assert_eq!(
+            Ok(Condition::Stunned),
+            combat.apply_damage(a, 11.into(), 2.into())
+        );
+        assert_eq!(Some(4), combat.stun(a));
+        assert_eq!(Some(6), combat.body(a));
+
+        // The stunned phase is spent recovering.
+        combat.advance().unwrap();
+        assert_eq!(Ok(CombatEvent::StunRecovered(a)), combat.advance());
+        assert_eq!(Some(Condition::Normal), combat.condition(a));
+    }
+
+    #[test]
+    fn knocked_out_recovers_on_phases() {
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 10)).unwrap();
+        combat.apply_damage(a, 25.into(), 0.into()).unwrap();
+        assert_eq!(Some(Condition::Unconscious), combat.condition(a));
+        assert_eq!(Some(-5), combat.stun(a));
+
+        combat.advance().unwrap();
+        assert_eq!(
+            Ok(CombatEvent::UnconsciousRecovery {
+                combatant: a,
+                woke_up: false
+            }),
+            combat.advance()
+        );
+        assert_eq!(Some(-1), combat.stun(a));
+
+        // Post segment 12 is a recovery too.
+        assert_eq!(Ok(CombatEvent::PostSegment12), combat.advance());
+        assert_eq!(Some(3), combat.stun(a));
+        assert_eq!(Some(Condition::Normal), combat.condition(a));
+    }
+
+    #[test]
+    fn dead_do_not_act() {
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 10)).unwrap();
+        let b = combat.add(character(2, 10)).unwrap();
+        assert_eq!(
+            Ok(Condition::Dead),
+            combat.apply_damage(a, 0.into(), 20.into())
+        );
+
+        assert_eq!((0, 12, b), next_phase(&mut combat));
+        combat.end_phase(b).unwrap();
+        assert_eq!(Ok(CombatEvent::PostSegment12), combat.advance());
+        assert_eq!(Some(-10), combat.body(a));
+    }
+
+    #[test]
+    fn strength_spends_endurance_while_acting() {
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 10)).unwrap();
+        assert_eq!(
+            Err(CombatError::NotActing(a)),
+            combat.use_strength(a, 20.into())
+        );
+
+        next_phase(&mut combat);
+        assert_eq!(Ok(UseResult::Success), combat.use_strength(a, 20.into()));
+        let endurance = |c: &Combat| {
+            c.character(a)
+                .unwrap()
+                .characteristic(Characteristic::Endurance)
+                .inner()
+        };
+        assert_eq!(18, endurance(&combat));
+
+        combat.take_recovery(a).unwrap();
+        assert_eq!(20, endurance(&combat));
+    }
+
+    #[test]
+    fn full_combat_is_refused() {
+        let mut combat = Combat::new();
+        for _ in 0..MAX_COMBATANTS {
+            combat.add(character(2, 10)).unwrap();
+        }
+        assert_eq!(Err(CombatError::Full), combat.add(character(2, 10)));
+    }
+
+    #[test]
+    fn attacks_apply_damage_to_target() {
+        use crate::hero_system::attack::{DamageKind, DefenseType};
+
+        let mut combat = Combat::new();
+        let a = combat.add(character(2, 20)).unwrap();
+        let b = combat.add(character(2, 10)).unwrap();
+        let punch = Attack {
+            dice: 4.into(),
+            kind: DamageKind::Normal,
+            defense: DefenseType::Physical,
+            armor_piercing: false,
+        };
+
+        let mut rng = Rng::new(1);
+        assert_eq!(
+            Err(CombatError::NotActing(a)),
+            combat.attack(&mut rng, a, b, &punch, 100.into(), 1.into())
+        );
+
+        next_phase(&mut combat);
+        let result = combat
+            .attack(&mut rng, a, b, &punch, 100.into(), 1.into())
+            .unwrap();
+        let (stun, body) = result.hit.map_or((0, 0), |hit| {
+            (hit.stun.inner() as i64, hit.body.inner() as i64)
+        });
+        assert_eq!(Some(20 - stun), combat.stun(b));
+        assert_eq!(Some(10 - body), combat.body(b));
+    }
+}
@@ -0,0 +1,383 @@
+use super::{
+    characteristic_roll, characteristics::CharacterPoints, Number, PositiveNumber, Quantity, D6,
+};
+use crate::rand::Rng;
+
+/// The most dice a single damage roll can use, so that the breakdown fits in a fixed array.
+pub const MAX_DICE: usize = 64;
+
+/// Rolls under this or less always succeed.
+const AUTOMATIC_SUCCESS: u64 = 3;
+/// Rolls of this or more always fail.
+const AUTOMATIC_FAILURE: u64 = 18;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DiceError {
+    /// More than `MAX_DICE` dice would be rolled.
+    TooManyDice { requested: PositiveNumber },
+}
+
+/// A number of d6, optionally with an extra half die, such as 2½d6.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DamageDice {
+    pub dice: Quantity<D6>,
+    pub half_die: bool,
+}
+impl DamageDice {
+    /// Returns how many dice are physically rolled, counting the half die as one.
+    pub fn dice_rolled(&self) -> u64 {
+        self.dice.number.inner() + self.half_die as u64
+    }
+}
+impl From<Quantity<D6>> for DamageDice {
+    fn from(dice: Quantity<D6>) -> Self {
+        Self {
+            dice,
+            half_die: false,
+        }
+    }
+}
+impl From<u64> for DamageDice {
+    fn from(n: u64) -> Self {
+        Quantity {
+            number: n.into(),
+            item: D6,
+        }
+        .into()
+    }
+}
+
+/// A single die that was rolled, and what it counted for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DieRoll {
+    /// The face that came up, from 1 to 6.
+    pub face: u8,
+    /// Whether this was the half die.
+    pub half: bool,
+    pub stun: PositiveNumber,
+    pub body: PositiveNumber,
+}
+
+/// Every die rolled for a damage roll, in the order they were rolled.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DieRolls {
+    rolls: [Option<DieRoll>; MAX_DICE],
+    len: usize,
+}
+impl DieRolls {
+    fn new() -> Self {
+        Self {
+            rolls: [None; MAX_DICE],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, roll: DieRoll) {
+        self.rolls[self.len] = Some(roll);
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DieRoll> {
+        self.rolls[..self.len].iter().flatten()
+    }
+}
+
+/// The result of a 3d6 roll-under check.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CheckResult {
+    pub rolls: [u8; 3],
+    pub total: PositiveNumber,
+    pub target: PositiveNumber,
+    /// How far under the target the roll was. Negative on a failure.
+    pub margin: Number,
+    pub success: bool,
+}
+
+/// The result of a normal damage roll.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NormalDamage {
+    pub rolls: DieRolls,
+    pub stun: PositiveNumber,
+    pub body: PositiveNumber,
+}
+
+/// The result of a killing damage roll.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KillingDamage {
+    pub rolls: DieRolls,
+    pub body: PositiveNumber,
+    /// The face rolled on the ½d6 used for the STUN multiplier.
+    pub stun_multiplier_roll: u8,
+    pub stun_multiplier: PositiveNumber,
+    pub stun: PositiveNumber,
+}
+
+fn roll_d6(rng: &mut Rng) -> u8 {
+    rng.die(6) as u8
+}
+
+/// Halves a die, rounding up.
+fn halve(face: u8) -> u64 {
+    (face as u64).div_ceil(2)
+}
+
+/// Rolls 3d6 and checks it against the target. Equal to or under the target succeeds.
+/// A 3 always succeeds and an 18 always fails.
+pub fn roll_check(rng: &mut Rng, target: PositiveNumber) -> CheckResult {
+    let rolls = [roll_d6(rng), roll_d6(rng), roll_d6(rng)];
+    let total: u64 = rolls.iter().map(|r| *r as u64).sum();
+    let success = match total {
+        AUTOMATIC_SUCCESS => true,
+        AUTOMATIC_FAILURE => false,
+        _ => total <= target.inner(),
+    };
+
+    CheckResult {
+        rolls,
+        total: total.into(),
+        target,
+        margin: (target.inner() as i64 - total as i64).into(),
+        success,
+    }
+}
+
+/// Makes a characteristic roll, which is a check against 9 + characteristic / 5.
+pub fn roll_characteristic(rng: &mut Rng, characteristic: CharacterPoints) -> CheckResult {
+    roll_check(rng, characteristic_roll(characteristic))
+}
+
+/// Rolls normal damage.
+/// STUN is the total of the dice. BODY counts 0 for a 1, 2 for a 6 and 1 for anything else.
+/// The half die counts half its face rounded up for STUN, and 1 BODY on a 4 or more.
+pub fn roll_normal_damage(rng: &mut Rng, dice: DamageDice) -> Result<NormalDamage, DiceError> {
+    check_dice(dice)?;
+
+    let mut damage = NormalDamage {
+        rolls: DieRolls::new(),
+        stun: 0.into(),
+        body: 0.into(),
+    };
+    for half in whole_then_half(dice) {
+        let face = roll_d6(rng);
+        let (stun, body) = if half {
+            (halve(face), (face >= 4) as u64)
+        } else {
+            let body = match face {
+                1 => 0,
+                6 => 2,
+                _ => 1,
+            };
+            (face as u64, body)
+        };
+
+        damage.stun += stun.into();
+        damage.body += body.into();
+        damage.rolls.push(DieRoll {
+            face,
+            half,
+            stun: stun.into(),
+            body: body.into(),
+        });
+    }
+
+    Ok(damage)
+}
+
+/// Rolls killing damage.
+/// BODY is the total of the dice, with the half die counting half its face rounded up.
+/// STUN is the BODY times a ½d6 multiplier.
+pub fn roll_killing_damage(rng: &mut Rng, dice: DamageDice) -> Result<KillingDamage, DiceError> {
+    check_dice(dice)?;
+
+    let mut rolls = DieRolls::new();
+    let mut body = 0;
+    for half in whole_then_half(dice) {
+        let face = roll_d6(rng);
+        let value = if half { halve(face) } else { face as u64 };
+
+        body += value;
+        rolls.push(DieRoll {
+            face,
+            half,
+            stun: 0.into(),
+            body: value.into(),
+        });
+    }
+
+    let stun_multiplier_roll = roll_d6(rng);
+    let stun_multiplier = halve(stun_multiplier_roll);
+    Ok(KillingDamage {
+        rolls,
+        body: body.into(),
+        stun_multiplier_roll,
+        stun_multiplier: stun_multiplier.into(),
+        stun: (body * stun_multiplier).into(),
+    })
+}
+
+fn check_dice(dice: DamageDice) -> Result<(), DiceError> {
+    if dice.dice_rolled() > MAX_DICE as u64 {
+        return Err(DiceError::TooManyDice {
+            requested: dice.dice_rolled().into(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns whether each die to roll is the half die, with the half die last.
+fn whole_then_half(dice: DamageDice) -> impl Iterator<Item = bool> {
+    let whole = dice.dice.number.inner();
+    (0..whole)
+        .map(|_| false)
+        .chain(dice.half_die.then_some(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn half(n: u64) -> DamageDice {
+        DamageDice {
+            half_die: true,
+            ..n.into()
+        }
+    }
+
+    #[test]
+    fn check_margin_matches_roll() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let result = roll_check(&mut rng, 11.into());
+            let total: u64 = result.rolls.iter().map(|r| *r as u64).sum();
+            assert_eq!(PositiveNumber::from(total), result.total);
+            assert_eq!(Number::from(11 - total as i64), result.margin);
+            assert_eq!(total <= 11, result.success);
+        }
+    }
+
+    #[test]
+    fn check_extremes_are_automatic() {
+        let mut rng = Rng::new(2);
+        let mut seen = (false, false);
+        for _ in 0..5000 {
+            let low = roll_check(&mut rng, 0.into());
+            if low.total.inner() == 3 {
+                assert!(low.success);
+                seen.0 = true;
+            } else {
+                assert!(!low.success);
+            }
+
+            let high = roll_check(&mut rng, 30.into());
+            if high.total.inner() == 18 {
+                assert!(!high.success);
+                seen.1 = true;
+            } else {
+                assert!(high.success);
+            }
+        }
+        assert_eq!((true, true), seen);
+    }
+
+    #[test]
+    fn characteristic_roll_uses_target() {
+        let mut rng = Rng::new(3);
+        let result = roll_characteristic(&mut rng, 20.into());
+        assert_eq!(PositiveNumber::from(13), result.target);
+    }
+
+    #[test]
+    fn normal_damage_counts_stun_and_body() {
+        let mut rng = Rng::new(4);
+        for _ in 0..200 {
+            let damage = roll_normal_damage(&mut rng, 5.into()).unwrap();
+            assert_eq!(5, damage.rolls.len());
+
+            let mut stun = 0;
+            let mut body = 0;
+            for roll in damage.rolls.iter() {
+                let expected_body = match roll.face {
+                    1 => 0,
+                    6 => 2,
+                    _ => 1,
+                };
+                assert!(!roll.half);
+                assert_eq!(PositiveNumber::from(roll.face as u64), roll.stun);
+                assert_eq!(PositiveNumber::from(expected_body), roll.body);
+                stun += roll.stun.inner();
+                body += roll.body.inner();
+            }
+            assert_eq!(PositiveNumber::from(stun), damage.stun);
+            assert_eq!(PositiveNumber::from(body), damage.body);
+        }
+    }
+
+    #[test]
+    fn normal_damage_half_die() {
+        let mut rng = Rng::new(5);
+        for _ in 0..200 {
+            let damage = roll_normal_damage(&mut rng, half(1)).unwrap();
+            let rolls: Vec<_> = damage.rolls.iter().collect();
+            assert_eq!(2, rolls.len());
+
+            let half = rolls[1];
+            assert!(half.half);
+            assert_eq!(
+                PositiveNumber::from((half.face as u64).div_ceil(2)),
+                half.stun
+            );
+            assert_eq!(PositiveNumber::from((half.face >= 4) as u64), half.body);
+        }
+    }
+
+    #[test]
+    fn killing_damage_multiplies_stun() {
+        let mut rng = Rng::new(6);
+        for _ in 0..200 {
+            let damage = roll_killing_damage(&mut rng, half(2)).unwrap();
+            let body: u64 = damage.rolls.iter().map(|r| r.body.inner()).sum();
+            assert_eq!(PositiveNumber::from(body), damage.body);
+            assert!((1..=3).contains(&damage.stun_multiplier.inner()));
+            assert_eq!(
+                PositiveNumber::from(body * damage.stun_multiplier.inner()),
+                damage.stun
+            );
+            assert!((3..=15).contains(&body));
+        }
+    }
+
+    #[test]
+    fn rolls_are_deterministic() {
+        let a = roll_normal_damage(&mut Rng::new(7), 10.into());
+        let b = roll_normal_damage(&mut Rng::new(7), 10.into());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn zero_dice_do_nothing() {
+        let damage = roll_normal_damage(&mut Rng::new(8), 0.into()).unwrap();
+        assert!(damage.rolls.is_empty());
+        assert_eq!(PositiveNumber::from(0), damage.stun);
+    }
+
+    #[test]
+    fn too_many_dice_fails() {
+        let mut rng = Rng::new(9);
+        assert_eq!(
+            Err(DiceError::TooManyDice {
+                requested: 65.into()
+            }),
+            roll_killing_damage(&mut rng, half(MAX_DICE as u64))
+        );
+        assert!(roll_normal_damage(&mut rng, (MAX_DICE as u64).into()).is_ok());
+    }
+}
@@ -1,9 +1,18 @@
-use core::ops::{Div, SubAssign};
+use core::ops::{AddAssign, Div, SubAssign};
 
 use self::characteristics::CharacterPoints;
 
+mod attack;
 mod character;
 mod characteristics;
+mod combat;
+mod dice;
+mod powers;
+
+pub use attack::*;
+pub use combat::*;
+pub use dice::*;
+pub use powers::*;
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq, PartialOrd, Ord)]
 pub struct PositiveNumber(u64);
@@ -12,6 +21,11 @@ impl PositiveNumber {
         self.0
     }
 }
+impl AddAssign for PositiveNumber {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
 impl SubAssign for PositiveNumber {
     fn sub_assign(&mut self, rhs: Self) {
         self.0 -= rhs.0;
@@ -32,6 +46,11 @@ impl From<u64> for PositiveNumber {
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Number(i64);
+impl Number {
+    pub fn inner(&self) -> i64 {
+        self.0
+    }
+}
 impl From<i64> for Number {
     fn from(n: i64) -> Self {
         Self(n)
@@ -40,6 +59,11 @@ impl From<i64> for Number {
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Meters(Number);
+impl Meters {
+    pub fn inner(&self) -> i64 {
+        self.0.inner()
+    }
+}
 impl From<i64> for Meters {
     fn from(n: i64) -> Self {
         Self(n.into())
@@ -48,6 +72,11 @@ impl From<i64> for Meters {
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Kilograms(Number);
+impl Kilograms {
+    pub fn inner(&self) -> i64 {
+        self.0.inner()
+    }
+}
 impl From<i32> for Kilograms {
     fn from(i: i32) -> Self {
         (i as i64).into()
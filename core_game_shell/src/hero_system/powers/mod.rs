@@ -0,0 +1,379 @@
+use super::{
+    attack::{Attack, DamageKind, DefenseType},
+    characteristics::{ratio_cost, CharacterPoints, MovementType},
+    dice::DamageDice,
+    Meters, PositiveNumber,
+};
+
+/// The most advantages, and separately limitations, a single power can have.
+pub const MAX_MODIFIERS: usize = 4;
+/// Modifiers are worth whole quarters.
+const QUARTER: u64 = 4;
+
+/// Cost of a Blast per d6 and per ½d6.
+const BLAST_COST: (u64, u64) = (5, 3);
+/// Cost of a Killing Attack per d6 and per ½d6.
+const KILLING_ATTACK_COST: (u64, u64) = (15, 10);
+/// Armor costs this many points for every `ARMOR_PER` points of defense.
+const ARMOR_COST: u64 = 3;
+const ARMOR_PER: u64 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerError {
+    /// The power already has `MAX_MODIFIERS` advantages or limitations.
+    TooManyModifiers,
+}
+
+/// The value of an advantage or limitation, in quarters. Advantages add it, limitations take it away.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, PartialOrd, Ord)]
+pub struct ModifierValue(u64);
+impl ModifierValue {
+    pub fn from_quarters(quarters: u64) -> Self {
+        Self(quarters)
+    }
+    pub fn quarters(&self) -> u64 {
+        self.0
+    }
+}
+
+/// What a power does.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Effect {
+    /// A ranged attack that does normal damage.
+    Blast {
+        dice: DamageDice,
+        defense: DefenseType,
+    },
+    KillingAttack {
+        dice: DamageDice,
+        defense: DefenseType,
+    },
+    /// Extra meters of movement, such as Flight or more Running.
+    Movement {
+        movement: MovementType,
+        meters: Meters,
+    },
+    /// Resistant defenses.
+    Armor {
+        physical: PositiveNumber,
+        energy: PositiveNumber,
+    },
+}
+impl Effect {
+    /// Returns the cost before any advantages or limitations, or `None` if it's too large to count.
+    pub fn base_cost(&self) -> Option<CharacterPoints> {
+        let dice_cost = |dice: &DamageDice, (per_die, per_half_die): (u64, u64)| {
+            let whole = dice.dice.number.inner().checked_mul(per_die)?;
+            whole
+                .checked_add(dice.half_die as u64 * per_half_die)
+                .map(CharacterPoints::from)
+        };
+
+        match self {
+            Effect::Blast { dice, .. } => dice_cost(dice, BLAST_COST),
+            Effect::KillingAttack { dice, .. } => dice_cost(dice, KILLING_ATTACK_COST),
+            Effect::Movement { movement, meters } => {
+                movement.cost().cost_of(meters.inner().max(0) as u64)
+            }
+            Effect::Armor { physical, energy } => {
+                let defense = physical.inner().checked_add(energy.inner())?;
+                ratio_cost(defense, ARMOR_COST, ARMOR_PER).map(CharacterPoints::from)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Advantage {
+    /// Halves the defenses the attack is applied against.
+    ArmorPiercing,
+    /// Any other advantage, which only changes the cost.
+    Other(ModifierValue),
+}
+impl Advantage {
+    pub fn value(&self) -> ModifierValue {
+        match self {
+            Advantage::ArmorPiercing => ModifierValue(1),
+            Advantage::Other(value) => *value,
+        }
+    }
+}
+
+/// A power that comes from an item, which can be taken away.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Focus {
+    ObviousAccessible,
+    ObviousInaccessible,
+    InobviousAccessible,
+    InobviousInaccessible,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Limitation {
+    Focus(Focus),
+    /// Any other limitation, which only changes the cost.
+    Other(ModifierValue),
+}
+impl Limitation {
+    pub fn value(&self) -> ModifierValue {
+        match self {
+            Limitation::Focus(Focus::ObviousAccessible) => ModifierValue(4),
+            Limitation::Focus(Focus::ObviousInaccessible) => ModifierValue(2),
+            Limitation::Focus(Focus::InobviousAccessible) => ModifierValue(2),
+            Limitation::Focus(Focus::InobviousInaccessible) => ModifierValue(1),
+            Limitation::Other(value) => *value,
+        }
+    }
+}
+
+/// An effect along with the advantages and limitations that change it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Power {
+    effect: Effect,
+    advantages: [Option<Advantage>; MAX_MODIFIERS],
+    limitations: [Option<Limitation>; MAX_MODIFIERS],
+}
+impl Power {
+    /// Creates a power with no advantages or limitations.
+    pub fn new(effect: Effect) -> Self {
+        Self {
+            effect,
+            advantages: [None; MAX_MODIFIERS],
+            limitations: [None; MAX_MODIFIERS],
+        }
+    }
+
+    pub fn effect(&self) -> Effect {
+        self.effect
+    }
+
+    pub fn with_advantage(mut self, advantage: Advantage) -> Result<Self, PowerError> {
+        insert(&mut self.advantages, advantage)?;
+        Ok(self)
+    }
+
+    pub fn with_limitation(mut self, limitation: Limitation) -> Result<Self, PowerError> {
+        insert(&mut self.limitations, limitation)?;
+        Ok(self)
+    }
+
+    pub fn advantages(&self) -> impl Iterator<Item = Advantage> + '_ {
+        self.advantages.iter().flatten().copied()
+    }
+
+    pub fn limitations(&self) -> impl Iterator<Item = Limitation> + '_ {
+        self.limitations.iter().flatten().copied()
+    }
+
+    pub fn has_advantage(&self, advantage: Advantage) -> bool {
+        self.advantages().any(|a| a == advantage)
+    }
+
+    /// Returns the base cost with advantages applied, rounded to the nearest point.
+    /// Returns `None` if the cost is too large to count.
+    pub fn active_cost(&self) -> Option<CharacterPoints> {
+        let advantages = quarters_plus_one(self.advantages().map(|a| a.value()))?;
+        ratio_cost(self.effect.base_cost()?.inner(), advantages, QUARTER).map(CharacterPoints::from)
+    }
+
+    /// Returns what the power costs to buy, which is base × (1 + advantages) / (1 + limitations).
+    /// The active cost is rounded before limitations are applied, and a power always costs at least 1.
+    /// Returns `None` if the cost is too large to count.
+    pub fn real_cost(&self) -> Option<CharacterPoints> {
+        let limitations = quarters_plus_one(self.limitations().map(|l| l.value()))?;
+        let active = self.active_cost()?.inner();
+        let real = ratio_cost(active, QUARTER, limitations)?;
+
+        Some(real.max(active.min(1)).into())
+    }
+
+    /// Returns the attack the power makes, if it is one.
+    pub fn attack(&self) -> Option<Attack> {
+        let (dice, kind, defense) = match self.effect {
+            Effect::Blast { dice, defense } => (dice, DamageKind::Normal, defense),
+            Effect::KillingAttack { dice, defense } => (dice, DamageKind::Killing, defense),
+            _ => return None,
+        };
+
+        Some(Attack {
+            dice,
+            kind,
+            defense,
+            armor_piercing: self.has_advantage(Advantage::ArmorPiercing),
+        })
+    }
+
+    /// Returns the meters the power adds to the movement.
+    pub fn movement(&self, movement: MovementType) -> Meters {
+        match self.effect {
+            Effect::Movement {
+                movement: m,
+                meters,
+            } if m == movement => meters,
+            _ => 0.into(),
+        }
+    }
+
+    /// Returns the resistant physical and energy defense the power gives.
+    pub fn resistant_defenses(&self) -> (PositiveNumber, PositiveNumber) {
+        match self.effect {
+            Effect::Armor { physical, energy } => (physical, energy),
+            _ => (0.into(), 0.into()),
+        }
+    }
+}
+
+/// Returns one plus the modifiers, in quarters, or `None` if it's too large to count.
+fn quarters_plus_one<I>(mut values: I) -> Option<u64>
+where
+    I: Iterator<Item = ModifierValue>,
+{
+    values.try_fold(QUARTER, |sum, value| sum.checked_add(value.quarters()))
+}
+
+fn insert<T>(modifiers: &mut [Option<T>; MAX_MODIFIERS], modifier: T) -> Result<(), PowerError> {
+    let slot = modifiers
+        .iter_mut()
+        .find(|m| m.is_none())
+        .ok_or(PowerError::TooManyModifiers)?;
+    *slot = Some(modifier);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blast(dice: u64) -> Power {
+        Power::new(Effect::Blast {
+            dice: dice.into(),
+            defense: DefenseType::Energy,
+        })
+    }
+
+    #[test]
+    fn base_costs() {
+        assert_eq!(Some(CharacterPoints::from(40)), blast(8).real_cost());
+
+        let killing = |dice: u64, half_die| {
+            Effect::KillingAttack {
+                dice: DamageDice {
+                    half_die,
+                    ..dice.into()
+                },
+                defense: DefenseType::Physical,
+            }
+            .base_cost()
+        };
+        assert_eq!(Some(CharacterPoints::from(30)), killing(2, false));
+        assert_eq!(Some(CharacterPoints::from(40)), killing(2, true));
+
+        let flight = Effect::Movement {
+            movement: MovementType::Flight,
+            meters: 20.into(),
+        };
+        assert_eq!(Some(CharacterPoints::from(10)), flight.base_cost());
+
+        let armor = Effect::Armor {
+            physical: 8.into(),
+            energy: 7.into(),
+        };
+        // 22.5 rounds down.
+        assert_eq!(Some(CharacterPoints::from(22)), armor.base_cost());
+    }
+
+    #[test]
+    fn cost_is_base_times_advantages_over_limitations() {
+        let power = blast(8)
+            .with_advantage(Advantage::ArmorPiercing)
+            .unwrap()
+            .with_advantage(Advantage::Other(ModifierValue::from_quarters(1)))
+            .unwrap();
+        assert_eq!(Some(CharacterPoints::from(60)), power.active_cost());
+        assert_eq!(Some(CharacterPoints::from(60)), power.real_cost());
+
+        let power = power
+            .with_limitation(Limitation::Focus(Focus::ObviousAccessible))
+            .unwrap();
+        assert_eq!(Some(CharacterPoints::from(60)), power.active_cost());
+        assert_eq!(Some(CharacterPoints::from(30)), power.real_cost());
+
+        let power = power
+            .with_limitation(Limitation::Other(ModifierValue::from_quarters(2)))
+            .unwrap();
+        assert_eq!(Some(CharacterPoints::from(24)), power.real_cost());
+    }
+
+    #[test]
+    fn costs_round_to_nearest_and_never_reach_zero() {
+        // 5 × 1½ = 7.5, which rounds down.
+        let power = blast(1)
+            .with_advantage(Advantage::ArmorPiercing)
+            .unwrap()
+            .with_advantage(Advantage::ArmorPiercing)
+            .unwrap();
+        assert_eq!(Some(CharacterPoints::from(7)), power.active_cost());
+
+        let power = blast(1)
+            .with_limitation(Limitation::Other(ModifierValue::from_quarters(40)))
+            .unwrap();
+        assert_eq!(Some(CharacterPoints::from(1)), power.real_cost());
+
+        let nothing = Power::new(Effect::Armor {
+            physical: 0.into(),
+            energy: 0.into(),
+        });
+        assert_eq!(Some(CharacterPoints::from(0)), nothing.real_cost());
+    }
+
+    #[test]
+    fn huge_costs_are_none() {
+        assert_eq!(None, blast(u64::MAX).effect().base_cost());
+        let armor = Effect::Armor {
+            physical: u64::MAX.into(),
+            energy: 1.into(),
+        };
+        assert_eq!(None, armor.base_cost());
+
+        let huge = ModifierValue::from_quarters(u64::MAX);
+        let power = blast(1).with_advantage(Advantage::Other(huge)).unwrap();
+        assert_eq!(None, power.active_cost());
+        let power = blast(1).with_limitation(Limitation::Other(huge)).unwrap();
+        assert_eq!(None, power.real_cost());
+    }
+
+    #[test]
+    fn modifiers_are_limited() {
+        let mut power = blast(1);
+        for _ in 0..MAX_MODIFIERS {
+            power = power
+                .with_limitation(Limitation::Focus(Focus::InobviousInaccessible))
+                .unwrap();
+        }
+        assert_eq!(
+            Err(PowerError::TooManyModifiers),
+            power.with_limitation(Limitation::Focus(Focus::InobviousInaccessible))
+        );
+        assert_eq!(MAX_MODIFIERS, power.limitations().count());
+    }
+
+    #[test]
+    fn attacks_come_from_attack_powers() {
+        let attack = blast(6)
+            .with_advantage(Advantage::ArmorPiercing)
+            .unwrap()
+            .attack()
+            .unwrap();
+        assert_eq!(DamageKind::Normal, attack.kind);
+        assert_eq!(DefenseType::Energy, attack.defense);
+        assert!(attack.armor_piercing);
+
+        let flight = Power::new(Effect::Movement {
+            movement: MovementType::Flight,
+            meters: 10.into(),
+        });
+        assert_eq!(None, flight.attack());
+        assert_eq!(Meters::from(10), flight.movement(MovementType::Flight));
+        assert_eq!(Meters::from(0), flight.movement(MovementType::Running));
+    }
+}
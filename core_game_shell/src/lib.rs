@@ -1,11 +1,16 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 // #![deny(warnings)]
 
 mod aabb;
+mod bytes;
+mod ecs;
 mod events;
 mod game;
 mod hero_system;
 mod math;
+mod physics;
 mod player_input;
 mod rand;
+#[cfg(any(test, feature = "std"))]
+mod script;
 mod time;
@@ -0,0 +1,647 @@
+mod tables;
+
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, Sub, SubAssign};
+use tables::{ATAN, SIN, TABLE_FRAC_BITS, TABLE_STEPS};
+
+/// PI with 61 fractional bits.
+const PI_Q61: i64 = 7244019458077122842;
+/// 1 / (2 * PI) with 64 fractional bits.
+const INV_TAU_Q64: i128 = 2935890503282001226;
+/// PI and PI / 2 with the same fractional bits as the tables.
+const PI_Q30: i64 = 3373259426;
+const FRAC_PI_2_Q30: i64 = 1686629713;
+
+/// Deterministic number with 16 fractional bits.
+pub type DNum = Fixed<16>;
+
+/// Fixed-point number, stored as an `i64` with `FRAC_BITS` fractional bits.
+/// `FRAC_BITS` must be less than 63.
+///
+/// All operations are integer only, so results are identical on every platform and optimisation level.
+/// The operators saturate on overflow, use the `checked_*` methods to detect it instead.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Hash)]
+pub struct Fixed<const FRAC_BITS: u32>(i64);
+
+impl<const FRAC_BITS: u32> Fixed<FRAC_BITS> {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << FRAC_BITS);
+    pub const MAX: Self = Self(i64::MAX);
+    pub const MIN: Self = Self(i64::MIN);
+    /// The smallest positive value that can be represented.
+    pub const EPSILON: Self = Self(1);
+    pub const PI: Self = Self::from_fixed(PI_Q61, 61);
+    pub const FRAC_PI_2: Self = Self::from_fixed(PI_Q61, 62);
+    pub const TAU: Self = Self::from_fixed(PI_Q61, 60);
+
+    const FRAC_MASK: i64 = (1 << FRAC_BITS) - 1;
+
+    /// Creates a number from its raw fixed-point representation.
+    pub const fn from_raw(raw: i64) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the raw fixed-point representation.
+    pub const fn inner(&self) -> i64 {
+        self.0
+    }
+
+    /// Converts a fixed-point value with a different number of fractional bits.
+    const fn from_fixed(value: i64, frac_bits: u32) -> Self {
+        if frac_bits > FRAC_BITS {
+            Self(value >> (frac_bits - FRAC_BITS))
+        } else {
+            Self(saturate((value as i128) << (FRAC_BITS - frac_bits)))
+        }
+    }
+
+    /// Converts from a float. Floats are not deterministic across platforms,
+    /// so this should only be used at the edges such as loading config.
+    /// Values out of range saturate and NaN becomes zero.
+    pub fn from_f32(f: f32) -> Self {
+        Self((f as f64 * (1u64 << FRAC_BITS) as f64) as i64)
+    }
+
+    /// Converts to a float. Should only be used at the edges such as rendering.
+    pub fn to_f32(self) -> f32 {
+        (self.0 as f64 / (1u64 << FRAC_BITS) as f64) as f32
+    }
+
+    /// Returns the integer part, rounded towards negative infinity.
+    pub const fn to_i64(self) -> i64 {
+        self.0 >> FRAC_BITS
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        i64::try_from(self.wide_mul(rhs)).ok().map(Self)
+    }
+
+    /// Returns `None` on overflow or division by zero.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.0 == 0 {
+            return None;
+        }
+
+        i64::try_from(self.wide_div(rhs)).ok().map(Self)
+    }
+
+    /// Returns `None` on division by zero.
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        if rhs.0 == 0 {
+            return None;
+        }
+
+        // MIN % -1 overflows in i64, but the remainder is always zero.
+        Some(Self(self.0.wrapping_rem(rhs.0)))
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Self)
+    }
+
+    pub fn checked_abs(self) -> Option<Self> {
+        self.0.checked_abs().map(Self)
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        Self(saturate(self.wide_mul(rhs)))
+    }
+
+    /// Division by zero saturates towards the sign of `self`, and `0 / 0` is zero.
+    pub fn saturating_div(self, rhs: Self) -> Self {
+        if rhs.0 == 0 {
+            return match self.0 {
+                0 => Self::ZERO,
+                n if n > 0 => Self::MAX,
+                _ => Self::MIN,
+            };
+        }
+
+        Self(saturate(self.wide_div(rhs)))
+    }
+
+    pub fn saturating_neg(self) -> Self {
+        Self(self.0.saturating_neg())
+    }
+
+    pub fn saturating_abs(self) -> Self {
+        Self(self.0.saturating_abs())
+    }
+
+    /// Returns the absolute value, saturating on overflow.
+    pub fn abs(self) -> Self {
+        self.saturating_abs()
+    }
+
+    /// Rounds towards negative infinity.
+    pub fn floor(self) -> Self {
+        Self(self.0 & !Self::FRAC_MASK)
+    }
+
+    /// Rounds towards positive infinity, saturating on overflow.
+    pub fn ceil(self) -> Self {
+        Self(self.0.saturating_add(Self::FRAC_MASK) & !Self::FRAC_MASK)
+    }
+
+    /// Returns the square root, or `None` if negative. Rounds down.
+    pub fn checked_sqrt(self) -> Option<Self> {
+        if self.0 < 0 {
+            return None;
+        }
+
+        // sqrt(n / 2^F) = sqrt(n * 2^F) / 2^F
+        let root = isqrt((self.0 as u128) << FRAC_BITS);
+        Some(Self(root as i64))
+    }
+
+    /// Returns the square root, or zero if negative. Rounds down.
+    pub fn sqrt(self) -> Self {
+        self.checked_sqrt().unwrap_or(Self::ZERO)
+    }
+
+    /// Returns the sine of the angle in radians.
+    pub fn sin(self) -> Self {
+        Self::from_fixed(sin_turns(self.turns()), TABLE_FRAC_BITS)
+    }
+
+    /// Returns the cosine of the angle in radians.
+    pub fn cos(self) -> Self {
+        let quarter_turn = 1 << 30;
+        Self::from_fixed(
+            sin_turns(self.turns().wrapping_add(quarter_turn)),
+            TABLE_FRAC_BITS,
+        )
+    }
+
+    /// Returns the angle in radians from the positive x axis to the point, between -PI and PI.
+    /// The angle of the origin is zero.
+    pub fn atan2(y: Self, x: Self) -> Self {
+        if x.0 == 0 && y.0 == 0 {
+            return Self::ZERO;
+        }
+
+        let abs_x = (x.0 as i128).abs();
+        let abs_y = (y.0 as i128).abs();
+        let one = 1 << TABLE_FRAC_BITS;
+        let mut angle = if abs_y <= abs_x {
+            lookup(&ATAN, ((abs_y * one) / abs_x) as i64)
+        } else {
+            FRAC_PI_2_Q30 - lookup(&ATAN, ((abs_x * one) / abs_y) as i64)
+        };
+
+        if x.0 < 0 {
+            angle = PI_Q30 - angle;
+        }
+        if y.0 < 0 {
+            angle = -angle;
+        }
+
+        Self::from_fixed(angle, TABLE_FRAC_BITS)
+    }
+
+    /// Converts the angle in radians to turns, where a full turn is `u32::MAX + 1`.
+    fn turns(self) -> u32 {
+        let turns = (self.0 as i128 * INV_TAU_Q64) >> (FRAC_BITS + 64 - 32);
+        // Truncating keeps the fraction of a turn, which is all that matters for a periodic function.
+        turns as u32
+    }
+
+    fn wide_mul(self, rhs: Self) -> i128 {
+        (self.0 as i128 * rhs.0 as i128) >> FRAC_BITS
+    }
+
+    fn wide_div(self, rhs: Self) -> i128 {
+        ((self.0 as i128) << FRAC_BITS) / rhs.0 as i128
+    }
+}
+
+/// Clamps the number to the range of an `i64`.
+const fn saturate(n: i128) -> i64 {
+    if n > i64::MAX as i128 {
+        i64::MAX
+    } else if n < i64::MIN as i128 {
+        i64::MIN
+    } else {
+        n as i64
+    }
+}
+
+/// Integer square root, rounded down.
+fn isqrt(mut n: u128) -> u128 {
+    let mut root = 0;
+    let mut bit = 1 << 126;
+    while bit > n {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if n >= root + bit {
+            n -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+
+    root
+}
+
+/// Linearly interpolates a table, where `position` has `TABLE_FRAC_BITS` fractional bits
+/// and goes from 0 at the first entry to 1 at the last.
+fn lookup(table: &[i32; TABLE_STEPS + 1], position: i64) -> i64 {
+    const STEP_BITS: u32 = TABLE_FRAC_BITS - TABLE_STEPS.trailing_zeros();
+    let index = (position >> STEP_BITS) as usize;
+    if index >= TABLE_STEPS {
+        return table[TABLE_STEPS] as i64;
+    }
+
+    let frac = position & ((1 << STEP_BITS) - 1);
+    let a = table[index] as i64;
+    let b = table[index + 1] as i64;
+    a + (((b - a) * frac) >> STEP_BITS)
+}
+
+/// Returns the sine of the angle in turns, using the quarter turn table.
+fn sin_turns(turns: u32) -> i64 {
+    let quadrant = turns >> 30;
+    let position = (turns & ((1 << 30) - 1)) as i64;
+    match quadrant {
+        0 => lookup(&SIN, position),
+        1 => lookup(&SIN, (1 << 30) - position),
+        2 => -lookup(&SIN, position),
+        _ => -lookup(&SIN, (1 << 30) - position),
+    }
+}
+
+impl<const FRAC_BITS: u32> From<i64> for Fixed<FRAC_BITS> {
+    /// Converts an integer, saturating if it is out of range.
+    fn from(n: i64) -> Self {
+        Self::from_fixed(n, 0)
+    }
+}
+impl<const FRAC_BITS: u32> Add for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.saturating_add(rhs)
+    }
+}
+impl<const FRAC_BITS: u32> AddAssign for Fixed<FRAC_BITS> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+impl<const FRAC_BITS: u32> Sub for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.saturating_sub(rhs)
+    }
+}
+impl<const FRAC_BITS: u32> SubAssign for Fixed<FRAC_BITS> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<const FRAC_BITS: u32> Mul for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.saturating_mul(rhs)
+    }
+}
+impl<const FRAC_BITS: u32> Mul<Fixed<FRAC_BITS>> for i64 {
+    type Output = Fixed<FRAC_BITS>;
+
+    fn mul(self, rhs: Fixed<FRAC_BITS>) -> Self::Output {
+        Fixed(rhs.0.saturating_mul(self))
+    }
+}
+impl<const FRAC_BITS: u32> Mul<i64> for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    fn mul(self, rhs: i64) -> Self::Output {
+        Self(self.0.saturating_mul(rhs))
+    }
+}
+impl<const FRAC_BITS: u32> MulAssign for Fixed<FRAC_BITS> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+impl<const FRAC_BITS: u32> MulAssign<i64> for Fixed<FRAC_BITS> {
+    fn mul_assign(&mut self, rhs: i64) {
+        *self = *self * rhs;
+    }
+}
+
+impl<const FRAC_BITS: u32> Div for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    /// Division by zero saturates, see `saturating_div`.
+    fn div(self, rhs: Self) -> Self::Output {
+        self.saturating_div(rhs)
+    }
+}
+impl<const FRAC_BITS: u32> DivAssign for Fixed<FRAC_BITS> {
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+impl<const FRAC_BITS: u32> Rem for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    /// The remainder of division by zero is zero.
+    fn rem(self, rhs: Self) -> Self::Output {
+        self.checked_rem(rhs).unwrap_or(Self::ZERO)
+    }
+}
+
+impl<const FRAC_BITS: u32> Neg for Fixed<FRAC_BITS> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self.saturating_neg()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::hash::Fnv1a32;
+
+    type D = DNum;
+
+    fn d(f: f32) -> D {
+        D::from_f32(f)
+    }
+
+    fn assert_close(expected: f64, actual: D, tolerance: f64) {
+        let actual = actual.inner() as f64 / D::ONE.inner() as f64;
+        assert!(
+            (expected - actual).abs() <= tolerance,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn mul_assign_assigns() {
+        let mut a: DNum = 10.into();
+        let b: DNum = 120.into();
+        let expected: DNum = (10 * 120).into();
+        a *= b;
+        assert_eq!(expected, a)
+    }
+    #[test]
+    fn mul_assign_assigns_i64() {
+        let mut a: DNum = 10.into();
+        let b: i64 = 120.into();
+        let expected: DNum = (10 * 120).into();
+        a *= b;
+        assert_eq!(expected, a)
+    }
+
+    #[test]
+    fn mul_muls() {
+        let a: DNum = 10.into();
+        let b: DNum = 120.into();
+        let expected: DNum = (10 * 120).into();
+        assert_eq!(expected, a * b)
+    }
+    #[test]
+    fn mul_muls_i64() {
+        let a: DNum = 10.into();
+        let b: i64 = 120.into();
+        let expected: DNum = (10 * 120).into();
+        assert_eq!(expected, a * b)
+    }
+    #[test]
+    fn mul_muls_i64_start() {
+        let a: i64 = 10.into();
+        let b: DNum = 120.into();
+        let expected: DNum = (10 * 120).into();
+        assert_eq!(expected, a * b)
+    }
+
+    #[test]
+    fn add_adds() {
+        let a: DNum = 10.into();
+        let b: DNum = 120.into();
+        let expected: DNum = (10 + 120).into();
+        assert_eq!(expected, a + b)
+    }
+    #[test]
+    fn sub_subs() {
+        let a: DNum = 10.into();
+        let b: DNum = 120.into();
+        let expected: DNum = (10 - 120).into();
+        assert_eq!(expected, a - b)
+    }
+
+    #[test]
+    fn mul_keeps_fractions() {
+        assert_eq!(d(0.75), d(1.5) * d(0.5));
+        assert_eq!(d(-0.75), d(-1.5) * d(0.5));
+    }
+
+    #[test]
+    fn div_divs() {
+        assert_eq!(d(2.5), d(5.0) / d(2.0));
+        assert_eq!(d(-0.25), d(1.0) / d(-4.0));
+        let mut a = d(3.0);
+        a /= d(4.0);
+        assert_eq!(d(0.75), a);
+    }
+
+    #[test]
+    fn div_by_zero_saturates() {
+        assert_eq!(D::MAX, d(1.0) / D::ZERO);
+        assert_eq!(D::MIN, d(-1.0) / D::ZERO);
+        assert_eq!(D::ZERO, D::ZERO / D::ZERO);
+        assert_eq!(None, d(1.0).checked_div(D::ZERO));
+    }
+
+    #[test]
+    fn rem_rems() {
+        assert_eq!(d(1.5), d(5.5) % d(2.0));
+        assert_eq!(d(-1.5), d(-5.5) % d(2.0));
+        assert_eq!(D::ZERO, d(5.5) % D::ZERO);
+        assert_eq!(None, d(5.5).checked_rem(D::ZERO));
+        assert_eq!(Some(D::ZERO), D::MIN.checked_rem(D::from_raw(-1)));
+    }
+
+    #[test]
+    fn neg_and_abs() {
+        assert_eq!(d(-2.5), -d(2.5));
+        assert_eq!(d(2.5), d(-2.5).abs());
+        assert_eq!(D::MAX, -D::MIN);
+        assert_eq!(D::MAX, D::MIN.abs());
+        assert_eq!(None, D::MIN.checked_neg());
+        assert_eq!(None, D::MIN.checked_abs());
+    }
+
+    #[test]
+    fn floor_and_ceil() {
+        assert_eq!(d(1.0), d(1.5).floor());
+        assert_eq!(d(2.0), d(1.5).ceil());
+        assert_eq!(d(-2.0), d(-1.5).floor());
+        assert_eq!(d(-1.0), d(-1.5).ceil());
+        assert_eq!(d(3.0), d(3.0).floor());
+        assert_eq!(d(3.0), d(3.0).ceil());
+        assert_eq!(-2, d(-1.5).to_i64());
+    }
+
+    #[test]
+    fn overflow_saturates() {
+        assert_eq!(D::MAX, D::MAX + D::ONE);
+        assert_eq!(D::MIN, D::MIN - D::ONE);
+        assert_eq!(D::MAX, D::MAX * d(2.0));
+        assert_eq!(D::MIN, D::MAX * d(-2.0));
+        assert_eq!(D::MAX, D::MAX * 2);
+    }
+
+    #[test]
+    fn checked_overflow_returns_none() {
+        assert_eq!(None, D::MAX.checked_add(D::ONE));
+        assert_eq!(None, D::MIN.checked_sub(D::ONE));
+        assert_eq!(None, D::MAX.checked_mul(d(2.0)));
+        assert_eq!(None, D::MAX.checked_div(d(0.5)));
+        assert_eq!(Some(d(3.0)), d(1.0).checked_add(d(2.0)));
+    }
+
+    #[test]
+    fn from_i64_saturates() {
+        assert_eq!(D::MAX, D::from(i64::MAX));
+        assert_eq!(D::MIN, D::from(i64::MIN));
+    }
+
+    #[test]
+    fn f32_round_trip() {
+        assert_eq!(1.5, d(1.5).to_f32());
+        assert_eq!(-0.25, d(-0.25).to_f32());
+        assert_eq!(D::ZERO, d(f32::NAN));
+        assert_eq!(D::MAX, d(f32::INFINITY));
+    }
+
+    #[test]
+    fn sqrt_sqrts() {
+        assert_eq!(d(2.0), d(4.0).sqrt());
+        assert_eq!(d(0.5), d(0.25).sqrt());
+        assert_close(2.0f64.sqrt(), d(2.0).sqrt(), 1.0 / 65536.0);
+        assert_close(12345.0f64.sqrt(), d(12345.0).sqrt(), 1.0 / 65536.0);
+        assert_eq!(D::ZERO, d(-4.0).sqrt());
+        assert_eq!(None, d(-4.0).checked_sqrt());
+        assert!(D::MAX.checked_sqrt().is_some());
+    }
+
+    #[test]
+    fn sin_and_cos_match_floats() {
+        for i in -400..400 {
+            let angle = i as f64 / 50.0;
+            let a = D::from_raw((angle * 65536.0) as i64);
+            assert_close(angle.sin(), a.sin(), 0.0005);
+            assert_close(angle.cos(), a.cos(), 0.0005);
+        }
+    }
+
+    #[test]
+    fn sin_is_exact_at_quarter_turns() {
+        assert_eq!(D::ZERO, D::ZERO.sin());
+        assert_eq!(D::ONE, D::ZERO.cos());
+        assert_close(1.0, D::FRAC_PI_2.sin(), 1.0 / 65536.0);
+        assert_close(-1.0, D::PI.cos(), 1.0 / 65536.0);
+    }
+
+    #[test]
+    fn atan2_matches_floats() {
+        for y in -20..20 {
+            for x in -20..20 {
+                let expected = (y as f64).atan2(x as f64);
+                let actual = D::atan2((y as i64).into(), (x as i64).into());
+                assert_close(expected, actual, 0.0005);
+            }
+        }
+        assert_eq!(D::ZERO, D::atan2(D::ZERO, D::ZERO));
+    }
+
+    #[test]
+    fn atan2_handles_extremes() {
+        assert_close(
+            core::f64::consts::FRAC_PI_2,
+            D::atan2(D::MAX, D::ONE),
+            0.0005,
+        );
+        assert_close(
+            -core::f64::consts::FRAC_PI_2,
+            D::atan2(D::MIN, D::ONE),
+            0.0005,
+        );
+        assert_close(core::f64::consts::PI, D::atan2(D::ZERO, D::MIN), 0.0005);
+    }
+
+    #[test]
+    fn frac_bits_are_configurable() {
+        let a: Fixed<8> = 3.into();
+        let b: Fixed<8> = Fixed::from_f32(0.5);
+        assert_eq!(Fixed::<8>::from_f32(1.5), a * b);
+        assert_eq!(384, (a * b).inner());
+        assert_eq!(core::f32::consts::PI, Fixed::<32>::PI.to_f32());
+    }
+
+    /// Hashes the results of every operation across a range of inputs.
+    /// The expected values are fixed, so running the tests with and without `--release`
+    /// proves the results don't depend on the optimisation level.
+    fn hash_operations<const F: u32>() -> u32 {
+        let mut hasher = Fnv1a32::new();
+        let mut write = |n: Fixed<F>| hasher.write(&n.inner().to_le_bytes());
+        let mut a = Fixed::<F>::from_raw(-7 << F);
+        let step = Fixed::<F>::from_raw((1 << F) / 3 + 17);
+        for _ in 0..64 {
+            let b = a * a - step;
+            write(a * b);
+            write(a / b);
+            write(b / a);
+            write(a % step);
+            write(-a);
+            write(a.abs());
+            write(a.floor());
+            write(a.ceil());
+            write(a.sqrt());
+            write(a.sin());
+            write(a.cos());
+            write(Fixed::atan2(a, b));
+            write(Fixed::MAX.saturating_mul(a));
+            write(Fixed::MIN / a);
+            a += step;
+        }
+
+        hasher.finish()
+    }
+
+    #[test]
+    fn results_are_identical_across_builds() {
+        assert_eq!(3308466878, hash_operations::<16>());
+        assert_eq!(1254499381, hash_operations::<32>());
+        assert_eq!(1510566066, hash_operations::<8>());
+    }
+}
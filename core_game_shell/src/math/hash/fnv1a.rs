@@ -0,0 +1,64 @@
+const OFFSET_BASIS: u32 = 2166136261;
+const PRIME: u32 = 16777619;
+
+/// FNV1A hash function.
+/// https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+pub fn fnv1a_32(bytes: &[u8]) -> u32 {
+    let mut hasher = Fnv1a32::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Incremental FNV1A hasher, for hashing data that isn't in a single slice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fnv1a32(u32);
+impl Fnv1a32 {
+    /// Creates a new hasher.
+    pub fn new() -> Self {
+        Self(OFFSET_BASIS)
+    }
+
+    /// Adds the bytes to the hash.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u32;
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    /// Returns the hash of all bytes written.
+    pub fn finish(&self) -> u32 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case1() {
+        let bytes = "hello world!".as_bytes();
+        assert_eq!(2956263410, fnv1a_32(bytes))
+    }
+
+    #[test]
+    fn case2() {
+        let bytes = "foo bar!".as_bytes();
+        assert_eq!(1510564049, fnv1a_32(bytes))
+    }
+
+    #[test]
+    fn case3() {
+        let bytes = "herp".as_bytes();
+        assert_eq!(876315572, fnv1a_32(bytes))
+    }
+
+    #[test]
+    fn incremental_matches_single_slice() {
+        let mut hasher = Fnv1a32::new();
+        hasher.write("hello ".as_bytes());
+        hasher.write("world!".as_bytes());
+        assert_eq!(fnv1a_32("hello world!".as_bytes()), hasher.finish())
+    }
+}
@@ -0,0 +1,3 @@
+mod fnv1a;
+
+pub use fnv1a::*;
@@ -1,4 +1,5 @@
 mod dnum;
+pub mod hash;
 mod normalized_float;
 pub mod sequences;
 
@@ -1,17 +1,48 @@
 /// Checks if a wrapping sequence is after another.
 /// See https://stackoverflow.com/questions/2594423/whats-a-good-way-to-detect-wrap-around-in-a-fixed-width-message-counter
 pub fn sequence_a_after_b_u8(a: u8, b: u8) -> bool {
-    const MAX: u8 = u8::MAX;
-    let a_size = a.wrapping_sub(b) % MAX;
-    let b_size = b.wrapping_sub(a) % MAX;
-    a_size > b_size
+    // The distance travelled forward from b to a is shorter than from a to b.
+    let a_size = a.wrapping_sub(b);
+    let b_size = b.wrapping_sub(a);
+    a_size < b_size
 }
 
 /// Checks if a wrapping sequence is after another.
 /// See https://stackoverflow.com/questions/2594423/whats-a-good-way-to-detect-wrap-around-in-a-fixed-width-message-counter
 pub fn sequence_a_after_b_u16(a: u16, b: u16) -> bool {
-    const MAX: u16 = u16::MAX;
-    let a_size = a.wrapping_sub(b) % MAX;
-    let b_size = b.wrapping_sub(a) % MAX;
-    a_size > b_size
+    // The distance travelled forward from b to a is shorter than from a to b.
+    let a_size = a.wrapping_sub(b);
+    let b_size = b.wrapping_sub(a);
+    a_size < b_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u8_later_is_after() {
+        assert!(sequence_a_after_b_u8(5, 3));
+        assert!(!sequence_a_after_b_u8(3, 5));
+        assert!(!sequence_a_after_b_u8(3, 3));
+    }
+
+    #[test]
+    fn u16_later_is_after() {
+        assert!(sequence_a_after_b_u16(5, 3));
+        assert!(!sequence_a_after_b_u16(3, 5));
+        assert!(!sequence_a_after_b_u16(3, 3));
+    }
+
+    #[test]
+    fn u8_handles_wrap_around() {
+        assert!(sequence_a_after_b_u8(0, u8::MAX));
+        assert!(!sequence_a_after_b_u8(u8::MAX, 0));
+    }
+
+    #[test]
+    fn u16_handles_wrap_around() {
+        assert!(sequence_a_after_b_u16(0, u16::MAX));
+        assert!(!sequence_a_after_b_u16(u16::MAX, 0));
+    }
 }
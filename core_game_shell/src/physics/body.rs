@@ -0,0 +1,86 @@
+use crate::{aabb::Aabb2d, math::DNum};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BodyKind {
+    /// Never moves.
+    Static,
+    /// Moved only by its velocity. Pushes dynamic bodies but is never pushed itself.
+    Kinematic,
+    /// Moved by its velocity, gravity and collisions.
+    Dynamic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BodyId(u16);
+impl BodyId {
+    /// Returns the index of the body.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+impl From<u16> for BodyId {
+    fn from(id: u16) -> Self {
+        Self(id)
+    }
+}
+
+/// An axis aligned box in the physics world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Body {
+    pub kind: BodyKind,
+    /// The center of the body.
+    pub position: [DNum; 2],
+    /// Half the width and height of the body.
+    pub half_size: [DNum; 2],
+    pub velocity: [DNum; 2],
+}
+impl Body {
+    /// Creates a new body at rest.
+    pub fn new(kind: BodyKind, position: [DNum; 2], half_size: [DNum; 2]) -> Self {
+        Self {
+            kind,
+            position,
+            half_size,
+            velocity: [DNum::ZERO; 2],
+        }
+    }
+
+    /// Returns the bounds of the body.
+    pub fn aabb(&self) -> Aabb2d<DNum> {
+        Aabb2d {
+            min: [
+                self.position[0] - self.half_size[0],
+                self.position[1] - self.half_size[1],
+            ],
+            max: [
+                self.position[0] + self.half_size[0],
+                self.position[1] + self.half_size[1],
+            ],
+        }
+    }
+
+    /// Returns how much of a collision response the body takes.
+    pub(crate) fn response_weight(&self) -> i64 {
+        match self.kind {
+            BodyKind::Dynamic => 1,
+            BodyKind::Static | BodyKind::Kinematic => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aabb_is_centered_on_position() {
+        let body = Body::new(
+            BodyKind::Static,
+            [2.into(), 3.into()],
+            [1.into(), DNum::from_f32(0.5)],
+        );
+        let aabb = body.aabb();
+        assert_eq!([1.into(), DNum::from_f32(2.5)], aabb.min);
+        assert_eq!([3.into(), DNum::from_f32(3.5)], aabb.max);
+    }
+}
@@ -0,0 +1,165 @@
+use super::{Body, BodyId, MAX_BODIES};
+use crate::{aabb::Aabb2d, math::DNum};
+
+/// Sort and sweep broadphase along the x axis.
+/// The order is kept between steps, so sorting is close to linear when bodies move a little each step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SortAndSweep {
+    order: [u16; MAX_BODIES],
+    len: usize,
+    bounds: [Aabb2d<DNum>; MAX_BODIES],
+}
+impl SortAndSweep {
+    /// Creates a new empty broadphase.
+    pub fn new() -> Self {
+        Self {
+            order: [0; MAX_BODIES],
+            len: 0,
+            bounds: [Aabb2d {
+                min: [DNum::ZERO; 2],
+                max: [DNum::ZERO; 2],
+            }; MAX_BODIES],
+        }
+    }
+
+    /// Updates the bounds and order for the bodies.
+    pub fn update(&mut self, bodies: &[Option<Body>; MAX_BODIES]) {
+        // Drop removed bodies, keeping the existing order.
+        let mut tracked = [false; MAX_BODIES];
+        let mut len = 0;
+        for i in 0..self.len {
+            let index = self.order[i] as usize;
+            if let Some(body) = &bodies[index] {
+                self.order[len] = index as u16;
+                self.bounds[index] = body.aabb();
+                tracked[index] = true;
+                len += 1;
+            }
+        }
+
+        // Append new bodies.
+        for (index, body) in bodies.iter().enumerate() {
+            if let (Some(body), false) = (body, tracked[index]) {
+                self.order[len] = index as u16;
+                self.bounds[index] = body.aabb();
+                len += 1;
+            }
+        }
+        self.len = len;
+
+        // Insertion sort, breaking ties by index so the order never depends on history.
+        for i in 1..self.len {
+            let mut j = i;
+            while j > 0 && self.key(self.order[j]) < self.key(self.order[j - 1]) {
+                self.order.swap(j, j - 1);
+                j -= 1;
+            }
+        }
+    }
+
+    fn key(&self, index: u16) -> (DNum, u16) {
+        (self.bounds[index as usize].min[0], index)
+    }
+
+    /// Calls `on_pair` for every pair of bodies whose bounds overlap, as of the last update.
+    pub fn for_each_pair<F>(&self, mut on_pair: F)
+    where
+        F: FnMut(BodyId, BodyId),
+    {
+        for i in 0..self.len {
+            let a = self.order[i];
+            let a_bounds = &self.bounds[a as usize];
+
+            for &b in &self.order[i + 1..self.len] {
+                let b_bounds = &self.bounds[b as usize];
+                // Sorted by min x, so nothing further along can overlap.
+                if b_bounds.min[0] >= a_bounds.max[0] {
+                    break;
+                }
+
+                if a_bounds.min[1] < b_bounds.max[1] && b_bounds.min[1] < a_bounds.max[1] {
+                    on_pair(a.into(), b.into());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::BodyKind;
+
+    fn bodies() -> [Option<Body>; MAX_BODIES] {
+        let mut bodies = [None; MAX_BODIES];
+        for i in 0..40 {
+            let x = (i * 7 % 13) as i64;
+            let y = (i * 5 % 11) as i64;
+            let half = DNum::from_raw(DNum::ONE.inner() * (1 + i % 3) as i64 / 2);
+            bodies[i * 2] = Some(Body::new(
+                BodyKind::Dynamic,
+                [x.into(), y.into()],
+                [half, half],
+            ));
+        }
+        bodies
+    }
+
+    fn pairs(broadphase: &SortAndSweep) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        broadphase
+            .for_each_pair(|a, b| pairs.push((a.index().min(b.index()), a.index().max(b.index()))));
+        pairs.sort();
+        pairs
+    }
+
+    fn brute_force(bodies: &[Option<Body>; MAX_BODIES]) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (i, a) in bodies.iter().enumerate() {
+            for (j, b) in bodies.iter().enumerate().skip(i + 1) {
+                if let (Some(a), Some(b)) = (a, b) {
+                    if a.aabb().check_collision(&b.aabb()).is_some() {
+                        pairs.push((i, j));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn finds_same_pairs_as_brute_force() {
+        let bodies = bodies();
+        let mut broadphase = SortAndSweep::new();
+        broadphase.update(&bodies);
+
+        let expected = brute_force(&bodies);
+        assert!(!expected.is_empty());
+        assert_eq!(expected, pairs(&broadphase));
+    }
+
+    #[test]
+    fn tracks_moved_added_and_removed_bodies() {
+        let mut bodies = bodies();
+        let mut broadphase = SortAndSweep::new();
+        broadphase.update(&bodies);
+
+        for (i, body) in bodies.iter_mut().enumerate() {
+            match body {
+                Some(body) => body.position[0] += ((i % 5) as i64).into(),
+                None if i % 3 == 0 => {
+                    *body = Some(Body::new(
+                        BodyKind::Static,
+                        [((i % 9) as i64).into(), 4.into()],
+                        [DNum::ONE; 2],
+                    ))
+                }
+                None => {}
+            }
+        }
+        bodies[4] = None;
+        broadphase.update(&bodies);
+
+        assert_eq!(brute_force(&bodies), pairs(&broadphase));
+    }
+}
@@ -0,0 +1,6 @@
+mod body;
+mod broadphase;
+mod world;
+
+pub use body::*;
+pub use world::*;
@@ -0,0 +1,316 @@
+use super::{broadphase::SortAndSweep, Body, BodyId, BodyKind};
+use crate::{aabb::Manifold, math::DNum};
+
+/// The maximum number of bodies a world can hold.
+pub const MAX_BODIES: usize = 128;
+/// The maximum number of contacts recorded each step. Further contacts are still resolved.
+pub const MAX_CONTACTS: usize = 256;
+
+/// A collision that was resolved during the last step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub a: BodyId,
+    pub b: BodyId,
+    /// The normal points from `a` towards `b`.
+    pub manifold: Manifold<DNum>,
+}
+
+/// Deterministic physics world.
+/// All state is plain data, so it can be copied for rollback.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct World {
+    gravity: [DNum; 2],
+    bodies: [Option<Body>; MAX_BODIES],
+    broadphase: SortAndSweep,
+    contacts: [Option<Contact>; MAX_CONTACTS],
+}
+impl World {
+    /// Creates a new empty world.
+    pub fn new(gravity: [DNum; 2]) -> Self {
+        Self {
+            gravity,
+            bodies: [None; MAX_BODIES],
+            broadphase: SortAndSweep::new(),
+            contacts: [None; MAX_CONTACTS],
+        }
+    }
+
+    /// Adds a body to the world. Returns `None` if the world is full.
+    pub fn add_body(&mut self, body: Body) -> Option<BodyId> {
+        let index = self.bodies.iter().position(|b| b.is_none())?;
+        self.bodies[index] = Some(body);
+        Some((index as u16).into())
+    }
+
+    /// Removes a body from the world, returning it.
+    pub fn remove_body(&mut self, id: BodyId) -> Option<Body> {
+        self.bodies.get_mut(id.index())?.take()
+    }
+
+    pub fn body(&self, id: BodyId) -> Option<&Body> {
+        self.bodies.get(id.index())?.as_ref()
+    }
+
+    pub fn body_mut(&mut self, id: BodyId) -> Option<&mut Body> {
+        self.bodies.get_mut(id.index())?.as_mut()
+    }
+
+    /// Returns all bodies in the world.
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyId, &Body)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.as_ref().map(|b| ((i as u16).into(), b)))
+    }
+
+    /// Returns the contacts resolved during the last step.
+    pub fn contacts(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.iter().filter_map(|c| c.as_ref())
+    }
+
+    /// Advances the world by the given time.
+    pub fn step(&mut self, delta_t: DNum) {
+        self.integrate(delta_t);
+
+        self.broadphase.update(&self.bodies);
+        self.contacts = [None; MAX_CONTACTS];
+
+        let bodies = &mut self.bodies;
+        let contacts = &mut self.contacts;
+        let mut contact_count = 0;
+        self.broadphase.for_each_pair(|a, b| {
+            if let Some(manifold) = resolve(bodies, a, b) {
+                if contact_count < MAX_CONTACTS {
+                    contacts[contact_count] = Some(Contact { a, b, manifold });
+                    contact_count += 1;
+                }
+            }
+        });
+    }
+
+    /// Applies gravity and velocity using semi-implicit Euler.
+    fn integrate(&mut self, delta_t: DNum) {
+        for body in self.bodies.iter_mut().filter_map(|b| b.as_mut()) {
+            if body.kind == BodyKind::Dynamic {
+                for axis in 0..2 {
+                    body.velocity[axis] += self.gravity[axis] * delta_t;
+                }
+            }
+
+            if body.kind != BodyKind::Static {
+                for axis in 0..2 {
+                    body.position[axis] += body.velocity[axis] * delta_t;
+                }
+            }
+        }
+    }
+}
+
+/// Separates the two bodies along the normal and removes their approaching velocity.
+/// Returns the manifold if they collided.
+fn resolve(
+    bodies: &mut [Option<Body>; MAX_BODIES],
+    a_id: BodyId,
+    b_id: BodyId,
+) -> Option<Manifold<DNum>> {
+    let mut a = bodies[a_id.index()]?;
+    let mut b = bodies[b_id.index()]?;
+
+    // Static and kinematic bodies never respond, so there's nothing to resolve between them.
+    let a_weight = a.response_weight();
+    let b_weight = b.response_weight();
+    let total_weight: DNum = (a_weight + b_weight).into();
+    if total_weight == DNum::ZERO {
+        return None;
+    }
+
+    let manifold = a.aabb().check_collision(&b.aabb())?;
+    let axis = if manifold.normal[0] != DNum::ZERO {
+        0
+    } else {
+        1
+    };
+    let direction = manifold.normal[axis];
+
+    // Split the separation between the bodies that respond.
+    let separation = manifold.depth * direction;
+    a.position[axis] -= separation * a_weight / total_weight;
+    b.position[axis] += separation * b_weight / total_weight;
+
+    // Only stop the bodies if they're moving towards each other.
+    let a_velocity = a.velocity[axis];
+    let b_velocity = b.velocity[axis];
+    if (b_velocity - a_velocity) * direction < DNum::ZERO {
+        let velocity = match (a_weight, b_weight) {
+            (0, _) => a_velocity,
+            (_, 0) => b_velocity,
+            _ => (a_velocity + b_velocity) / 2.into(),
+        };
+
+        if a_weight > 0 {
+            a.velocity[axis] = velocity;
+        }
+        if b_weight > 0 {
+            b.velocity[axis] = velocity;
+        }
+    }
+
+    bodies[a_id.index()] = Some(a);
+    bodies[b_id.index()] = Some(b);
+    Some(manifold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(f: f32) -> DNum {
+        DNum::from_f32(f)
+    }
+
+    fn delta_t() -> DNum {
+        DNum::ONE / 60.into()
+    }
+
+    fn gravity() -> [DNum; 2] {
+        [DNum::ZERO, (-10).into()]
+    }
+
+    fn floor() -> Body {
+        Body::new(
+            BodyKind::Static,
+            [0.into(), 0.into()],
+            [20.into(), 1.into()],
+        )
+    }
+
+    fn crate_at(x: f32, y: f32) -> Body {
+        Body::new(BodyKind::Dynamic, [n(x), n(y)], [1.into(), 1.into()])
+    }
+
+    #[test]
+    fn dynamic_body_falls() {
+        let mut world = World::new(gravity());
+        let id = world.add_body(crate_at(0.0, 10.0)).unwrap();
+        for _ in 0..60 {
+            world.step(delta_t());
+        }
+
+        let body = world.body(id).unwrap();
+        assert!(body.velocity[1] < n(-9.9));
+        assert!(body.position[1] < n(6.0));
+    }
+
+    #[test]
+    fn dynamic_body_rests_on_static_floor() {
+        let mut world = World::new(gravity());
+        let floor = world.add_body(floor()).unwrap();
+        let id = world.add_body(crate_at(0.0, 4.0)).unwrap();
+        for _ in 0..180 {
+            world.step(delta_t());
+        }
+
+        let body = world.body(id).unwrap();
+        assert!((body.position[1] - 2.into()).abs() < n(0.01));
+        assert_eq!(DNum::ZERO, body.velocity[1]);
+        assert_eq!([DNum::ZERO; 2], world.body(floor).unwrap().position);
+
+        let contact = world.contacts().next().unwrap();
+        assert_eq!((floor, id), (contact.a, contact.b));
+        assert_eq!([DNum::ZERO, DNum::ONE], contact.manifold.normal);
+    }
+
+    #[test]
+    fn kinematic_body_pushes_dynamic_body() {
+        let mut world = World::new([DNum::ZERO; 2]);
+        let mut paddle = Body::new(
+            BodyKind::Kinematic,
+            [0.into(), 0.into()],
+            [1.into(), 1.into()],
+        );
+        paddle.velocity = [5.into(), 0.into()];
+        let paddle = world.add_body(paddle).unwrap();
+        let ball = world.add_body(crate_at(3.0, 0.0)).unwrap();
+        for _ in 0..60 {
+            world.step(delta_t());
+        }
+
+        let paddle = world.body(paddle).unwrap();
+        let ball = world.body(ball).unwrap();
+        assert_eq!([n(5.0), DNum::ZERO], paddle.velocity);
+        assert!(ball.position[0] - paddle.position[0] >= n(1.99));
+        assert_eq!(paddle.velocity, ball.velocity);
+    }
+
+    #[test]
+    fn static_and_kinematic_bodies_pass_through_each_other() {
+        let mut world = World::new([DNum::ZERO; 2]);
+        let mut ghost = Body::new(
+            BodyKind::Kinematic,
+            [0.into(), 0.into()],
+            [1.into(), 1.into()],
+        );
+        ghost.velocity = [0.into(), (-60).into()];
+        let ghost = world.add_body(ghost).unwrap();
+        world.add_body(floor()).unwrap();
+        world.step(delta_t());
+
+        assert_eq!(None, world.contacts().next());
+        assert!(world.body(ghost).unwrap().position[1] < n(-0.99));
+    }
+
+    #[test]
+    fn dynamic_bodies_share_separation() {
+        let mut world = World::new([DNum::ZERO; 2]);
+        let a = world.add_body(crate_at(0.0, 0.0)).unwrap();
+        let b = world.add_body(crate_at(1.0, 0.0)).unwrap();
+        world.body_mut(a).unwrap().velocity = [2.into(), 0.into()];
+        world.step(delta_t());
+
+        let a = world.body(a).unwrap();
+        let b = world.body(b).unwrap();
+        assert_eq!(None, a.aabb().check_collision(&b.aabb()));
+        assert!((b.position[0] - a.position[0] - 2.into()).abs() <= DNum::EPSILON);
+        assert_eq!(a.velocity, b.velocity);
+        assert_eq!([DNum::ONE, DNum::ZERO], a.velocity);
+    }
+
+    #[test]
+    fn world_is_copyable_for_rollback() {
+        let mut world = World::new(gravity());
+        world.add_body(floor()).unwrap();
+        for i in 0..10 {
+            world
+                .add_body(crate_at(i as f32 * 1.5 - 7.0, 3.0 + i as f32 * 2.1))
+                .unwrap();
+        }
+        for _ in 0..30 {
+            world.step(delta_t());
+        }
+
+        let snapshot = world;
+        for _ in 0..60 {
+            world.step(delta_t());
+        }
+
+        let mut restored = snapshot;
+        for _ in 0..60 {
+            restored.step(delta_t());
+        }
+        assert_eq!(world, restored);
+        assert!(world.contacts().count() > 1);
+    }
+
+    #[test]
+    fn add_body_fails_when_full() {
+        let mut world = World::new(gravity());
+        for _ in 0..MAX_BODIES {
+            assert!(world.add_body(floor()).is_some());
+        }
+        assert_eq!(None, world.add_body(floor()));
+
+        let removed = world.remove_body(3.into());
+        assert_eq!(Some(floor()), removed);
+        assert_eq!(Some(3.into()), world.add_body(floor()));
+    }
+}
@@ -1,100 +1,366 @@
-use crate::math::{map_range, NormalizedF32};
+use crate::{
+    bytes::{ByteReader, ByteWriter, SliceWriter},
+    math::NormalizedF32,
+};
 
-type N = u32;
+type N = u64;
 
 /*
 Use a u64 to represent all bytes.
 This way it can be sent over the wire easily.
-*/
 
-const FLAG_EMPTY: N = 0b0000_0000_0000_0000;
-const AXIS_FLAG: N = 0b1111;
+Bits 0-31 hold the axes, 8 bits each, as signed values scaled by the precision.
+Bits 32-47 hold the buttons, 1 bit each.
+Bit 48 is set if the axes use high precision.
+*/
 
-const PRIMARY_X_AXIS_INDEX: N = 0;
-const PRIMARY_X_AXIS: N = AXIS_FLAG << PRIMARY_X_AXIS_INDEX;
+const AXIS_BITS: u32 = 8;
+const AXIS_FLAG: N = 0xFF;
 
-const PRIMARY_Y_AXIS_INDEX: N = 1 * 4;
-const PRIMARY_Y_AXIS: N = AXIS_FLAG << PRIMARY_Y_AXIS_INDEX;
+const PRIMARY_X_AXIS_INDEX: u32 = 0;
+const PRIMARY_Y_AXIS_INDEX: u32 = AXIS_BITS;
+const SECONDARY_X_AXIS_INDEX: u32 = 2 * AXIS_BITS;
+const SECONDARY_Y_AXIS_INDEX: u32 = 3 * AXIS_BITS;
+const AXIS_INDEXES: [u32; 4] = [
+    PRIMARY_X_AXIS_INDEX,
+    PRIMARY_Y_AXIS_INDEX,
+    SECONDARY_X_AXIS_INDEX,
+    SECONDARY_Y_AXIS_INDEX,
+];
 
-const SECONDARY_X_AXIS_INDEX: N = 2 * 4;
-const SECONDARY_X_AXIS: N = AXIS_FLAG << SECONDARY_X_AXIS_INDEX;
+const BUTTONS_INDEX: u32 = 32;
+const BUTTONS_FLAG: N = 0xFFFF << BUTTONS_INDEX;
 
-const SECONDARY_Y_AXIS_INDEX: N = 3 * 4;
-const SECONDARY_Y_AXIS: N = AXIS_FLAG << SECONDARY_Y_AXIS_INDEX;
+const HIGH_PRECISION_FLAG: N = 1 << 48;
 
-/// The maximum value that can be held in 4 bits
-const AXIS_MIN_VALUE: f32 = 0.0;
-/// The minimum value that can be held in 4 bits
-const AXIS_MAX_VALUE: f32 = 15.0;
+/// All bits that are part of the layout.
+const USED_FLAGS: N = HIGH_PRECISION_FLAG | BUTTONS_FLAG | 0xFFFF_FFFF;
 
-const CLAMP_VALUE: f32 = 0.07;
+/// The version of the wire format, stored in the high nibble of the first byte.
+const WIRE_VERSION: u8 = 1;
+/// Set in the low nibble of the first byte if the axes use high precision.
+const WIRE_HIGH_PRECISION_FLAG: u8 = 0b0001;
 
 macro_rules! make_input {
-    ($get_id:ident,$set_id:ident => $index:expr, $flag:expr) => {
+    ($get_id:ident,$set_id:ident,$steps_id:ident => $index:expr) => {
         pub fn $set_id(&mut self, value: NormalizedF32) {
-            self.set_axis($index, $flag, value)
+            self.set_axis($index, value)
         }
 
         pub fn $get_id(&self) -> NormalizedF32 {
-            self.get_axis($index, $flag)
+            self.get_axis($index)
+        }
+
+        /// Returns the axis as it's stored, from minus to plus the precision's `max_value`.
+        pub fn $steps_id(&self) -> i8 {
+            self.axis_value($index)
         }
     };
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AxisPrecision {
+    /// 4 bits per axis, with 15 distinct values.
+    Low,
+    /// 8 bits per axis, with 255 distinct values.
+    High,
+}
+impl AxisPrecision {
+    /// Returns the largest value an axis can be stored as.
+    pub fn max_value(&self) -> i8 {
+        match self {
+            Self::Low => 7,
+            Self::High => 127,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    A,
+    B,
+    X,
+    Y,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    LeftStick,
+    RightStick,
+    Start,
+    Select,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+impl Button {
+    pub const ALL: [Button; 16] = [
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::LeftTrigger,
+        Button::RightTrigger,
+        Button::LeftStick,
+        Button::RightStick,
+        Button::Start,
+        Button::Select,
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+    ];
+
+    fn flag(&self) -> N {
+        1 << (BUTTONS_INDEX + *self as u32)
+    }
+}
+
+/// The state of a button compared to the previous frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ButtonState {
+    /// Not held on either frame.
+    Up,
+    /// Held on this frame but not the previous one.
+    Pressed,
+    /// Held on both frames.
+    Held,
+    /// Held on the previous frame but not this one.
+    Released,
+}
+impl ButtonState {
+    /// Returns whether the button is held on the current frame.
+    pub fn is_down(&self) -> bool {
+        matches!(self, Self::Pressed | Self::Held)
+    }
+}
+
+/// Ignores small axis movements, such as from a stick that doesn't fully center.
+/// Should be applied before setting an axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadzone(f32);
+impl Deadzone {
+    /// Creates a deadzone covering the given amount of the axis, from 0 to 1.
+    pub fn new(size: f32) -> Self {
+        Self(size.clamp(0.0, 1.0))
+    }
+
+    /// Zeroes values inside the deadzone, rescaling the rest so the full range is still reachable.
+    pub fn apply(&self, value: NormalizedF32) -> NormalizedF32 {
+        let v = value.inner();
+        let magnitude = if v < 0.0 { -v } else { v };
+        if magnitude <= self.0 {
+            return NormalizedF32::ZERO;
+        }
+
+        let scaled = (magnitude - self.0) / (1.0 - self.0);
+        (if v < 0.0 { -scaled } else { scaled }).into()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct PlayerInput(N);
 impl PlayerInput {
-    make_input!(get_primary_x_axis, set_primary_x_axis => PRIMARY_X_AXIS_INDEX, PRIMARY_X_AXIS);
-    make_input!(get_primary_y_axis, set_primary_y_axis => PRIMARY_Y_AXIS_INDEX, PRIMARY_Y_AXIS);
+    make_input!(get_primary_x_axis, set_primary_x_axis, primary_x_axis_steps => PRIMARY_X_AXIS_INDEX);
+    make_input!(get_primary_y_axis, set_primary_y_axis, primary_y_axis_steps => PRIMARY_Y_AXIS_INDEX);
+
+    make_input!(get_secondary_x_axis, set_secondary_x_axis, secondary_x_axis_steps => SECONDARY_X_AXIS_INDEX);
+    make_input!(get_secondary_y_axis, set_secondary_y_axis, secondary_y_axis_steps => SECONDARY_Y_AXIS_INDEX);
 
-    make_input!(get_secondary_x_axis, set_secondary_x_axis => SECONDARY_X_AXIS_INDEX, SECONDARY_X_AXIS);
-    make_input!(get_secondary_y_axis, set_secondary_y_axis => SECONDARY_Y_AXIS_INDEX, SECONDARY_Y_AXIS);
+    /// The maximum number of bytes a serialized input takes.
+    pub const MAX_SIZE: usize = 7;
 
-    /// Creates a new instance of a player input.
+    /// Creates a new instance of a player input, with centered axes at low precision and no buttons held.
     pub fn new() -> Self {
-        let mut i = Self(0);
+        Self(0)
+    }
 
-        i.set_primary_x_axis(NormalizedF32::ZERO);
+    /// Returns the packed representation of the input.
+    pub fn inner(&self) -> N {
+        self.0
+    }
 
-        i
+    pub fn precision(&self) -> AxisPrecision {
+        if self.0 & HIGH_PRECISION_FLAG == 0 {
+            AxisPrecision::Low
+        } else {
+            AxisPrecision::High
+        }
+    }
+
+    /// Sets the precision of the axes, requantizing any that are already set.
+    pub fn set_precision(&mut self, precision: AxisPrecision) {
+        let axes = AXIS_INDEXES.map(|index| self.get_axis(index));
+
+        match precision {
+            AxisPrecision::Low => self.0 &= !HIGH_PRECISION_FLAG,
+            AxisPrecision::High => self.0 |= HIGH_PRECISION_FLAG,
+        }
+
+        for (index, value) in AXIS_INDEXES.iter().zip(axes) {
+            self.set_axis(*index, value);
+        }
+    }
+
+    /// Returns whether the button is held.
+    pub fn is_held(&self, button: Button) -> bool {
+        self.0 & button.flag() != 0
+    }
+
+    pub fn set_button(&mut self, button: Button, held: bool) {
+        if held {
+            self.0 |= button.flag();
+        } else {
+            self.0 &= !button.flag();
+        }
+    }
+
+    /// Returns all buttons as bits, ordered as in `Button::ALL`.
+    pub fn buttons(&self) -> u16 {
+        ((self.0 & BUTTONS_FLAG) >> BUTTONS_INDEX) as u16
+    }
+
+    fn set_buttons(&mut self, buttons: u16) {
+        self.0 = self.0 & !BUTTONS_FLAG | (buttons as N) << BUTTONS_INDEX;
+    }
+
+    /// Returns whether the button was pressed, held or released since the previous frame's input.
+    pub fn button_state(&self, previous: &Self, button: Button) -> ButtonState {
+        match (previous.is_held(button), self.is_held(button)) {
+            (false, false) => ButtonState::Up,
+            (false, true) => ButtonState::Pressed,
+            (true, true) => ButtonState::Held,
+            (true, false) => ButtonState::Released,
+        }
+    }
+
+    /// Gets the stored value of an axis.
+    fn axis_value(&self, index: u32) -> i8 {
+        ((self.0 >> index) & AXIS_FLAG) as u8 as i8
+    }
+
+    fn set_axis_value(&mut self, index: u32, value: i8) {
+        self.0 = self.0 & !(AXIS_FLAG << index) | (value as u8 as N) << index;
     }
 
     /// Gets some axis bits.
-    fn get_axis(&self, index: N, flag: N) -> NormalizedF32 {
-        let value = self.0 & flag >> index;
-        let value_f32 = value as f32;
-        let mapped_value: f32 = map_range(
-            value_f32,
-            AXIS_MIN_VALUE,
-            AXIS_MAX_VALUE,
-            NormalizedF32::MIN.inner(),
-            NormalizedF32::MAX.inner(),
-        );
-
-        let should_clamp_due_to_rounding_errors = mapped_value <= CLAMP_VALUE && mapped_value > 0.0
-            || mapped_value < 0.0 && mapped_value >= -CLAMP_VALUE;
-
-        (if should_clamp_due_to_rounding_errors {
-            0.0
+    fn get_axis(&self, index: u32) -> NormalizedF32 {
+        let max = self.precision().max_value();
+        (self.axis_value(index) as f32 / max as f32).into()
+    }
+
+    /// Sets some axis bits, rounding to the nearest value the precision can hold.
+    fn set_axis(&mut self, index: u32, value: NormalizedF32) {
+        let scaled = value.inner() * self.precision().max_value() as f32;
+        // Casting truncates, so offset by a half to round away from zero.
+        let rounded = if scaled < 0.0 {
+            scaled - 0.5
         } else {
-            mapped_value
-        })
-        .into()
+            scaled + 0.5
+        };
+
+        self.set_axis_value(index, rounded as i8);
+    }
+
+    /// Writes the input in the versioned wire format.
+    /// Low precision axes are packed as nibbles, so take less space.
+    pub fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        let precision = self.precision();
+        let flags = match precision {
+            AxisPrecision::Low => 0,
+            AxisPrecision::High => WIRE_HIGH_PRECISION_FLAG,
+        };
+        writer.write_u8(WIRE_VERSION << 4 | flags);
+
+        match precision {
+            AxisPrecision::Low => {
+                let nibbles = AXIS_INDEXES.map(|index| self.axis_value(index) as u8 & 0x0F);
+                writer.write_u8(nibbles[0] | nibbles[1] << 4);
+                writer.write_u8(nibbles[2] | nibbles[3] << 4);
+            }
+            AxisPrecision::High => {
+                for index in AXIS_INDEXES {
+                    writer.write_u8(self.axis_value(index) as u8);
+                }
+            }
+        }
+
+        writer.write_u16(self.buttons());
+    }
+
+    /// Reads an input that was written with `write_bytes`.
+    /// Returns `None` for unknown versions or values the precision can't hold.
+    pub fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let header = reader.read_u8()?;
+        if header >> 4 != WIRE_VERSION || header & 0x0F & !WIRE_HIGH_PRECISION_FLAG != 0 {
+            return None;
+        }
+
+        let mut input = Self::new();
+        let mut values = [0; 4];
+        if header & WIRE_HIGH_PRECISION_FLAG == 0 {
+            for pair in values.chunks_exact_mut(2) {
+                let byte = reader.read_u8()?;
+                // Shift each nibble to the top of the byte then back down to sign extend it.
+                pair[0] = ((byte << 4) as i8) >> 4;
+                pair[1] = (byte as i8) >> 4;
+            }
+        } else {
+            input.0 |= HIGH_PRECISION_FLAG;
+            for value in values.iter_mut() {
+                *value = reader.read_u8()? as i8;
+            }
+        }
+
+        let max = input.precision().max_value();
+        for (index, value) in AXIS_INDEXES.iter().zip(values) {
+            if value < -max || value > max {
+                return None;
+            }
+            input.set_axis_value(*index, value);
+        }
+
+        input.set_buttons(reader.read_u16()?);
+        Some(input)
     }
 
-    /// Sets some axis bits.
-    fn set_axis(&mut self, index: N, flag: N, value: NormalizedF32) {
-        let four_bit_f32 = map_range(
-            value.inner(),
-            NormalizedF32::MIN.inner(),
-            NormalizedF32::MAX.inner(),
-            AXIS_MIN_VALUE,
-            AXIS_MAX_VALUE,
-        );
+    /// Serializes the input into the buffer, returning the number of bytes written.
+    pub fn to_bytes(self, buffer: &mut [u8; Self::MAX_SIZE]) -> usize {
+        let mut writer = SliceWriter::new(buffer);
+        self.write_bytes(&mut writer);
+        writer.len()
+    }
+
+    /// Attempts to deserialize an input that takes up all of the given bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let input = Self::read_bytes(&mut reader)?;
+        if reader.is_empty() {
+            Some(input)
+        } else {
+            None
+        }
+    }
+}
 
-        let four_bit_n = four_bit_f32 as N;
+impl From<N> for PlayerInput {
+    /// Unpacks an input from `inner`. Unused bits are cleared and axes are clamped to the precision.
+    fn from(n: N) -> Self {
+        let mut input = Self(n & USED_FLAGS);
+        let max = input.precision().max_value();
+        for index in AXIS_INDEXES {
+            let value = input.axis_value(index);
+            if value < -max {
+                input.set_axis_value(index, -max);
+            } else if value > max {
+                input.set_axis_value(index, max);
+            }
+        }
 
-        self.0 = self.0 & (!flag) | (four_bit_n << index);
+        input
     }
 }
 
@@ -102,11 +368,51 @@ impl PlayerInput {
 mod tests {
     use super::*;
 
+    type Getter = fn(&PlayerInput) -> NormalizedF32;
+    type Setter = fn(&mut PlayerInput, NormalizedF32);
+
+    const AXES: [(Getter, Setter); 4] = [
+        (
+            PlayerInput::get_primary_x_axis,
+            PlayerInput::set_primary_x_axis,
+        ),
+        (
+            PlayerInput::get_primary_y_axis,
+            PlayerInput::set_primary_y_axis,
+        ),
+        (
+            PlayerInput::get_secondary_x_axis,
+            PlayerInput::set_secondary_x_axis,
+        ),
+        (
+            PlayerInput::get_secondary_y_axis,
+            PlayerInput::set_secondary_y_axis,
+        ),
+    ];
+
+    /// Deterministic pseudo random inputs covering every field.
+    fn random_inputs() -> impl Iterator<Item = PlayerInput> {
+        let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+        (0..5000).map(move |_| {
+            // xorshift64
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed.into()
+        })
+    }
+
+    fn round_trip(input: PlayerInput) -> Option<PlayerInput> {
+        let mut buffer = [0; PlayerInput::MAX_SIZE];
+        let len = input.to_bytes(&mut buffer);
+        PlayerInput::from_bytes(&buffer[..len])
+    }
+
     #[test]
     fn set_x_axis_returns_min() {
         let mut i = PlayerInput::new();
         i.set_primary_x_axis(NormalizedF32::MIN);
-        assert_eq!(0, i.0);
+        assert_eq!(-7i8 as u8 as N, i.0);
     }
 
     #[test]
@@ -120,7 +426,7 @@ mod tests {
     fn set_x_axis_returns_max() {
         let mut i = PlayerInput::new();
         i.set_primary_x_axis(NormalizedF32::MAX);
-        assert_eq!(15, i.0)
+        assert_eq!(7, i.0)
     }
     #[test]
     fn get_x_axis_returns_max() {
@@ -133,7 +439,7 @@ mod tests {
     fn set_x_axis_returns_half() {
         let mut i = PlayerInput::new();
         i.set_primary_x_axis(0.0.into());
-        assert_eq!(7, i.0)
+        assert_eq!(0, i.0)
     }
     #[test]
     fn get_x_axis_returns_half() {
@@ -147,4 +453,180 @@ mod tests {
         let i = PlayerInput::new();
         assert_eq!(NormalizedF32::ZERO, i.get_primary_x_axis())
     }
+
+    #[test]
+    fn axes_do_not_overlap() {
+        for (i, (get, set)) in AXES.iter().enumerate() {
+            let mut input = PlayerInput::new();
+            set(&mut input, NormalizedF32::MIN);
+            assert_eq!(NormalizedF32::MIN, get(&input));
+
+            for (j, (other, _)) in AXES.iter().enumerate() {
+                if i != j {
+                    assert_eq!(NormalizedF32::ZERO, other(&input));
+                }
+            }
+            assert_eq!(0, input.buttons());
+        }
+    }
+
+    #[test]
+    fn low_precision_rounds_to_nearest() {
+        let mut i = PlayerInput::new();
+        i.set_primary_y_axis(0.5.into());
+        assert_eq!(4.0 / 7.0, i.get_primary_y_axis().inner());
+        i.set_primary_y_axis((-0.5).into());
+        assert_eq!(-4.0 / 7.0, i.get_primary_y_axis().inner());
+        i.set_primary_y_axis(0.05.into());
+        assert_eq!(NormalizedF32::ZERO, i.get_primary_y_axis());
+    }
+
+    #[test]
+    fn high_precision_keeps_more_detail() {
+        let mut i = PlayerInput::new();
+        i.set_precision(AxisPrecision::High);
+        i.set_secondary_x_axis(0.5.into());
+        assert_eq!(64.0 / 127.0, i.get_secondary_x_axis().inner());
+        i.set_secondary_x_axis(NormalizedF32::MIN);
+        assert_eq!(NormalizedF32::MIN, i.get_secondary_x_axis());
+    }
+
+    #[test]
+    fn set_precision_requantizes_axes() {
+        let mut i = PlayerInput::new();
+        i.set_precision(AxisPrecision::High);
+        i.set_primary_x_axis(0.3.into());
+        i.set_primary_y_axis(NormalizedF32::MAX);
+        i.set_precision(AxisPrecision::Low);
+
+        assert_eq!(AxisPrecision::Low, i.precision());
+        assert_eq!(2.0 / 7.0, i.get_primary_x_axis().inner());
+        assert_eq!(NormalizedF32::MAX, i.get_primary_y_axis());
+    }
+
+    #[test]
+    fn deadzone_zeroes_small_values() {
+        let d = Deadzone::new(0.2);
+        assert_eq!(NormalizedF32::ZERO, d.apply(0.1.into()));
+        assert_eq!(NormalizedF32::ZERO, d.apply((-0.2).into()));
+        assert_eq!(NormalizedF32::MAX, d.apply(NormalizedF32::MAX));
+        assert_eq!(NormalizedF32::MIN, d.apply(NormalizedF32::MIN));
+        assert!((d.apply(0.6.into()).inner() - 0.5).abs() < 0.0001);
+        assert!((d.apply((-0.6).into()).inner() + 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn buttons_do_not_overlap() {
+        for button in Button::ALL {
+            let mut input = PlayerInput::new();
+            input.set_button(button, true);
+            for other in Button::ALL {
+                assert_eq!(button == other, input.is_held(other));
+            }
+            for (get, _) in AXES {
+                assert_eq!(NormalizedF32::ZERO, get(&input));
+            }
+
+            input.set_button(button, false);
+            assert_eq!(PlayerInput::new(), input);
+        }
+    }
+
+    #[test]
+    fn button_state_detects_edges() {
+        let up = PlayerInput::new();
+        let mut down = PlayerInput::new();
+        down.set_button(Button::Start, true);
+
+        assert_eq!(ButtonState::Up, up.button_state(&up, Button::Start));
+        assert_eq!(ButtonState::Pressed, down.button_state(&up, Button::Start));
+        assert_eq!(ButtonState::Held, down.button_state(&down, Button::Start));
+        assert_eq!(ButtonState::Released, up.button_state(&down, Button::Start));
+        assert_eq!(ButtonState::Up, down.button_state(&down, Button::A));
+        assert!(ButtonState::Pressed.is_down());
+        assert!(!ButtonState::Released.is_down());
+    }
+
+    #[test]
+    fn from_clears_unused_bits_and_clamps_axes() {
+        let input = PlayerInput::from(N::MAX & !HIGH_PRECISION_FLAG);
+        assert_eq!(0, input.inner() & !USED_FLAGS);
+        // 0xFF is -1, which fits
+        assert_eq!(-1.0 / 7.0, input.get_primary_x_axis().inner());
+
+        let input = PlayerInput::from(0x7F);
+        assert_eq!(NormalizedF32::MAX, input.get_primary_x_axis());
+    }
+
+    #[test]
+    fn low_precision_is_smaller_on_the_wire() {
+        let mut buffer = [0; PlayerInput::MAX_SIZE];
+        let mut input = PlayerInput::new();
+        assert_eq!(5, input.to_bytes(&mut buffer));
+        input.set_precision(AxisPrecision::High);
+        assert_eq!(PlayerInput::MAX_SIZE, input.to_bytes(&mut buffer));
+    }
+
+    #[test]
+    fn round_trip_keeps_every_field() {
+        let mut precisions = [0; 2];
+        for input in random_inputs() {
+            let actual = round_trip(input).unwrap();
+            assert_eq!(input, actual);
+
+            assert_eq!(input.precision(), actual.precision());
+            precisions[input.precision() as usize] += 1;
+            for (get, _) in AXES {
+                assert_eq!(get(&input), get(&actual));
+            }
+            for button in Button::ALL {
+                assert_eq!(input.is_held(button), actual.is_held(button));
+            }
+        }
+
+        assert!(precisions.iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn round_trip_covers_every_axis_value() {
+        for precision in [AxisPrecision::Low, AxisPrecision::High] {
+            let max = precision.max_value();
+            for value in -max..=max {
+                for (index, (get, set)) in AXIS_INDEXES.iter().zip(AXES) {
+                    let mut input = PlayerInput::new();
+                    input.set_precision(precision);
+                    input.set_axis_value(*index, value);
+                    assert_eq!(Some(input), round_trip(input));
+
+                    // Setting the decoded value back stores the same value.
+                    let mut copy = PlayerInput::new();
+                    copy.set_precision(precision);
+                    set(&mut copy, get(&input));
+                    assert_eq!(input, copy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_invalid_data() {
+        let mut buffer = [0; PlayerInput::MAX_SIZE];
+        let len = PlayerInput::new().to_bytes(&mut buffer);
+
+        assert_eq!(None, PlayerInput::from_bytes(&buffer[..len - 1]));
+        assert_eq!(None, PlayerInput::from_bytes(&buffer[..len + 1]));
+
+        let mut version = buffer;
+        version[0] = (WIRE_VERSION + 1) << 4;
+        assert_eq!(None, PlayerInput::from_bytes(&version[..len]));
+
+        let mut flags = buffer;
+        flags[0] |= 0b0100;
+        assert_eq!(None, PlayerInput::from_bytes(&flags[..len]));
+
+        // -8 can't be held in low precision
+        let mut axis = buffer;
+        axis[1] = 0x08;
+        assert_eq!(None, PlayerInput::from_bytes(&axis[..len]));
+    }
 }
@@ -1,41 +1,372 @@
-use crate::math::map_range;
+use crate::bytes::{ByteReader, ByteWriter};
 
-type N = u64;
-pub struct Rng(N);
+/// Multiplier of the 64 bit linear congruential generator that drives PCG.
+const MULTIPLIER: u64 = 6364136223846793005;
+
+/// Deterministic random number generator, using PCG-XSH-RR 32 from https://www.pcg-random.org/.
+/// The whole state is plain data, so it can live in `State` and be rolled back along with it.
+///
+/// Each stream is an independent sequence for the same seed.
+/// Use `fork` to give every system its own stream, so adding one doesn't change the numbers the others get.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rng {
+    state: u64,
+    /// Selects the stream. Always odd.
+    increment: u64,
+}
 
 impl Rng {
-    pub fn new(seed: N) -> Self {
-        let mut x = seed;
-        x ^= x << 13;
-        x ^= x >> 17;
-        x ^= x << 5;
-        Self(x)
+    /// The number of bytes `write_bytes` writes.
+    pub const SIZE: usize = 16;
+
+    /// Creates a new generator with the seed, on the first stream.
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0)
+    }
+
+    /// Creates a new generator with the seed, on the given stream.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+
+        rng
+    }
+
+    /// Returns a new generator on another stream, seeded from the current state.
+    /// Doesn't advance this generator, so the same stream always gives the same fork until it does.
+    pub fn fork(&self, stream: u64) -> Self {
+        let mut source = *self;
+        Self::with_stream(source.u64(), stream)
+    }
+
+    /// Returns a new generator on another stream, advancing this one.
+    pub fn split(&mut self, stream: u64) -> Self {
+        Self::with_stream(self.u64(), stream)
     }
 
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+
+    /// Returns a uniformly distributed `u32`.
+    pub fn u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Returns a uniformly distributed `u64`.
+    pub fn u64(&mut self) -> u64 {
+        let high = self.u32() as u64;
+        let low = self.u32() as u64;
+        (high << 32) | low
+    }
+
+    /// Returns a uniformly distributed `u8`.
     pub fn u8(&mut self) -> u8 {
-        let n = self.0 % (u8::MAX as N);
-        *self = Self::new(self.0);
+        // The high bits are the best quality.
+        (self.u32() >> 24) as u8
+    }
 
-        n as u8
+    pub fn bool(&mut self) -> bool {
+        self.u32() >> 31 == 1
     }
 
+    /// Returns a uniformly distributed value from 0 up to, but not including, 1.
     pub fn f32(&mut self) -> f32 {
-        let n = self.0 as f32;
-        let n = n / (N::MAX as f32);
-        *self = Self::new(self.0);
+        // f32 has 24 bits of precision, so every value is exact and evenly spaced.
+        (self.u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Returns a uniformly distributed value from 0 up to, but not including, `bound`.
+    /// Uses Lemire's method, which rejects the few values that would bias the result.
+    ///
+    /// Panics if `bound` is 0.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        assert!(bound > 0, "bound must be greater than 0");
 
-        n
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let m = self.u32() as u64 * bound as u64;
+            if m as u32 >= threshold {
+                return (m >> 32) as u32;
+            }
+        }
+    }
+
+    /// Returns a uniformly distributed value from 0 up to, but not including, `bound`.
+    ///
+    /// Panics if `bound` is 0.
+    pub fn below_u64(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "bound must be greater than 0");
+
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let m = self.u64() as u128 * bound as u128;
+            if m as u64 >= threshold {
+                return (m >> 64) as u64;
+            }
+        }
+    }
+
+    /// Returns a uniformly distributed value from `low` to `high`, inclusive.
+    ///
+    /// Panics if `low` is greater than `high`.
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        assert!(low <= high, "low must not be greater than high");
+
+        // The span only overflows when covering every i32, in which case any u32 will do.
+        let offset = match (high.wrapping_sub(low) as u32).checked_add(1) {
+            Some(span) => self.below(span),
+            None => self.u32(),
+        };
+        low.wrapping_add(offset as i32)
+    }
+
+    /// Rolls a single die with the given number of sides, returning 1 to `sides`.
+    ///
+    /// Panics if `sides` is 0.
+    pub fn die(&mut self, sides: u32) -> u32 {
+        self.below(sides) + 1
+    }
+
+    /// Rolls `count` dice with the given number of sides, returning the total.
+    ///
+    /// Panics if `sides` is 0.
+    pub fn dice(&mut self, count: u32, sides: u32) -> u32 {
+        (0..count).map(|_| self.die(sides)).sum()
+    }
+
+    /// Randomly reorders the items, with every order being equally likely.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        // Fisher-Yates
+        for i in (1..items.len()).rev() {
+            let j = self.below_u64(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Returns a random item, or `None` if there are none.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+
+        let i = self.below_u64(items.len() as u64) as usize;
+        items.get(i)
+    }
+
+    /// Returns a random index into `weights`, where each index is picked in proportion to its weight.
+    /// Returns `None` if all weights are 0.
+    pub fn weighted(&mut self, weights: &[u32]) -> Option<usize> {
+        let total: u64 = weights.iter().map(|w| *w as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut target = self.below_u64(total);
+        for (i, weight) in weights.iter().enumerate() {
+            let weight = *weight as u64;
+            if target < weight {
+                return Some(i);
+            }
+            target -= weight;
+        }
+
+        None
+    }
+
+    /// Writes the generator as bytes that are stable across platforms.
+    pub fn write_bytes<W: ByteWriter>(&self, writer: &mut W) {
+        writer.write_u64(self.state);
+        writer.write_u64(self.increment);
+    }
+
+    /// Reads a generator that was written with `write_bytes`.
+    pub fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let state = reader.read_u64()?;
+        let increment = reader.read_u64()?;
+        if increment & 1 == 0 {
+            return None;
+        }
+
+        Some(Self { state, increment })
     }
 }
 
-#[test]
-fn stuff() {
-    let mut a = Rng::new(123);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_implementation() {
+        // Output of the PCG reference demo for pcg32_srandom(42, 54).
+        let mut rng = Rng::with_stream(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for e in expected {
+            assert_eq!(e, rng.u32());
+        }
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(123);
+        let mut b = Rng::new(123);
+        let mut c = Rng::new(124);
+
+        let a: Vec<_> = (0..100).map(|_| a.u32()).collect();
+        let b: Vec<_> = (0..100).map(|_| b.u32()).collect();
+        let c: Vec<_> = (0..100).map(|_| c.u32()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn streams_differ() {
+        let rng = Rng::new(7);
+        let mut a = rng.fork(1);
+        let mut b = rng.fork(2);
+        let mut a_again = rng.fork(1);
+
+        let first = a.u64();
+        assert_ne!(first, b.u64());
+        assert_eq!(first, a_again.u64());
+    }
+
+    #[test]
+    fn split_advances_parent() {
+        let mut rng = Rng::new(7);
+        let mut a = rng.split(1);
+        let mut b = rng.split(1);
+        assert_ne!(a.u64(), b.u64());
+    }
+
+    #[test]
+    fn u8_covers_every_value() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 256];
+        for _ in 0..10_000 {
+            seen[rng.u8() as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+
+    #[test]
+    fn f32_is_in_unit_range() {
+        let mut rng = Rng::new(123);
+        let values: Vec<_> = (0..1000).map(|_| rng.f32()).collect();
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        assert!((mean - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn below_is_unbiased() {
+        let mut rng = Rng::new(99);
+        let mut counts = [0; 3];
+        for _ in 0..30_000 {
+            counts[rng.below(3) as usize] += 1;
+        }
+        for count in counts {
+            assert!((9_500..10_500).contains(&count), "{:?}", counts);
+        }
 
-    let mut x = vec![];
-    for _ in 0..100 {
-        x.push(a.f32())
+        assert_eq!(0, rng.below(1));
     }
 
-    assert_eq!(vec![0.0], x)
+    #[test]
+    #[should_panic]
+    fn below_zero_panics() {
+        Rng::new(0).below(0);
+    }
+
+    #[test]
+    fn range_is_inclusive() {
+        let mut rng = Rng::new(5);
+        let mut seen = [false; 5];
+        for _ in 0..1000 {
+            let n = rng.range(-2, 2);
+            assert!((-2..=2).contains(&n));
+            seen[(n + 2) as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+
+        assert_eq!(4, rng.range(4, 4));
+        rng.range(i32::MIN, i32::MAX);
+    }
+
+    #[test]
+    fn dice_are_in_range() {
+        let mut rng = Rng::new(6);
+        for _ in 0..1000 {
+            assert!((1..=6).contains(&rng.die(6)));
+            assert!((3..=18).contains(&rng.dice(3, 6)));
+        }
+        assert_eq!(0, rng.dice(0, 6));
+    }
+
+    #[test]
+    fn shuffle_keeps_items() {
+        let mut rng = Rng::new(8);
+        let mut items: Vec<u32> = (0..50).collect();
+        rng.shuffle(&mut items);
+        assert_ne!((0..50).collect::<Vec<_>>(), items);
+
+        items.sort_unstable();
+        assert_eq!((0..50).collect::<Vec<_>>(), items);
+
+        rng.shuffle::<u32>(&mut []);
+    }
+
+    #[test]
+    fn choose_picks_item() {
+        let mut rng = Rng::new(9);
+        let items = [1, 2, 3];
+        for _ in 0..100 {
+            assert!(items.contains(rng.choose(&items).unwrap()));
+        }
+        assert_eq!(None, rng.choose::<u32>(&[]));
+    }
+
+    #[test]
+    fn weighted_follows_weights() {
+        let mut rng = Rng::new(10);
+        let mut counts = [0; 3];
+        for _ in 0..10_000 {
+            counts[rng.weighted(&[1, 0, 3]).unwrap()] += 1;
+        }
+
+        assert_eq!(0, counts[1]);
+        assert!((2_300..2_700).contains(&counts[0]), "{:?}", counts);
+        assert_eq!(None, rng.weighted(&[0, 0]));
+        assert_eq!(None, rng.weighted(&[]));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut rng = Rng::with_stream(3, 4);
+        rng.u32();
+
+        let mut bytes = vec![];
+        rng.write_bytes(&mut bytes);
+        assert_eq!(Rng::SIZE, bytes.len());
+        assert_eq!(Some(rng), Rng::read_bytes(&mut ByteReader::new(&bytes)));
+
+        bytes[8] = 0;
+        assert_eq!(None, Rng::read_bytes(&mut ByteReader::new(&bytes)));
+    }
 }
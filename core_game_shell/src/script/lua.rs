@@ -0,0 +1,393 @@
+use super::{
+    InstructionBudget, ScriptEngine, ScriptError, ScriptValue, BUDGET_STEP, NEW_ENVIRONMENT,
+};
+use mlua::{Function, HookTriggers, Lua, RegistryKey, Table, Value};
+use std::{cell::Cell, collections::HashMap, rc::Rc, string::String, vec::Vec};
+
+/// The most nested tables a script can return, so that cycles can't recurse forever.
+const MAX_DEPTH: usize = 16;
+
+/// The most memory the VM may use. Instructions such as `string.rep` can allocate a lot at once,
+/// which the instruction budget doesn't catch.
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Runs scripts on Lua 5.4, charging the budget from an instruction count hook.
+pub struct LuaEngine {
+    lua: Lua,
+    /// The compiled scripts, which return the function to call when run.
+    chunks: HashMap<String, RegistryKey>,
+    /// The budget of the call in progress, which the hook charges. Loading isn't charged.
+    budget: Rc<Cell<Option<InstructionBudget>>>,
+}
+impl LuaEngine {
+    pub fn new() -> Self {
+        let lua = Lua::new();
+        lua.set_memory_limit(MEMORY_LIMIT)
+            .expect("Lua 5.4 supports memory limits");
+        let budget: Rc<Cell<Option<InstructionBudget>>> = Rc::new(Cell::new(None));
+
+        let charged = Rc::clone(&budget);
+        let triggers = HookTriggers::new().every_nth_instruction(BUDGET_STEP as u32);
+        lua.set_hook(triggers, move |_, _| {
+            let mut budget = match charged.get() {
+                Some(budget) => budget,
+                None => return Ok(()),
+            };
+            let within = budget.charge(BUDGET_STEP);
+            charged.set(Some(budget));
+
+            match within {
+                true => Ok(()),
+                false => Err(mlua::Error::RuntimeError("out of instructions".into())),
+            }
+        });
+
+        Self {
+            lua,
+            chunks: HashMap::new(),
+            budget,
+        }
+    }
+}
+impl LuaEngine {
+    /// Runs the chunk in a fresh environment and returns the function it returns.
+    fn instantiate<'lua>(&'lua self, chunk: &Function<'lua>) -> mlua::Result<Function<'lua>> {
+        let new_environment: Function = self.lua.globals().get(NEW_ENVIRONMENT)?;
+        let environment: Table = new_environment.call(())?;
+        chunk.set_environment(environment)?;
+        chunk.call(())
+    }
+}
+impl Default for LuaEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ScriptEngine for LuaEngine {
+    fn run(&mut self, chunk: &str) -> Result<(), String> {
+        self.lua.load(chunk).exec().map_err(|e| e.to_string())
+    }
+
+    fn load(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        let error = |e: mlua::Error| ScriptError::Load {
+            script: name.into(),
+            message: e.to_string(),
+        };
+
+        let chunk = self
+            .lua
+            .load(source)
+            .set_name(name)
+            .into_function()
+            .map_err(error)?;
+        self.instantiate(&chunk).map_err(error)?;
+        let key = self.lua.create_registry_value(chunk).map_err(error)?;
+        self.chunks.insert(name.into(), key);
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        argument: ScriptValue,
+        budget: &mut InstructionBudget,
+    ) -> Result<ScriptValue, ScriptError> {
+        let runtime = |message: String| ScriptError::Runtime {
+            script: name.into(),
+            message,
+        };
+
+        let key = self
+            .chunks
+            .get(name)
+            .ok_or_else(|| runtime("not loaded".into()))?;
+        let chunk: Function = self
+            .lua
+            .registry_value(key)
+            .map_err(|e| runtime(e.to_string()))?;
+        let argument = to_lua(&self.lua, &argument).map_err(|e| runtime(e.to_string()))?;
+
+        self.budget.set(Some(*budget));
+        let result = self
+            .instantiate(&chunk)
+            .and_then(|function| function.call::<_, Value>(argument));
+        *budget = self.budget.take().unwrap_or(*budget);
+
+        if budget.used() > budget.limit() {
+            return Err(ScriptError::BudgetExceeded {
+                script: name.into(),
+            });
+        }
+        let result = result.map_err(|e| runtime(e.to_string()))?;
+        from_lua(result, 0).ok_or_else(|| ScriptError::InvalidResult {
+            script: name.into(),
+        })
+    }
+}
+
+fn to_lua<'lua>(lua: &'lua Lua, value: &ScriptValue) -> mlua::Result<Value<'lua>> {
+    let value = match value {
+        ScriptValue::Nil => Value::Nil,
+        ScriptValue::Bool(b) => Value::Boolean(*b),
+        ScriptValue::Integer(i) => Value::Integer(*i),
+        ScriptValue::String(s) => Value::String(lua.create_string(s)?),
+        ScriptValue::List(items) => {
+            let table = lua.create_table()?;
+            for (i, item) in items.iter().enumerate() {
+                table.raw_set(i + 1, to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+        ScriptValue::Table(entries) => {
+            let table = lua.create_table()?;
+            for (key, item) in entries {
+                table.raw_set(key.as_str(), to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+    };
+
+    Ok(value)
+}
+
+/// Reads a value a script returned. Tables become lists if keyed 1 to n, or tables sorted by key if keyed by strings.
+/// Anything else, such as floats or functions, can't be read.
+fn from_lua(value: Value, depth: usize) -> Option<ScriptValue> {
+    let table = match value {
+        Value::Nil => return Some(ScriptValue::Nil),
+        Value::Boolean(b) => return Some(ScriptValue::Bool(b)),
+        Value::Integer(i) => return Some(ScriptValue::Integer(i)),
+        Value::String(s) => return Some(ScriptValue::String(s.to_str().ok()?.into())),
+        Value::Table(table) if depth < MAX_DEPTH => table,
+        _ => return None,
+    };
+
+    let mut items = Vec::new();
+    let mut entries = Vec::new();
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair.ok()?;
+        let value = from_lua(value, depth + 1)?;
+        match key {
+            Value::Integer(i) => items.push((i, value)),
+            Value::String(s) => entries.push((String::from(s.to_str().ok()?), value)),
+            _ => return None,
+        }
+    }
+
+    if entries.is_empty() {
+        items.sort_by_key(|(i, _)| *i);
+        let is_sequence = items.iter().zip(1..).all(|((i, _), n)| *i == n);
+        is_sequence.then(|| ScriptValue::List(items.into_iter().map(|(_, v)| v).collect()))
+    } else if items.is_empty() {
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Some(ScriptValue::Table(entries))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{Event, EventQueue},
+        game::MAX_PLAYERS,
+        player_input::PlayerInput,
+        rand::Rng,
+        script::Scripts,
+    };
+
+    fn scripts(sources: &[(&str, &str)]) -> Scripts<LuaEngine> {
+        let mut scripts = Scripts::new(LuaEngine::new(), 100 * BUDGET_STEP).unwrap();
+        for (name, source) in sources {
+            scripts.add(name, source).unwrap();
+        }
+        scripts
+    }
+
+    fn run(scripts: &mut Scripts<LuaEngine>) -> (Result<(), ScriptError>, Vec<Event>) {
+        let mut queue = EventQueue::new();
+        queue.emit(Event::PlayerJoined { player: 1.into() });
+        let result = scripts.run(
+            4.into(),
+            &[PlayerInput::new(); MAX_PLAYERS],
+            &Rng::new(1),
+            &mut queue,
+        );
+        (result, queue.iter().skip(1).copied().collect())
+    }
+
+    #[test]
+    fn returned_events_are_emitted() {
+        let source = r#"
+            return function(ctx)
+                local hits = {}
+                for _, event in ipairs(ctx.events) do
+                    if event.kind == "joined" then
+                        hits[#hits + 1] = { kind = "damage", target = event.player, amount = ctx.frame }
+                    end
+                end
+                return hits
+            end
+        "#;
+        let mut s = scripts(&[("hit", source)]);
+
+        assert_eq!(
+            (
+                Ok(()),
+                vec![Event::Damage {
+                    target: 1.into(),
+                    amount: 4
+                }]
+            ),
+            run(&mut s)
+        );
+    }
+
+    #[test]
+    fn sandbox_removes_raw_access_and_metatables() {
+        let source = r#"
+            return function()
+                local removed = {
+                    rawset, rawget, setmetatable, getmetatable, next, pcall, io, os, load, debug, _G,
+                }
+                return { { kind = "sound", cue = #removed } }
+            end
+        "#;
+        let mut s = scripts(&[("check", source)]);
+        assert_eq!((Ok(()), vec![Event::Sound { cue: 0 }]), run(&mut s));
+    }
+
+    /// Returns the first error, or the cue of the sound the script returned.
+    fn sound(source: &str) -> Result<u16, ScriptError> {
+        let mut s = scripts(&[("check", source)]);
+        let (result, events) = run(&mut s);
+        result.map(|_| match events[..] {
+            [Event::Sound { cue }] => cue,
+            _ => panic!("expected a sound, got {:?}", events),
+        })
+    }
+
+    fn runtime_error(result: Result<u16, ScriptError>) -> String {
+        match result {
+            Err(ScriptError::Runtime { message, .. }) => message,
+            result => panic!("expected a runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn nothing_is_kept_between_calls() {
+        let source = r#"
+            local calls = 0
+            return function()
+                calls = calls + 1
+                count = (count or 0) + 1
+                return { { kind = "sound", cue = calls * 10 + count } }
+            end
+        "#;
+        let mut s = scripts(&[("count", source)]);
+        for _ in 0..3 {
+            assert_eq!((Ok(()), vec![Event::Sound { cue: 11 }]), run(&mut s));
+        }
+    }
+
+    #[test]
+    fn libraries_are_read_only() {
+        for change in [
+            "math.floor = nil",
+            "string.rep = nil",
+            "table.insert = nil",
+            "string.cue = 1",
+        ] {
+            let source = format!("return function() {} end", change);
+            assert!(runtime_error(sound(&source)).contains("can't change the library field"));
+        }
+
+        // Replacing a library only replaces it for the call.
+        let replaces = r#"
+            return function()
+                local floor = math.floor(2.5)
+                math = { floor = function() return 9 end }
+                return { { kind = "sound", cue = floor } }
+            end
+        "#;
+        let mut s = scripts(&[("first", replaces), ("second", replaces)]);
+        let sound = Event::Sound { cue: 2 };
+        assert_eq!((Ok(()), vec![sound, sound]), run(&mut s));
+    }
+
+    #[test]
+    fn pairs_goes_through_keys_in_order() {
+        let source = r#"
+            return function()
+                local t = { z = 1, a = 2, [3] = 3, [1] = 4, m = 5 }
+                local cue = 0
+                for _, value in pairs(t) do
+                    cue = cue * 10 + value
+                end
+                return { { kind = "sound", cue = cue } }
+            end
+        "#;
+        assert_eq!(Ok(43251), sound(source));
+
+        let table_keys = "return function() for _ in pairs({ [{}] = 1 }) do end end";
+        assert!(runtime_error(sound(table_keys)).contains("number and string keys"));
+    }
+
+    #[test]
+    fn addresses_are_hidden() {
+        let source = r#"
+            return function()
+                local printed = tostring({}) .. tostring(ipairs) .. string.format("%s%s", {}, tostring)
+                return { { kind = "sound", cue = #printed } }
+            end
+        "#;
+        assert_eq!(Ok(26), sound(source));
+
+        let pointer = r#"return function() return ("%p"):format({}) end"#;
+        assert!(runtime_error(sound(pointer)).contains("can't format addresses"));
+        let escaped =
+            r#"return function() return { { kind = "sound", cue = #string.format("%%p") } } end"#;
+        assert_eq!(Ok(2), sound(escaped));
+    }
+
+    #[test]
+    fn memory_is_limited() {
+        let source = r#"return function() local s = string.rep("x", 1 << 30) end"#;
+        assert!(runtime_error(sound(source)).contains("memory"));
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped_by_the_budget() {
+        let mut s = scripts(&[("spin", "return function() while true do end end")]);
+        for _ in 0..2 {
+            assert_eq!(
+                Err(ScriptError::BudgetExceeded {
+                    script: "spin".into()
+                }),
+                run(&mut s).0
+            );
+            assert!(s.budget().used() > s.budget().limit());
+        }
+    }
+
+    #[test]
+    fn only_plain_values_can_be_returned() {
+        let returns = |value: &str| {
+            let source = format!("return function() return {} end", value);
+            run(&mut scripts(&[("returns", &source)])).0
+        };
+        let invalid = Err(ScriptError::InvalidResult {
+            script: "returns".into(),
+        });
+
+        assert_eq!(Ok(()), returns("{}"));
+        assert_eq!(invalid, returns("1.5"));
+        assert_eq!(invalid, returns("{ [2] = {} }"));
+        assert_eq!(invalid, returns("function() end"));
+
+        let cycle = "return function() local t = {} t[1] = t return t end";
+        let mut s = scripts(&[("returns", cycle)]);
+        assert_eq!(invalid, run(&mut s).0);
+    }
+}
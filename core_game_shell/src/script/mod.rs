@@ -0,0 +1,657 @@
+#[cfg(feature = "lua")]
+pub mod lua;
+
+use crate::{
+    events::{Event, EventQueue},
+    game::{Frame, MAX_PLAYERS},
+    math::hash::Fnv1a32,
+    player_input::PlayerInput,
+    rand::Rng,
+};
+use std::{boxed::Box, string::String, vec, vec::Vec};
+
+/// Lua run before any script, building the environment scripts run in.
+///
+/// Scripts only see what is listed here, so nothing that isn't deterministic or reaches outside the game.
+/// Randomness comes from the seed each script is given instead of `math.random`.
+/// Errors can't be caught, so running out of budget always stops the script.
+///
+/// The libraries are read-only proxies, so scripts can't change them for later calls.
+/// The hash seed changes between runs, so `pairs` goes through keys in sorted order instead of hash order,
+/// and tables and functions are printed as their type instead of their address.
+/// Each call gets a fresh environment from `new_environment`, so globals a script sets are dropped afterwards.
+pub const SANDBOX_PRELUDE: &str = r#"
+do
+    local error, format, ipairs, next, rawget = error, string.format, ipairs, next, rawget
+    local select, setmetatable, sort, tostring, type = select, setmetatable, table.sort, tostring, type
+    local find, gsub, pack, unpack = string.find, string.gsub, table.pack, table.unpack
+
+    local function frozen(library, except)
+        local copy = {}
+        for name, value in pairs(library) do
+            if not except[name] then
+                copy[name] = value
+            end
+        end
+        return copy, setmetatable({}, {
+            __index = copy,
+            __newindex = function(_, name)
+                error("scripts can't change the library field '" .. tostring(name) .. "'", 2)
+            end,
+            __metatable = false,
+        })
+    end
+
+    local function printable(value)
+        local kind = type(value)
+        if kind == "table" or kind == "function" or kind == "thread" or kind == "userdata" then
+            return kind
+        end
+        return value
+    end
+
+    local string_copy, frozen_string = frozen(string, { dump = true })
+    string_copy.format = function(pattern, ...)
+        if find(gsub(pattern, "%%%%", ""), "%%[-+ #0-9.]*p") then
+            error("scripts can't format addresses", 2)
+        end
+        local arguments = pack(...)
+        for i = 1, arguments.n do
+            arguments[i] = printable(arguments[i])
+        end
+        return format(pattern, unpack(arguments, 1, arguments.n))
+    end
+    getmetatable("").__index = frozen_string
+
+    local function before(a, b)
+        local kind_a, kind_b = type(a), type(b)
+        if kind_a ~= kind_b then
+            return kind_a < kind_b
+        end
+        return a < b
+    end
+
+    local base = {
+        assert = assert,
+        error = error,
+        ipairs = ipairs,
+        select = select,
+        tonumber = tonumber,
+        type = type,
+        string = frozen_string,
+        math = select(2, frozen(math, { random = true, randomseed = true })),
+        table = select(2, frozen(table, {})),
+        utf8 = select(2, frozen(utf8, {})),
+        tostring = function(value)
+            return tostring(printable(value))
+        end,
+        pairs = function(t)
+            local keys = {}
+            for key in next, t do
+                local kind = type(key)
+                if kind ~= "number" and kind ~= "string" then
+                    error("pairs only goes through number and string keys", 2)
+                end
+                keys[#keys + 1] = key
+            end
+            sort(keys, before)
+
+            local i = 0
+            return function()
+                i = i + 1
+                local key = keys[i]
+                if key ~= nil then
+                    return key, rawget(t, key)
+                end
+            end, t, nil
+        end,
+    }
+    local environment = { __index = base, __metatable = false }
+
+    function new_environment()
+        return setmetatable({}, environment)
+    end
+end
+"#;
+
+/// The global the prelude defines, which returns a fresh environment to run a script in.
+pub const NEW_ENVIRONMENT: &str = "new_environment";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptError {
+    /// The engine failed to run the sandbox prelude.
+    Sandbox(String),
+    /// A script was added after the scripts started running.
+    Sealed,
+    DuplicateName(String),
+    Load {
+        script: String,
+        message: String,
+    },
+    Runtime {
+        script: String,
+        message: String,
+    },
+    /// The scripts ran more instructions in a single tick than the budget allows.
+    BudgetExceeded {
+        script: String,
+    },
+    /// The script returned something other than a list of events.
+    InvalidResult {
+        script: String,
+    },
+}
+
+/// A value passed to or returned from a script.
+/// Only integers are used for numbers, so results don't depend on floating point.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptValue {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    /// A table with keys 1 to n.
+    List(Vec<ScriptValue>),
+    /// A table with string keys, in a fixed order.
+    Table(Vec<(String, ScriptValue)>),
+}
+impl ScriptValue {
+    /// Returns the value for the key if this is a table.
+    pub fn get(&self, key: &str) -> Option<&ScriptValue> {
+        match self {
+            ScriptValue::Table(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            ScriptValue::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+/// The instructions scripts may run in a single tick.
+/// Engines charge it as they go, so a runaway script is stopped instead of stalling the game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InstructionBudget {
+    limit: u64,
+    used: u64,
+}
+impl InstructionBudget {
+    pub fn new(limit: u64) -> Self {
+        Self { limit, used: 0 }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Records that `instructions` more ran. Returns false once the limit is passed.
+    pub fn charge(&mut self, instructions: u64) -> bool {
+        self.used = self.used.saturating_add(instructions);
+        self.used <= self.limit
+    }
+
+    fn reset(&mut self) {
+        self.used = 0;
+    }
+}
+
+/// A virtual machine scripts run in.
+///
+/// Engines must count instructions, for Lua with a count hook, and call `InstructionBudget::charge`
+/// at least every `BUDGET_STEP` instructions, stopping the call with `BudgetExceeded` once it returns false.
+pub trait ScriptEngine {
+    /// Runs the chunk, which runs in the global environment.
+    fn run(&mut self, chunk: &str) -> Result<(), String>;
+
+    /// Compiles the script, to call as `name`. It must return a function.
+    fn load(&mut self, name: &str, source: &str) -> Result<(), ScriptError>;
+
+    /// Runs the script `name` in a fresh environment from `NEW_ENVIRONMENT` and calls the function it returns.
+    /// Nothing the script sets, in globals or in upvalues, is kept for the next call.
+    fn call(
+        &mut self,
+        name: &str,
+        argument: ScriptValue,
+        budget: &mut InstructionBudget,
+    ) -> Result<ScriptValue, ScriptError>;
+}
+
+/// How often engines charge the budget.
+pub const BUDGET_STEP: u64 = 1000;
+
+impl<E: ScriptEngine + ?Sized> ScriptEngine for Box<E> {
+    fn run(&mut self, chunk: &str) -> Result<(), String> {
+        (**self).run(chunk)
+    }
+
+    fn load(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        (**self).load(name, source)
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        argument: ScriptValue,
+        budget: &mut InstructionBudget,
+    ) -> Result<ScriptValue, ScriptError> {
+        (**self).call(name, argument, budget)
+    }
+}
+
+/// Gameplay rules written as scripts, run at the end of every tick.
+///
+/// Each returns a function that is called with a table of `frame`, `seed`, `inputs` and the `events` so far,
+/// and returns a list of events to emit, such as `{ kind = "damage", target = 1, amount = 5 }`.
+/// Scripts run from scratch for every call, so they work with rollback without the VM being snapshotted.
+pub struct Scripts<E: ScriptEngine> {
+    engine: E,
+    names: Vec<String>,
+    budget: InstructionBudget,
+    started: bool,
+}
+impl<E: ScriptEngine> Scripts<E> {
+    /// Sandboxes the engine and allows scripts `instructions_per_tick` instructions between them each tick.
+    pub fn new(mut engine: E, instructions_per_tick: u64) -> Result<Self, ScriptError> {
+        engine.run(SANDBOX_PRELUDE).map_err(ScriptError::Sandbox)?;
+
+        Ok(Self {
+            engine,
+            names: vec![],
+            budget: InstructionBudget::new(instructions_per_tick),
+            started: false,
+        })
+    }
+
+    /// Adds a script to run after the others.
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        if self.started {
+            return Err(ScriptError::Sealed);
+        }
+        if self.names.iter().any(|n| n == name) {
+            return Err(ScriptError::DuplicateName(name.into()));
+        }
+
+        self.engine.load(name, source)?;
+        self.names.push(name.into());
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// Returns the budget as it was left by the last tick.
+    pub fn budget(&self) -> InstructionBudget {
+        self.budget
+    }
+
+    /// Runs every script for the frame, emitting the events they return.
+    ///
+    /// A script that fails emits nothing, and the rest still run. The first error is returned.
+    /// Instruction counts are deterministic, so every peer fails the same way.
+    pub fn run(
+        &mut self,
+        frame: Frame,
+        inputs: &[PlayerInput; MAX_PLAYERS],
+        rng: &Rng,
+        events: &mut EventQueue,
+    ) -> Result<(), ScriptError> {
+        self.started = true;
+        self.budget.reset();
+
+        let inputs = ScriptValue::List(
+            inputs
+                .iter()
+                .map(|i| ScriptValue::Integer(i.inner() as i64))
+                .collect(),
+        );
+
+        let mut first_error = None;
+        for name in &self.names {
+            let argument = ScriptValue::Table(vec![
+                ("frame".into(), ScriptValue::Integer(frame.inner() as i64)),
+                ("seed".into(), ScriptValue::Integer(seed(rng, name))),
+                ("inputs".into(), inputs.clone()),
+                ("events".into(), events_value(events)),
+            ]);
+
+            let result = self
+                .engine
+                .call(name, argument, &mut self.budget)
+                .and_then(|result| {
+                    read_events(&result).ok_or_else(|| ScriptError::InvalidResult {
+                        script: name.clone(),
+                    })
+                });
+            match result {
+                Ok(emitted) => {
+                    for event in emitted {
+                        events.emit(event);
+                    }
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the seed for the script. Based on the name, like the streams systems get.
+fn seed(rng: &Rng, name: &str) -> i64 {
+    let mut hasher = Fnv1a32::new();
+    hasher.write(name.as_bytes());
+    rng.fork(hasher.finish() as u64).u64() as i64
+}
+
+/// Returns the events scripts can see. Events only the game uses, such as desyncs, are left out.
+fn events_value(events: &EventQueue) -> ScriptValue {
+    let entry = |kind: &str, fields: &[(&str, i64)]| {
+        let mut table = vec![("kind".into(), ScriptValue::String(kind.into()))];
+        for (key, value) in fields {
+            table.push(((*key).into(), ScriptValue::Integer(*value)));
+        }
+        ScriptValue::Table(table)
+    };
+
+    ScriptValue::List(
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                Event::PlayerJoined { player } => {
+                    Some(entry("joined", &[("player", player.index() as i64)]))
+                }
+                Event::PlayerLeft { player } => {
+                    Some(entry("left", &[("player", player.index() as i64)]))
+                }
+                Event::Damage { target, amount } => Some(entry(
+                    "damage",
+                    &[("target", target.index() as i64), ("amount", amount as i64)],
+                )),
+                Event::Sound { cue } => Some(entry("sound", &[("cue", cue as i64)])),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Reads the events a script returned. Scripts can only emit damage and sounds.
+fn read_events(value: &ScriptValue) -> Option<Vec<Event>> {
+    let entries = match value {
+        ScriptValue::Nil => return Some(vec![]),
+        ScriptValue::List(entries) => entries,
+        _ => return None,
+    };
+
+    let field = |entry: &ScriptValue, key| entry.get(key)?.as_integer();
+    entries
+        .iter()
+        .map(|entry| match entry.get("kind")? {
+            ScriptValue::String(kind) if kind == "damage" => {
+                let target = field(entry, "target")?;
+                if !(0..MAX_PLAYERS as i64).contains(&target) {
+                    return None;
+                }
+                Some(Event::Damage {
+                    target: (target as u8).into(),
+                    amount: u16::try_from(field(entry, "amount")?).ok()?,
+                })
+            }
+            ScriptValue::String(kind) if kind == "sound" => Some(Event::Sound {
+                cue: u16::try_from(field(entry, "cue")?).ok()?,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    type Function = fn(&ScriptValue, &mut InstructionBudget) -> Result<ScriptValue, String>;
+
+    /// Stands in for a Lua VM. The source of a script names one of the functions below.
+    #[derive(Default)]
+    struct TestEngine {
+        chunks: Vec<String>,
+        functions: HashMap<String, Function>,
+    }
+    impl ScriptEngine for TestEngine {
+        fn run(&mut self, chunk: &str) -> Result<(), String> {
+            self.chunks.push(chunk.into());
+            Ok(())
+        }
+
+        fn load(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+            let function: Function = match source {
+                "hit_everyone" => hit_everyone,
+                "spin" => spin,
+                "bad_result" => |_, _| Ok(ScriptValue::Integer(1)),
+                "seed_as_sound" => seed_as_sound,
+                _ => {
+                    return Err(ScriptError::Load {
+                        script: name.into(),
+                        message: "unknown".into(),
+                    })
+                }
+            };
+            self.functions.insert(name.into(), function);
+            Ok(())
+        }
+
+        fn call(
+            &mut self,
+            name: &str,
+            argument: ScriptValue,
+            budget: &mut InstructionBudget,
+        ) -> Result<ScriptValue, ScriptError> {
+            let function = self.functions[name];
+            function(&argument, budget).map_err(|message| {
+                if message == "budget" {
+                    ScriptError::BudgetExceeded {
+                        script: name.into(),
+                    }
+                } else {
+                    ScriptError::Runtime {
+                        script: name.into(),
+                        message,
+                    }
+                }
+            })
+        }
+    }
+
+    fn charge(budget: &mut InstructionBudget, instructions: u64) -> Result<(), String> {
+        if budget.charge(instructions) {
+            Ok(())
+        } else {
+            Err("budget".into())
+        }
+    }
+
+    /// Damages every player that joined this frame by 3.
+    fn hit_everyone(
+        ctx: &ScriptValue,
+        budget: &mut InstructionBudget,
+    ) -> Result<ScriptValue, String> {
+        charge(budget, BUDGET_STEP)?;
+        let ScriptValue::List(events) = ctx.get("events").unwrap() else {
+            unreachable!()
+        };
+        Ok(ScriptValue::List(
+            events
+                .iter()
+                .filter(|e| e.get("kind") == Some(&ScriptValue::String("joined".into())))
+                .map(|e| {
+                    ScriptValue::Table(vec![
+                        ("kind".into(), ScriptValue::String("damage".into())),
+                        ("target".into(), e.get("player").unwrap().clone()),
+                        ("amount".into(), ScriptValue::Integer(3)),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    fn spin(_: &ScriptValue, budget: &mut InstructionBudget) -> Result<ScriptValue, String> {
+        loop {
+            charge(budget, BUDGET_STEP)?;
+        }
+    }
+
+    fn seed_as_sound(ctx: &ScriptValue, _: &mut InstructionBudget) -> Result<ScriptValue, String> {
+        let seed = ctx.get("seed").unwrap().as_integer().unwrap();
+        Ok(ScriptValue::List(vec![ScriptValue::Table(vec![
+            ("kind".into(), ScriptValue::String("sound".into())),
+            ("cue".into(), ScriptValue::Integer(seed & 0xFFFF)),
+        ])]))
+    }
+
+    fn scripts(sources: &[(&str, &str)]) -> Scripts<TestEngine> {
+        let mut scripts = Scripts::new(TestEngine::default(), 10 * BUDGET_STEP).unwrap();
+        for (name, source) in sources {
+            scripts.add(name, source).unwrap();
+        }
+        scripts
+    }
+
+    fn run(
+        scripts: &mut Scripts<TestEngine>,
+        events: &[Event],
+    ) -> (Result<(), ScriptError>, Vec<Event>) {
+        let mut queue = EventQueue::new();
+        for event in events {
+            queue.emit(*event);
+        }
+        let result = scripts.run(
+            4.into(),
+            &[PlayerInput::new(); MAX_PLAYERS],
+            &Rng::new(1),
+            &mut queue,
+        );
+        (result, queue.iter().copied().collect())
+    }
+
+    #[test]
+    fn sandbox_runs_first_and_scripts_cant_be_added_after_running() {
+        let mut s = scripts(&[("hit", "hit_everyone")]);
+        assert_eq!(vec![SANDBOX_PRELUDE], s.engine.chunks);
+
+        run(&mut s, &[]).0.unwrap();
+        assert_eq!(Err(ScriptError::Sealed), s.add("late", "spin"));
+        assert_eq!(1, s.engine.chunks.len());
+    }
+
+    #[test]
+    fn returned_events_are_emitted() {
+        let mut s = scripts(&[("hit", "hit_everyone")]);
+        let joined = Event::PlayerJoined { player: 2.into() };
+        let (result, events) = run(&mut s, &[joined]);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(
+            vec![
+                joined,
+                Event::Damage {
+                    target: 2.into(),
+                    amount: 3
+                }
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn budget_is_shared_and_reset_each_tick() {
+        let mut s = scripts(&[("spin", "spin"), ("hit", "hit_everyone")]);
+        let joined = Event::PlayerJoined { player: 0.into() };
+
+        for _ in 0..2 {
+            let (result, events) = run(&mut s, &[joined]);
+            assert_eq!(
+                Err(ScriptError::BudgetExceeded {
+                    script: "spin".into()
+                }),
+                result
+            );
+            // The budget is spent, so the next script is stopped on its first charge.
+            assert_eq!(vec![joined], events);
+            assert!(s.budget().used() > s.budget().limit());
+        }
+    }
+
+    #[test]
+    fn failing_scripts_emit_nothing_and_others_still_run() {
+        let mut s = scripts(&[("bad", "bad_result"), ("hit", "hit_everyone")]);
+        let joined = Event::PlayerJoined { player: 1.into() };
+        let (result, events) = run(&mut s, &[joined]);
+
+        assert_eq!(
+            Err(ScriptError::InvalidResult {
+                script: "bad".into()
+            }),
+            result
+        );
+        assert_eq!(2, events.len());
+    }
+
+    #[test]
+    fn scripts_get_own_deterministic_seeds() {
+        let mut a = scripts(&[("one", "seed_as_sound"), ("two", "seed_as_sound")]);
+        let mut b = scripts(&[("one", "seed_as_sound")]);
+
+        let (_, a_events) = run(&mut a, &[]);
+        let (_, b_events) = run(&mut b, &[]);
+        assert_ne!(a_events[0], a_events[1]);
+        assert_eq!(a_events[0], b_events[0]);
+        assert_eq!(a_events, run(&mut a, &[]).1);
+    }
+
+    #[test]
+    fn names_must_be_unique() {
+        let mut s = scripts(&[("hit", "hit_everyone")]);
+        assert_eq!(
+            Err(ScriptError::DuplicateName("hit".into())),
+            s.add("hit", "spin")
+        );
+        assert_eq!(vec!["hit"], s.names().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn only_damage_and_sounds_can_be_emitted() {
+        let event = |kind: &str, key: &str, value| {
+            ScriptValue::List(vec![ScriptValue::Table(vec![
+                ("kind".into(), ScriptValue::String(kind.into())),
+                (key.into(), ScriptValue::Integer(value)),
+                ("amount".into(), ScriptValue::Integer(1)),
+            ])])
+        };
+
+        assert_eq!(Some(vec![]), read_events(&ScriptValue::Nil));
+        assert_eq!(None, read_events(&event("joined", "player", 1)));
+        assert_eq!(
+            None,
+            read_events(&event("damage", "target", MAX_PLAYERS as i64))
+        );
+        assert_eq!(None, read_events(&event("sound", "cue", -1)));
+        assert_eq!(
+            Some(vec![Event::Sound { cue: 7 }]),
+            read_events(&event("sound", "cue", 7))
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use super::Seconds;
-use core::ops::AddAssign;
+use core::ops::{AddAssign, SubAssign};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AccumulatedTime(Seconds);
@@ -8,6 +8,11 @@ impl AddAssign<Seconds> for AccumulatedTime {
         self.0 = rhs + self.0;
     }
 }
+impl SubAssign<Seconds> for AccumulatedTime {
+    fn sub_assign(&mut self, rhs: Seconds) {
+        self.0 = self.0 - rhs;
+    }
+}
 impl From<f32> for AccumulatedTime {
     fn from(f: f32) -> Self {
         Self(f.into())
@@ -18,6 +23,11 @@ impl AccumulatedTime {
     pub fn to_f32_seconds(&self) -> f32 {
         self.0.inner()
     }
+
+    /// Returns the accumulated seconds.
+    pub fn seconds(&self) -> Seconds {
+        self.0
+    }
 }
 
 #[cfg(test)]
@@ -32,6 +42,14 @@ mod tests {
         assert_eq!(AccumulatedTime(3.0.into()), a)
     }
 
+    #[test]
+    fn sub_assign() {
+        let mut a: AccumulatedTime = 3.0.into();
+        let s: Seconds = 2.0.into();
+        a -= s;
+        assert_eq!(AccumulatedTime(1.0.into()), a)
+    }
+
     #[test]
     fn from_f32() {
         let a: Seconds = 1.0.into();
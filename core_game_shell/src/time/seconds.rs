@@ -1,6 +1,6 @@
-use core::ops::Add;
+use core::ops::{Add, Div, Mul, Sub};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct Seconds(f32);
 impl Seconds {
     /// Returns the seconds value in a F32.
@@ -20,6 +20,28 @@ impl Add for Seconds {
         Self(self.0 + rhs.0)
     }
 }
+impl Sub for Seconds {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+impl Mul<f32> for Seconds {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self(self.0 * rhs)
+    }
+}
+/// Returns how many times `rhs` fits into `self`.
+impl Div for Seconds {
+    type Output = f32;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.0 / rhs.0
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,4 +64,24 @@ mod tests {
         let b: Seconds = 2.0.into();
         assert_eq!(Seconds(3.0), a + b)
     }
+
+    #[test]
+    fn sub() {
+        let a: Seconds = 3.0.into();
+        let b: Seconds = 2.0.into();
+        assert_eq!(Seconds(1.0), a - b)
+    }
+
+    #[test]
+    fn mul() {
+        let a: Seconds = 1.5.into();
+        assert_eq!(Seconds(3.0), a * 2.0)
+    }
+
+    #[test]
+    fn div() {
+        let a: Seconds = 3.0.into();
+        let b: Seconds = 2.0.into();
+        assert_eq!(1.5, a / b)
+    }
 }
@@ -7,6 +7,11 @@ impl TickRate {
     pub fn to_f32_seconds(&self) -> f32 {
         self.0.inner()
     }
+
+    /// Returns the seconds between ticks.
+    pub fn seconds(&self) -> Seconds {
+        self.0
+    }
 }
 impl From<f32> for TickRate {
     fn from(s: f32) -> Self {
@@ -1,7 +1,76 @@
-pub type Parent = Box<Environment>;
+use crate::interpreter::Value;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-pub struct Environment {}
+/// A shared handle to an environment. Closures keep the environment they were made in alive.
+pub type Parent = Rc<RefCell<Environment>>;
+
+/// A single scope of bindings, chained to the scope it was created in.
+#[derive(Debug, Default)]
+pub struct Environment {
+    parent: Option<Parent>,
+    values: HashMap<String, Value>,
+}
 
 impl Environment {
-    pub fn new(parent: Option<Parent>) {}
+    /// Creates a new environment. Lookups that fail fall back to the parent.
+    pub fn new(parent: Option<Parent>) -> Self {
+        Self {
+            parent,
+            values: HashMap::new(),
+        }
+    }
+
+    /// Creates a new environment wrapped in a handle.
+    pub fn new_shared(parent: Option<Parent>) -> Parent {
+        Rc::new(RefCell::new(Self::new(parent)))
+    }
+
+    /// Binds the value in this scope, shadowing any binding in a parent.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.into(), value);
+    }
+
+    /// Looks up the value, walking up through the parents.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.borrow().get(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_returns_none_when_undefined() {
+        let env = Environment::new(None);
+
+        assert_eq!(None, env.get("foo"));
+    }
+
+    #[test]
+    fn get_falls_back_to_parent() {
+        let parent = Environment::new_shared(None);
+        parent.borrow_mut().define("foo", Value::Number(1.0));
+        let child = Environment::new(Some(parent.clone()));
+
+        assert_eq!(Some(Value::Number(1.0)), child.get("foo"));
+
+        // Later definitions in the parent are seen too.
+        parent.borrow_mut().define("bar", Value::Number(2.0));
+        assert_eq!(Some(Value::Number(2.0)), child.get("bar"));
+    }
+
+    #[test]
+    fn define_shadows_parent() {
+        let parent = Environment::new_shared(None);
+        parent.borrow_mut().define("foo", Value::Number(1.0));
+        let mut child = Environment::new(Some(parent.clone()));
+        child.define("foo", Value::Number(2.0));
+
+        assert_eq!(Some(Value::Number(2.0)), child.get("foo"));
+        assert_eq!(Some(Value::Number(1.0)), parent.borrow().get("foo"));
+    }
 }
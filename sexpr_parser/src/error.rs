@@ -1,7 +1,89 @@
 use crate::location::Location;
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Error<Kind> {
     pub kind: Kind,
     pub location: Location,
 }
+
+impl<Kind: fmt::Display> Error<Kind> {
+    /// Returns the error followed by the line of source it occured on, with a caret under the column.
+    pub fn report(&self, source: &str) -> String {
+        let mut report = self.to_string();
+
+        let line = match self.location.line.checked_sub(1) {
+            Some(index) => source.lines().nth(index),
+            None => None,
+        };
+
+        if let Some(line) = line {
+            let number = self.location.line.to_string();
+            let gutter = " ".repeat(number.len());
+
+            // Keep tabs so the caret lines up with the source.
+            let indent: String = line
+                .chars()
+                .take(self.location.column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            report.push_str(&format!("\n{} | {}", number, line));
+            report.push_str(&format!("\n{} | {}^", gutter, indent));
+        }
+
+        report
+    }
+}
+
+/// Displayed as `path:line:column: message`, with the column counted from 1.
+impl<Kind: fmt::Display> fmt::Display for Error<Kind> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.location.path.display(),
+            self.location.line,
+            self.location.column + 1,
+            self.kind
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: usize, column: usize) -> Error<&'static str> {
+        Error {
+            kind: "oops",
+            location: Location {
+                line,
+                column,
+                path: "derpy.egg".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn report_points_at_column() {
+        let source = "(define a 1)\n(+ a\n   foo)";
+        let expected = "derpy.egg:3:4: oops\n3 |    foo)\n  |    ^";
+
+        assert_eq!(expected, error(3, 3).report(source));
+    }
+
+    #[test]
+    fn report_keeps_tabs() {
+        let source = "\t\t(foo)";
+        let expected = "derpy.egg:1:3: oops\n1 | \t\t(foo)\n  | \t\t^";
+
+        assert_eq!(expected, error(1, 2).report(source));
+    }
+
+    #[test]
+    fn report_skips_missing_lines() {
+        assert_eq!("derpy.egg:0:1: oops", error(0, 0).report("(foo)"));
+        assert_eq!("derpy.egg:5:1: oops", error(5, 0).report("(foo)"));
+    }
+}
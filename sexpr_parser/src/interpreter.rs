@@ -0,0 +1,878 @@
+use crate::environment::{Environment, Parent};
+use crate::error;
+use crate::parser::{Ast, Node};
+use std::{fmt, rc::Rc};
+
+pub type Err = error::Error<RuntimeErr>;
+
+/// How deeply evaluation can nest before it's stopped, so runaway recursion doesn't overflow the stack.
+/// Tail positions don't nest, so tail calls can loop any number of times.
+pub const MAX_DEPTH: usize = 512;
+
+/// An error that occured while evaluating.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErr {
+    /// The identifier isn't bound in any scope.
+    Undefined(String),
+    /// An empty list was evaluated.
+    EmptyCall,
+    NotCallable {
+        got: ValueType,
+    },
+    WrongArgCount {
+        expected: usize,
+        got: usize,
+    },
+    TooFewArgs {
+        minimum: usize,
+        got: usize,
+    },
+    WrongType {
+        expected: ValueType,
+        got: ValueType,
+    },
+    DivideByZero,
+    MalformedForm(SpecialForm),
+    TooDeep,
+}
+impl fmt::Display for RuntimeErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeErr::Undefined(name) => write!(f, "undefined identifier `{}`", name),
+            RuntimeErr::EmptyCall => write!(f, "can't call an empty list"),
+            RuntimeErr::NotCallable { got } => write!(f, "can't call a {}", got),
+            RuntimeErr::WrongArgCount { expected, got } => {
+                write!(f, "expected {} arguments but got {}", expected, got)
+            }
+            RuntimeErr::TooFewArgs { minimum, got } => {
+                write!(f, "expected at least {} arguments but got {}", minimum, got)
+            }
+            RuntimeErr::WrongType { expected, got } => {
+                write!(f, "expected a {} but got a {}", expected, got)
+            }
+            RuntimeErr::DivideByZero => write!(f, "divide by zero"),
+            RuntimeErr::MalformedForm(form) => write!(f, "malformed `{}`", form.name()),
+            RuntimeErr::TooDeep => write!(f, "evaluation nested deeper than {}", MAX_DEPTH),
+        }
+    }
+}
+
+/// Forms that are evaluated differently than a function call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecialForm {
+    Define,
+    If,
+    Lambda,
+    Let,
+    Quote,
+}
+impl SpecialForm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "define" => Some(Self::Define),
+            "if" => Some(Self::If),
+            "lambda" => Some(Self::Lambda),
+            "let" => Some(Self::Let),
+            "quote" => Some(Self::Quote),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Define => "define",
+            Self::If => "if",
+            Self::Lambda => "lambda",
+            Self::Let => "let",
+            Self::Quote => "quote",
+        }
+    }
+}
+
+/// The result of evaluating a node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Symbol(String),
+    List(Vec<Value>),
+    Lambda(Rc<Lambda>),
+    Builtin(Builtin),
+}
+impl Value {
+    /// Everything but `nil` and `false` is true.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Nil => ValueType::Nil,
+            Value::Bool(_) => ValueType::Bool,
+            Value::Number(_) => ValueType::Number,
+            Value::String(_) => ValueType::String,
+            Value::Symbol(_) => ValueType::Symbol,
+            Value::List(_) => ValueType::List,
+            Value::Lambda(_) | Value::Builtin(_) => ValueType::Function,
+        }
+    }
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Symbol(s) => write!(f, "{}", s),
+            Value::List(values) => {
+                write!(f, "(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Lambda(_) => write!(f, "<lambda>"),
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    Nil,
+    Bool,
+    Number,
+    String,
+    Symbol,
+    List,
+    Function,
+}
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Nil => "nil",
+            ValueType::Bool => "bool",
+            ValueType::Number => "number",
+            ValueType::String => "string",
+            ValueType::Symbol => "symbol",
+            ValueType::List => "list",
+            ValueType::Function => "function",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A function defined in code, along with the environment it closes over.
+pub struct Lambda {
+    params: Vec<String>,
+    body: Vec<Node>,
+    env: Parent,
+}
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The environment is skipped as it can contain the lambda itself.
+        f.debug_struct("Lambda")
+            .field("params", &self.params)
+            .finish()
+    }
+}
+impl Lambda {
+    /// Creates the scope of a call, with the arguments bound to the parameters.
+    fn bind(&self, args: Vec<Value>) -> Parent {
+        let env = Environment::new_shared(Some(self.env.clone()));
+        for (param, arg) in self.params.iter().zip(args) {
+            env.borrow_mut().define(param, arg);
+        }
+
+        env
+    }
+}
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// What's left of evaluating a node once everything but its tail position is done.
+enum Step<'a> {
+    Done(Value),
+    /// The node is evaluated in place of the one it's in.
+    Eval(&'a Node, Parent),
+    /// The lambda is called in place of the node.
+    Call(Rc<Lambda>, Vec<Value>),
+}
+
+type BuiltinFn = fn(&[Value]) -> Result<Value, RuntimeErr>;
+
+/// A function implemented in Rust.
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    name: &'static str,
+    function: BuiltinFn,
+}
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+const BUILTINS: &[(&str, BuiltinFn)] = &[
+    ("+", add),
+    ("-", subtract),
+    ("*", multiply),
+    ("/", divide),
+    ("=", equal),
+    ("<", |args| compare(args, |a, b| a < b)),
+    (">", |args| compare(args, |a, b| a > b)),
+    ("<=", |args| compare(args, |a, b| a <= b)),
+    (">=", |args| compare(args, |a, b| a >= b)),
+];
+
+/// A tree-walking interpreter over parsed nodes.
+pub struct Interpreter {
+    globals: Parent,
+    depth: usize,
+}
+impl Interpreter {
+    /// Creates a new interpreter with the builtins defined.
+    pub fn new() -> Self {
+        let globals = Environment::new_shared(None);
+        {
+            let mut env = globals.borrow_mut();
+            env.define("nil", Value::Nil);
+            env.define("true", Value::Bool(true));
+            env.define("false", Value::Bool(false));
+            for (name, function) in BUILTINS {
+                env.define(
+                    name,
+                    Value::Builtin(Builtin {
+                        name,
+                        function: *function,
+                    }),
+                );
+            }
+        }
+
+        Self { globals, depth: 0 }
+    }
+
+    /// Returns the global environment.
+    pub fn globals(&self) -> Parent {
+        self.globals.clone()
+    }
+
+    /// Evaluates the nodes in the global environment, returning the value of the last one.
+    /// Definitions are kept between runs.
+    pub fn run(&mut self, nodes: &[Node]) -> Result<Value, Err> {
+        let globals = self.globals.clone();
+        self.eval_body(&without_comments(nodes), &globals)
+    }
+
+    /// Evaluates the node in the given environment.
+    pub fn eval(&mut self, node: &Node, env: &Parent) -> Result<Value, Err> {
+        if self.depth >= MAX_DEPTH {
+            return Err(make_err(RuntimeErr::TooDeep, node));
+        }
+
+        self.depth += 1;
+        let result = self.eval_tail(node, env);
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Evaluates the node, looping through tail positions instead of nesting, so tail calls don't add depth.
+    fn eval_tail(&mut self, node: &Node, env: &Parent) -> Result<Value, Err> {
+        let mut call: Option<(Rc<Lambda>, Vec<Value>)> = None;
+        loop {
+            // The body borrows from the lambda, so both are kept until the call is done with.
+            let lambda;
+            let body: Vec<&Node>;
+            let mut step = match call.take() {
+                None => self.step(node, env)?,
+                Some((called, args)) => {
+                    lambda = called;
+                    body = lambda.body.iter().collect();
+                    self.step_body(&body, &lambda.bind(args))?
+                }
+            };
+
+            call = Some(loop {
+                step = match step {
+                    Step::Done(value) => return Ok(value),
+                    Step::Eval(node, env) => self.step(node, &env)?,
+                    Step::Call(lambda, args) => break (lambda, args),
+                };
+            });
+        }
+    }
+
+    /// Evaluates each node in turn, returning the value of the last one.
+    fn eval_body(&mut self, nodes: &[&Node], env: &Parent) -> Result<Value, Err> {
+        let mut value = Value::Nil;
+        for node in nodes {
+            value = self.eval(node, env)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Evaluates each node but the last, which is left as the tail position.
+    fn step_body<'a>(&mut self, nodes: &[&'a Node], env: &Parent) -> Result<Step<'a>, Err> {
+        let (last, rest) = match nodes.split_last() {
+            Some(split) => split,
+            None => return Ok(Step::Done(Value::Nil)),
+        };
+        for node in rest {
+            self.eval(node, env)?;
+        }
+
+        Ok(Step::Eval(last, env.clone()))
+    }
+
+    /// Evaluates the node up to its tail position.
+    fn step<'a>(&mut self, node: &'a Node, env: &Parent) -> Result<Step<'a>, Err> {
+        match &node.ast {
+            Ast::Comment(_) => Ok(Step::Done(Value::Nil)),
+            Ast::Number(n) => Ok(Step::Done(Value::Number(*n))),
+            Ast::String(s) => Ok(Step::Done(Value::String(s.clone()))),
+            Ast::Identifier(name) => env
+                .borrow()
+                .get(name)
+                .map(Step::Done)
+                .ok_or_else(|| make_err(RuntimeErr::Undefined(name.clone()), node)),
+            Ast::List(nodes) => {
+                let nodes = without_comments(nodes);
+                let (head, args) = nodes
+                    .split_first()
+                    .ok_or_else(|| make_err(RuntimeErr::EmptyCall, node))?;
+
+                if let Ast::Identifier(name) = &head.ast {
+                    if let Some(form) = SpecialForm::from_name(name) {
+                        return self.eval_form(form, node, args, env);
+                    }
+                }
+
+                let function = self.eval(head, env)?;
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg, env)?);
+                }
+
+                self.call(function, values, node)
+            }
+        }
+    }
+
+    /// Calls the function with the evaluated arguments. Lambdas are left to be called in tail position.
+    fn call<'a>(
+        &mut self,
+        function: Value,
+        args: Vec<Value>,
+        node: &Node,
+    ) -> Result<Step<'a>, Err> {
+        match function {
+            Value::Builtin(builtin) => (builtin.function)(&args)
+                .map(Step::Done)
+                .map_err(|kind| make_err(kind, node)),
+            Value::Lambda(lambda) => {
+                if lambda.params.len() != args.len() {
+                    return Err(make_err(
+                        RuntimeErr::WrongArgCount {
+                            expected: lambda.params.len(),
+                            got: args.len(),
+                        },
+                        node,
+                    ));
+                }
+
+                Ok(Step::Call(lambda, args))
+            }
+            value => Err(make_err(
+                RuntimeErr::NotCallable {
+                    got: value.value_type(),
+                },
+                node,
+            )),
+        }
+    }
+
+    fn eval_form<'a>(
+        &mut self,
+        form: SpecialForm,
+        node: &Node,
+        args: &[&'a Node],
+        env: &Parent,
+    ) -> Result<Step<'a>, Err> {
+        let malformed = || make_err(RuntimeErr::MalformedForm(form), node);
+
+        match form {
+            SpecialForm::Quote => match args {
+                [quoted] => Ok(Step::Done(quote(quoted))),
+                _ => Err(malformed()),
+            },
+            SpecialForm::If => match args {
+                [condition, then] => match self.eval(condition, env)?.is_truthy() {
+                    true => Ok(Step::Eval(then, env.clone())),
+                    false => Ok(Step::Done(Value::Nil)),
+                },
+                [condition, then, otherwise] => match self.eval(condition, env)?.is_truthy() {
+                    true => Ok(Step::Eval(then, env.clone())),
+                    false => Ok(Step::Eval(otherwise, env.clone())),
+                },
+                _ => Err(malformed()),
+            },
+            SpecialForm::Define => match args {
+                // (define name value)
+                [name, value] if matches!(name.ast, Ast::Identifier(_)) => {
+                    let name = identifier(name, form)?;
+                    let value = self.eval(value, env)?;
+                    env.borrow_mut().define(&name, value);
+                    Ok(Step::Done(Value::Nil))
+                }
+                // (define (name params...) body...)
+                [signature, body @ ..] if !body.is_empty() => {
+                    let names = identifiers(signature, form)?;
+                    let (name, params) = names.split_first().ok_or_else(malformed)?;
+                    let lambda = make_lambda(params.to_vec(), body, env);
+                    env.borrow_mut().define(name, lambda);
+                    Ok(Step::Done(Value::Nil))
+                }
+                _ => Err(malformed()),
+            },
+            SpecialForm::Lambda => match args {
+                [params, body @ ..] if !body.is_empty() => Ok(Step::Done(make_lambda(
+                    identifiers(params, form)?,
+                    body,
+                    env,
+                ))),
+                _ => Err(malformed()),
+            },
+            SpecialForm::Let => match args {
+                [bindings, body @ ..] if !body.is_empty() => {
+                    let bindings = match &bindings.ast {
+                        Ast::List(bindings) => bindings,
+                        _ => return Err(make_err(RuntimeErr::MalformedForm(form), bindings)),
+                    };
+
+                    // Bindings are evaluated in the outer scope, so they can't see each other.
+                    let scope = Environment::new_shared(Some(env.clone()));
+                    for binding in without_comments(bindings) {
+                        let (name, value) = match &binding.ast {
+                            Ast::List(pair) => match without_comments(pair)[..] {
+                                [name, value] => (identifier(name, form)?, value),
+                                _ => {
+                                    return Err(make_err(RuntimeErr::MalformedForm(form), binding))
+                                }
+                            },
+                            _ => return Err(make_err(RuntimeErr::MalformedForm(form), binding)),
+                        };
+
+                        let value = self.eval(value, env)?;
+                        scope.borrow_mut().define(&name, value);
+                    }
+
+                    self.step_body(body, &scope)
+                }
+                _ => Err(malformed()),
+            },
+        }
+    }
+}
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates an error located at the node.
+fn make_err(kind: RuntimeErr, node: &Node) -> Err {
+    error::Error {
+        kind,
+        location: node.location(),
+    }
+}
+
+fn without_comments(nodes: &[Node]) -> Vec<&Node> {
+    nodes
+        .iter()
+        .filter(|node| !matches!(node.ast, Ast::Comment(_)))
+        .collect()
+}
+
+fn make_lambda(params: Vec<String>, body: &[&Node], env: &Parent) -> Value {
+    Value::Lambda(Rc::new(Lambda {
+        params,
+        body: body.iter().map(|node| (*node).clone()).collect(),
+        env: env.clone(),
+    }))
+}
+
+/// Returns the name of an identifier node.
+fn identifier(node: &Node, form: SpecialForm) -> Result<String, Err> {
+    match &node.ast {
+        Ast::Identifier(name) => Ok(name.clone()),
+        _ => Err(make_err(RuntimeErr::MalformedForm(form), node)),
+    }
+}
+
+/// Returns the names of a list of identifiers.
+fn identifiers(node: &Node, form: SpecialForm) -> Result<Vec<String>, Err> {
+    match &node.ast {
+        Ast::List(nodes) => without_comments(nodes)
+            .into_iter()
+            .map(|node| identifier(node, form))
+            .collect(),
+        _ => Err(make_err(RuntimeErr::MalformedForm(form), node)),
+    }
+}
+
+/// Converts the node to a value without evaluating it.
+fn quote(node: &Node) -> Value {
+    match &node.ast {
+        Ast::Comment(_) => Value::Nil,
+        Ast::Number(n) => Value::Number(*n),
+        Ast::String(s) => Value::String(s.clone()),
+        Ast::Identifier(name) => Value::Symbol(name.clone()),
+        Ast::List(nodes) => Value::List(without_comments(nodes).into_iter().map(quote).collect()),
+    }
+}
+
+fn numbers(args: &[Value], minimum: usize) -> Result<Vec<f64>, RuntimeErr> {
+    if args.len() < minimum {
+        return Err(RuntimeErr::TooFewArgs {
+            minimum,
+            got: args.len(),
+        });
+    }
+
+    args.iter()
+        .map(|arg| match arg {
+            Value::Number(n) => Ok(*n),
+            _ => Err(RuntimeErr::WrongType {
+                expected: ValueType::Number,
+                got: arg.value_type(),
+            }),
+        })
+        .collect()
+}
+
+fn add(args: &[Value]) -> Result<Value, RuntimeErr> {
+    Ok(Value::Number(numbers(args, 0)?.iter().sum()))
+}
+
+fn multiply(args: &[Value]) -> Result<Value, RuntimeErr> {
+    Ok(Value::Number(numbers(args, 0)?.iter().product()))
+}
+
+/// Subtracts the rest from the first, or negates a single number.
+fn subtract(args: &[Value]) -> Result<Value, RuntimeErr> {
+    let n = numbers(args, 1)?;
+    match n[..] {
+        [only] => Ok(Value::Number(-only)),
+        _ => Ok(Value::Number(n[0] - n[1..].iter().sum::<f64>())),
+    }
+}
+
+/// Divides the first by the rest, or takes the reciprocal of a single number.
+fn divide(args: &[Value]) -> Result<Value, RuntimeErr> {
+    let n = numbers(args, 1)?;
+    let (first, divisors) = match n[..] {
+        [_] => (1.0, &n[..]),
+        _ => (n[0], &n[1..]),
+    };
+
+    divisors
+        .iter()
+        .try_fold(first, |total, divisor| {
+            if *divisor == 0.0 {
+                Err(RuntimeErr::DivideByZero)
+            } else {
+                Ok(total / divisor)
+            }
+        })
+        .map(Value::Number)
+}
+
+/// Returns whether all values are equal. Works on any type.
+fn equal(args: &[Value]) -> Result<Value, RuntimeErr> {
+    if args.is_empty() {
+        return Err(RuntimeErr::TooFewArgs { minimum: 1, got: 0 });
+    }
+
+    Ok(Value::Bool(args.windows(2).all(|pair| pair[0] == pair[1])))
+}
+
+/// Returns whether each number compares to the next.
+fn compare(args: &[Value], comparison: fn(f64, f64) -> bool) -> Result<Value, RuntimeErr> {
+    let n = numbers(args, 1)?;
+    Ok(Value::Bool(
+        n.windows(2).all(|pair| comparison(pair[0], pair[1])),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::location::Location;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    use super::*;
+
+    fn run(contents: &str) -> Result<Value, Err> {
+        let path: PathBuf = "derpy".into();
+        let tokens = Tokenizer::tokenize(contents, path).unwrap();
+        let nodes = Parser::parse(tokens).unwrap();
+
+        Interpreter::new().run(&nodes)
+    }
+
+    fn location(line: usize, column: usize) -> Location {
+        Location {
+            line,
+            column,
+            path: "derpy".into(),
+        }
+    }
+
+    #[test]
+    fn run_returns_nil_when_empty() {
+        assert_eq!(Value::Nil, run("").unwrap());
+        assert_eq!(Value::Nil, run(";; Nothing here").unwrap());
+    }
+
+    #[test]
+    fn run_returns_last_value() {
+        assert_eq!(Value::String("foo".into()), run("1 2 \"foo\"").unwrap());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(Value::Number(15.0), run("(+ 1 2 (* 3 4))").unwrap());
+        assert_eq!(Value::Number(0.0), run("(+)").unwrap());
+        assert_eq!(Value::Number(-5.0), run("(- 5)").unwrap());
+        assert_eq!(Value::Number(4.0), run("(- 10 4 2)").unwrap());
+        assert_eq!(Value::Number(2.5), run("(/ 10 2 2)").unwrap());
+        assert_eq!(Value::Number(0.5), run("(/ 2)").unwrap());
+    }
+
+    #[test]
+    fn arithmetic_errors_are_located_at_the_call() {
+        let expected = error::Error {
+            kind: RuntimeErr::DivideByZero,
+            location: location(2, 4),
+        };
+        assert_eq!(expected, run("(+ 1\n    (/ 1 0))").unwrap_err());
+
+        let expected = error::Error {
+            kind: RuntimeErr::WrongType {
+                expected: ValueType::Number,
+                got: ValueType::String,
+            },
+            location: location(1, 0),
+        };
+        assert_eq!(expected, run("(+ 1 \"foo\")").unwrap_err());
+
+        let expected = error::Error {
+            kind: RuntimeErr::TooFewArgs { minimum: 1, got: 0 },
+            location: location(1, 0),
+        };
+        assert_eq!(expected, run("(-)").unwrap_err());
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(Value::Bool(true), run("(< 1 2 3)").unwrap());
+        assert_eq!(Value::Bool(false), run("(< 1 3 2)").unwrap());
+        assert_eq!(Value::Bool(true), run("(>= 3 3 1)").unwrap());
+        assert_eq!(Value::Bool(true), run("(= 2 (+ 1 1))").unwrap());
+        assert_eq!(Value::Bool(true), run("(= \"a\" \"a\")").unwrap());
+        assert_eq!(Value::Bool(false), run("(= (quote a) (quote b))").unwrap());
+    }
+
+    #[test]
+    fn undefined_identifier_is_located() {
+        let expected = error::Error {
+            kind: RuntimeErr::Undefined("foo".into()),
+            location: location(3, 2),
+        };
+
+        assert_eq!(expected, run("(+ 1\n  ;; Comment\n  foo)").unwrap_err());
+    }
+
+    #[test]
+    fn define_binds_globally() {
+        assert_eq!(
+            Value::Number(3.0),
+            run("(define x 1) (define y 2) (+ x y)").unwrap()
+        );
+    }
+
+    #[test]
+    fn definitions_are_kept_between_runs() {
+        let path: PathBuf = "derpy".into();
+        let mut interpreter = Interpreter::new();
+        let mut run = |contents: &str| {
+            let tokens = Tokenizer::tokenize(contents, path.clone()).unwrap();
+            interpreter.run(&Parser::parse(tokens).unwrap())
+        };
+
+        run("(define x 2)").unwrap();
+        assert_eq!(Value::Number(4.0), run("(* x x)").unwrap());
+    }
+
+    #[test]
+    fn recursive_functions() {
+        let contents = "
+            (define (fact n)
+                ;; Recursion through the global scope
+                (if (<= n 1)
+                    1
+                    (* n (fact (- n 1)))))
+            (fact 5)";
+
+        assert_eq!(Value::Number(120.0), run(contents).unwrap());
+    }
+
+    #[test]
+    fn lambdas_close_over_their_scope() {
+        let contents = "
+            (define (make-adder n) (lambda (x) (+ x n)))
+            (define add-two (make-adder 2))
+            (define n 100)
+            (add-two 3)";
+
+        assert_eq!(Value::Number(5.0), run(contents).unwrap());
+        assert_eq!(Value::Number(7.0), run("((lambda (a b) a b) 6 7)").unwrap());
+    }
+
+    #[test]
+    fn lambda_arguments_are_checked() {
+        let expected = error::Error {
+            kind: RuntimeErr::WrongArgCount {
+                expected: 1,
+                got: 2,
+            },
+            location: location(1, 24),
+        };
+
+        assert_eq!(
+            expected,
+            run("(define (f x) x)        (f 1 2)").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn let_creates_a_scope() {
+        assert_eq!(
+            Value::Number(3.0),
+            run("(let ((a 1) (b 2)) (+ a b))").unwrap()
+        );
+
+        // Bindings shadow, but don't leak out.
+        let contents = "
+            (define a 10)
+            (define b (let ((a 1)) a))
+            (+ a b)";
+        assert_eq!(Value::Number(11.0), run(contents).unwrap());
+
+        // Bindings are evaluated in the outer scope.
+        let contents = "
+            (define a 10)
+            (let ((a 1) (b a)) b)";
+        assert_eq!(Value::Number(10.0), run(contents).unwrap());
+    }
+
+    #[test]
+    fn if_uses_truthiness() {
+        assert_eq!(Value::Number(1.0), run("(if 0 1 2)").unwrap());
+        assert_eq!(Value::Number(2.0), run("(if false 1 2)").unwrap());
+        assert_eq!(Value::Number(2.0), run("(if nil 1 2)").unwrap());
+        assert_eq!(Value::Nil, run("(if false 1)").unwrap());
+
+        // Only the taken branch is evaluated.
+        assert_eq!(Value::Number(1.0), run("(if true 1 undefined)").unwrap());
+    }
+
+    #[test]
+    fn quote_returns_unevaluated() {
+        let expected = Value::List(vec![
+            Value::Symbol("foo".into()),
+            Value::Number(1.0),
+            Value::List(vec![Value::String("bar".into())]),
+        ]);
+
+        assert_eq!(expected, run("(quote (foo 1 (\"bar\")))").unwrap());
+        assert_eq!("(foo 1 (\"bar\"))", format!("{}", expected));
+    }
+
+    #[test]
+    fn malformed_forms_are_located() {
+        let expected = error::Error {
+            kind: RuntimeErr::MalformedForm(SpecialForm::Lambda),
+            location: location(1, 9),
+        };
+        assert_eq!(expected, run("(lambda (1) 2)").unwrap_err());
+
+        let expected = error::Error {
+            kind: RuntimeErr::MalformedForm(SpecialForm::Let),
+            location: location(1, 6),
+        };
+        assert_eq!(expected, run("(let ((a)) a)").unwrap_err());
+
+        let expected = error::Error {
+            kind: RuntimeErr::MalformedForm(SpecialForm::If),
+            location: location(1, 0),
+        };
+        assert_eq!(expected, run("(if)").unwrap_err());
+    }
+
+    #[test]
+    fn calling_non_functions_errs() {
+        let expected = error::Error {
+            kind: RuntimeErr::NotCallable {
+                got: ValueType::Number,
+            },
+            location: location(1, 0),
+        };
+        assert_eq!(expected, run("(1 2)").unwrap_err());
+
+        let expected = error::Error {
+            kind: RuntimeErr::EmptyCall,
+            location: location(1, 0),
+        };
+        assert_eq!(expected, run("()").unwrap_err());
+    }
+
+    #[test]
+    fn runaway_recursion_errs() {
+        let actual = run("(define (f x) (+ 1 (f x))) (f 1)").unwrap_err();
+
+        assert_eq!(RuntimeErr::TooDeep, actual.kind);
+    }
+
+    #[test]
+    fn tail_calls_dont_nest() {
+        let contents = "
+            (define (loop n) (if (= n 0) 0 (loop (- n 1))))
+            (loop 10000)";
+        assert_eq!(Value::Number(0.0), run(contents).unwrap());
+
+        // The last form of a let or lambda body is a tail position too.
+        let contents = "
+            (define (count n total)
+                (let ((next (- n 1)))
+                    total
+                    (if (= n 0) total (count next (+ total 1)))))
+            ((lambda (n) n (count n 0)) 10000)";
+        assert_eq!(Value::Number(10000.0), run(contents).unwrap());
+    }
+}
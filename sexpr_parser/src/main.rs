@@ -2,30 +2,96 @@ pub mod backends;
 pub mod environment;
 pub mod error;
 pub mod intermediate_representation;
+pub mod interpreter;
 pub mod location;
 pub mod parser;
+pub mod repl;
 pub mod tokenizer;
 
 use benchy::Benchy;
-use std::{fs, path::PathBuf};
+use clap::{Parser, Subcommand};
+use interpreter::{Interpreter, Value};
+use std::{fs, io, path::PathBuf, process::ExitCode};
 
-fn main() {
-    {
-        Benchy::time("read_file");
+/// Tools for working with `.egg` files.
+#[derive(Parser)]
+#[clap(version, about)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the tokens in a file.
+    Tokens { path: PathBuf },
+    /// Prints the parsed nodes in a file.
+    Ast { path: PathBuf },
+    /// Evaluates a file, printing the value of the last expression.
+    Run { path: PathBuf },
+    /// Evaluates input line by line. Lists can span lines.
+    Repl,
+}
 
-        let path: PathBuf = "../test.egg".into();
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Tokens { path } => read_file(&path).and_then(|contents| {
+            for token in tokenize(&contents, path)? {
+                let location = &token.location;
+                println!("{}:{} {:?}", location.line, location.column + 1, token.kind);
+            }
+            Ok(())
+        }),
+        Command::Ast { path } => read_file(&path).and_then(|contents| {
+            println!("{:#?}", parse(&contents, path)?);
+            Ok(())
+        }),
+        Command::Run { path } => read_file(&path).and_then(|contents| {
+            match evaluate(&mut Interpreter::new(), &contents, path)? {
+                Value::Nil => {}
+                value => println!("{}", value),
+            }
+            Ok(())
+        }),
+        Command::Repl => {
+            repl::run(io::stdin().lock(), io::stdout()).map_err(|e| format!("repl: {}", e))
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(report) => {
+            eprintln!("{}", report);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-        let contents = fs::read_to_string(&path).expect("Something went wrong reading the file");
-        println!("{}", contents);
+/// Reads the file, returning a printable error if it can't be.
+fn read_file(path: &PathBuf) -> Result<String, String> {
+    Benchy::time("read_file");
 
-        println!("\n\n\n");
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
 
-        let tokens = tokenizer::Tokenizer::tokenize(&contents, path).unwrap();
+/// Tokenizes the source. Errors are reported against the source.
+fn tokenize(source: &str, path: PathBuf) -> Result<Vec<tokenizer::Token>, String> {
+    tokenizer::Tokenizer::tokenize(source, path).map_err(|e| e.report(source))
+}
 
-        let nodes = parser::Parser::parse(tokens).unwrap();
+/// Parses the source. Errors are reported against the source.
+fn parse(source: &str, path: PathBuf) -> Result<Vec<parser::Node>, String> {
+    parser::Parser::parse(tokenize(source, path)?).map_err(|e| e.report(source))
+}
 
-        println!("{:#?}", nodes);
-    }
+/// Parses and evaluates the source. Errors are reported against the source.
+pub fn evaluate(
+    interpreter: &mut Interpreter,
+    source: &str,
+    path: PathBuf,
+) -> Result<Value, String> {
+    let nodes = parse(source, path)?;
+    interpreter.run(&nodes).map_err(|e| e.report(source))
 }
 
 fn save_benchmarks() {
@@ -1,6 +1,7 @@
 use crate::error;
 use crate::location::Location;
 use crate::tokenizer::{Token, TokenKind};
+use std::fmt;
 
 pub type Err = error::Error<ParserErr>;
 
@@ -15,11 +16,27 @@ pub enum ListErr {
     UnstartedList,
 }
 
+impl fmt::Display for ParserErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserErr::List(ListErr::UnclosedList) => write!(f, "unclosed list"),
+            ParserErr::List(ListErr::UnstartedList) => write!(f, "unexpected `)`"),
+            ParserErr::StackUnderflow => write!(f, "parser stack underflow"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Node {
     pub ast: Ast,
     pub tokens: Vec<Token>,
 }
+impl Node {
+    /// Returns where the node starts.
+    pub fn location(&self) -> Location {
+        self.tokens[0].location.clone()
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ast {
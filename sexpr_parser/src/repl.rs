@@ -0,0 +1,178 @@
+use crate::interpreter::{Interpreter, Value};
+use crate::parser::Parser;
+use crate::tokenizer::{Tokenizer, COMMENT, ESCAPE_CHARACTER, NEW_LINE, QUOTE};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+pub const PROMPT: &str = "> ";
+/// Shown while waiting for the rest of an unbalanced input.
+pub const CONTINUATION_PROMPT: &str = ".. ";
+/// The path errors in the REPL are reported against.
+pub const REPL_PATH: &str = "<repl>";
+
+/// Collects lines of input until every list is closed.
+#[derive(Debug, Default)]
+pub struct InputBuffer {
+    contents: String,
+}
+
+impl InputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether no lines are waiting to be completed.
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    /// Adds a line, returning all of the buffered input once the parentheses balance.
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        self.contents.push_str(line);
+        self.contents.push(NEW_LINE);
+
+        if is_complete(&self.contents) {
+            Some(std::mem::take(&mut self.contents))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns whether every list and string in the input is closed, skipping comments.
+/// Extra closing parentheses count as complete so the parser can report them.
+fn is_complete(input: &str) -> bool {
+    let mut depth: i64 = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut prev_char = None;
+
+    for c in input.chars() {
+        if in_comment {
+            in_comment = c != NEW_LINE;
+        } else if in_string {
+            // Matches the tokenizer, which only looks at the previous character for escapes.
+            in_string = !(c == QUOTE && prev_char != Some(ESCAPE_CHARACTER));
+        } else {
+            match c {
+                QUOTE => in_string = true,
+                COMMENT => in_comment = true,
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        prev_char = Some(c);
+    }
+
+    depth <= 0 && !in_string
+}
+
+/// Evaluates one input, which starts on the given line of the session.
+/// Errors are reported against the whole session, since functions from earlier inputs can fail too.
+fn evaluate(
+    interpreter: &mut Interpreter,
+    session: &str,
+    input: &str,
+    line: usize,
+) -> Result<Value, String> {
+    let tokens = Tokenizer::tokenize_from_line(input, PathBuf::from(REPL_PATH), line)
+        .map_err(|e| e.report(session))?;
+    let nodes = Parser::parse(tokens).map_err(|e| e.report(session))?;
+    interpreter.run(&nodes).map_err(|e| e.report(session))
+}
+
+/// Reads input until it runs out, evaluating each complete expression and printing the result.
+/// Definitions are kept between inputs, and lines are numbered from the start of the session.
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut interpreter = Interpreter::new();
+    let mut buffer = InputBuffer::new();
+    // Every input so far, which errors may point anywhere in.
+    let mut session = String::new();
+    let mut line_count = 0;
+
+    write!(output, "{}", PROMPT)?;
+    output.flush()?;
+
+    for line in input.lines() {
+        if let Some(source) = buffer.push_line(&line?) {
+            let first_line = line_count + 1;
+            line_count += source.lines().count();
+            session.push_str(&source);
+
+            if !source.trim().is_empty() {
+                match evaluate(&mut interpreter, &session, &source, first_line) {
+                    Ok(Value::Nil) => {}
+                    Ok(value) => writeln!(output, "{}", value)?,
+                    Err(report) => writeln!(output, "{}", report)?,
+                }
+            }
+        }
+
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        write!(output, "{}", prompt)?;
+        output.flush()?;
+    }
+
+    writeln!(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_line_waits_for_lists_to_close() {
+        let mut buffer = InputBuffer::new();
+
+        assert_eq!(None, buffer.push_line("(define (f x)"));
+        assert!(!buffer.is_empty());
+        assert_eq!(None, buffer.push_line("  (+ x 1)"));
+        assert_eq!(
+            Some("(define (f x)\n  (+ x 1)\n)\n".into()),
+            buffer.push_line(")")
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn is_complete_ignores_strings_and_comments() {
+        assert!(is_complete("foo"));
+        assert!(is_complete("(foo \")\")"));
+        assert!(!is_complete("(foo \"(\""));
+        assert!(!is_complete("(foo \"\\\")\""));
+        assert!(!is_complete("(foo ;; )\n"));
+        assert!(is_complete("(foo ;; (\n)"));
+        assert!(!is_complete("\"unclosed"));
+        assert!(is_complete("())"));
+    }
+
+    #[test]
+    fn run_evaluates_complete_inputs() {
+        let input = "(define (square x)\n  (* x x))\n(square\n 3)\n(undefined)\n";
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let expected = "> .. > .. 9\n\
+            > <repl>:5:2: undefined identifier `undefined`\n\
+            5 | (undefined)\n  |  ^\n\
+            > \n";
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+    }
+    #[test]
+    fn run_reports_errors_in_earlier_inputs() {
+        let input = "(define (f x)\n  (+ x undefined))\n\n(f 1)\n";
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let expected = "> .. > > <repl>:2:8: undefined identifier `undefined`\n\
+            2 |   (+ x undefined))\n  |        ^\n\
+            > \n";
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+    }
+}
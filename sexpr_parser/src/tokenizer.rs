@@ -1,6 +1,6 @@
 use crate::{error, location::Location};
 use benchy::Benchy;
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
 pub type Err = error::Error<TokenErr>;
 pub type Success = Vec<Token>;
@@ -82,6 +82,26 @@ pub enum TypeErr {
     WrongType { got: State, expected: TokenType },
 }
 
+impl fmt::Display for TokenErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenErr::Comment(CommentErr::NotStarted) => write!(f, "comment was never started"),
+            TokenErr::String(StringErr::NotStarted) => write!(f, "string was never started"),
+            TokenErr::String(StringErr::Unclosed(_)) => write!(f, "unclosed string"),
+            TokenErr::Type(TypeErr::WrongType { expected, .. }) => {
+                write!(f, "expected to be making {:?} token", expected)
+            }
+            TokenErr::Identifier(IdentifierErr::NotStarted) => {
+                write!(f, "identifier was never started")
+            }
+            TokenErr::Identifier(IdentifierErr::BeginsWithNumber { got }) => {
+                write!(f, "identifier `{}` begins with a number", got)
+            }
+            TokenErr::StackUnderflow => write!(f, "tokenizer stack underflow"),
+        }
+    }
+}
+
 /// State for tokenizer.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Tokenizer {
@@ -105,9 +125,15 @@ fn is_symbol(c: char) -> bool {
 impl Tokenizer {
     /// tokenize the given contents into a series of tokens.
     pub fn tokenize<'a>(contents: &'a str, path: PathBuf) -> Result<Success, Err> {
+        Self::tokenize_from_line(contents, path, 1)
+    }
+
+    /// Tokenizes contents that start on the given line of a larger source, such as one input to the REPL.
+    pub fn tokenize_from_line(contents: &str, path: PathBuf, line: usize) -> Result<Success, Err> {
         Benchy::time("Tokenizer::tokenize");
 
         let mut tokenizer = Self::load(contents, path);
+        tokenizer.location.line = line;
 
         let mut prev_char = None;
         while let Some(c) = tokenizer.next_character() {